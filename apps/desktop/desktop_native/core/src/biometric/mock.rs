@@ -0,0 +1,182 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use base64::{engine::general_purpose::STANDARD as base64_engine, Engine};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    biometric::{KeyMaterial, OsDerivedKey},
+    crypto::SecretString,
+    error::{Error, Result},
+};
+
+use super::{get_decrypted_secret, set_encrypted_secret};
+
+static AVAILABLE: AtomicBool = AtomicBool::new(true);
+static AUTHORIZED: AtomicBool = AtomicBool::new(true);
+
+/// A biometric implementation which doesn't prompt the user, to test the code unlocking with
+/// biometrics. The secrets are stored through [`crate::password`] like on the other platforms.
+pub struct MockBiometric {}
+
+impl MockBiometric {
+    /// Whether biometric unlock is reported as available, `true` by default.
+    pub fn set_available(available: bool) {
+        AVAILABLE.store(available, Ordering::SeqCst);
+    }
+
+    /// Whether the user passes the prompts, `true` by default.
+    pub fn set_authorized(authorized: bool) {
+        AUTHORIZED.store(authorized, Ordering::SeqCst);
+    }
+}
+
+impl super::BiometricTrait for MockBiometric {
+    async fn prompt(_hwnd: Vec<u8>, _message: String) -> Result<bool> {
+        Ok(AUTHORIZED.load(Ordering::SeqCst))
+    }
+
+    async fn available() -> Result<bool> {
+        Ok(AVAILABLE.load(Ordering::SeqCst))
+    }
+
+    fn derive_key_material(challenge_str: Option<&str>) -> Result<OsDerivedKey> {
+        let challenge: [u8; 16] = match challenge_str {
+            Some(challenge_str) => base64_engine
+                .decode(challenge_str)
+                .map_err(|e| Error::InvalidInput(e.to_string()))?
+                .try_into()
+                .map_err(|e: Vec<_>| {
+                    Error::InvalidInput(format!("Expect length {}, got {}", 16, e.len()))
+                })?,
+            None => [0u8; 16],
+        };
+
+        // Deterministic, so that the tests can derive the same key again
+        let key = Sha256::digest(challenge);
+        Ok(OsDerivedKey {
+            key_b64: base64_engine.encode(key),
+            iv_b64: base64_engine.encode(challenge),
+        })
+    }
+
+    async fn set_biometric_secret(
+        service: &str,
+        account: &str,
+        secret: &str,
+        key_material: Option<KeyMaterial>,
+        iv_b64: &str,
+    ) -> Result<String> {
+        let key_material = key_material.ok_or(Error::InvalidInput(
+            "Key material is required for mock protected keys".to_string(),
+        ))?;
+
+        set_encrypted_secret(service, account, secret, &key_material, iv_b64).await
+    }
+
+    async fn get_biometric_secret(
+        service: &str,
+        account: &str,
+        key_material: Option<KeyMaterial>,
+    ) -> Result<SecretString> {
+        let key_material = key_material.ok_or(Error::InvalidInput(
+            "Key material is required for mock protected keys".to_string(),
+        ))?;
+
+        get_decrypted_secret(service, account, &key_material, false).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+    use crate::biometric::BiometricTrait;
+
+    fn key_material() -> KeyMaterial {
+        let os_key = MockBiometric::derive_key_material(None).unwrap();
+        KeyMaterial {
+            os_key_part_b64: os_key.key_b64,
+            client_key_part_b64: Some("AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=".to_owned()),
+        }
+    }
+
+    #[test]
+    fn prompt_and_available() {
+        futures::executor::block_on(async {
+            assert!(MockBiometric::prompt(Vec::new(), String::new())
+                .await
+                .unwrap());
+            assert!(MockBiometric::available().await.unwrap());
+
+            MockBiometric::set_authorized(false);
+            MockBiometric::set_available(false);
+            assert!(!MockBiometric::prompt(Vec::new(), String::new())
+                .await
+                .unwrap());
+            assert!(!MockBiometric::available().await.unwrap());
+
+            MockBiometric::set_authorized(true);
+            MockBiometric::set_available(true);
+        })
+    }
+
+    #[test]
+    fn set_get_biometric_secret() {
+        let _backend = crate::password::use_memory_backend();
+
+        let iv_b64 = MockBiometric::derive_key_material(None).unwrap().iv_b64;
+        let encrypted = block_on(MockBiometric::set_biometric_secret(
+            "BitwardenTestMock",
+            "BitwardenTest",
+            "secret",
+            Some(key_material()),
+            &iv_b64,
+        ))
+        .unwrap();
+        assert_eq!(crate::biometric::stored_iv(&encrypted).unwrap(), iv_b64);
+        assert_eq!(
+            encrypted,
+            &*block_on(crate::password::get_password(
+                "BitwardenTestMock",
+                "BitwardenTest"
+            ))
+            .unwrap()
+        );
+        assert_eq!(
+            "secret",
+            &*block_on(MockBiometric::get_biometric_secret(
+                "BitwardenTestMock",
+                "BitwardenTest",
+                Some(key_material())
+            ))
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn set_biometric_secret_requires_iv() {
+        assert!(matches!(
+            block_on(MockBiometric::set_biometric_secret(
+                "BitwardenTestMock",
+                "BitwardenTest",
+                "secret",
+                Some(key_material()),
+                "",
+            )),
+            Err(Error::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn get_biometric_secret_requires_key() {
+        assert!(matches!(
+            block_on(MockBiometric::get_biometric_secret(
+                "BitwardenTestMock",
+                "BitwardenTest",
+                None
+            )),
+            Err(Error::InvalidInput(_))
+        ));
+    }
+}
@@ -1,16 +1,23 @@
-use aes::cipher::generic_array::GenericArray;
-use anyhow::{anyhow, Result};
-
 #[cfg_attr(target_os = "linux", path = "unix.rs")]
 #[cfg_attr(target_os = "windows", path = "windows.rs")]
 #[cfg_attr(target_os = "macos", path = "macos.rs")]
 mod biometric;
+#[cfg(any(test, feature = "mock"))]
+mod mock;
+
+use std::{fmt::Display, str::FromStr};
 
 use base64::{engine::general_purpose::STANDARD as base64_engine, Engine};
 pub use biometric::Biometric;
+use log::warn;
+#[cfg(any(test, feature = "mock"))]
+pub use mock::MockBiometric;
 use sha2::{Digest, Sha256};
 
-use crate::crypto::{self, CipherString};
+use crate::{
+    crypto::{self, CipherString, SecretString, SymmetricKey},
+    error::{CryptoError, Error, Result},
+};
 
 pub struct KeyMaterial {
     pub os_key_part_b64: String,
@@ -28,41 +35,269 @@ pub trait BiometricTrait {
     #[allow(async_fn_in_trait)]
     async fn available() -> Result<bool>;
     fn derive_key_material(secret: Option<&str>) -> Result<OsDerivedKey>;
-    fn set_biometric_secret(
+    /// Encrypt and store the secret, returning the stored value.
+    ///
+    /// `iv_b64` must be the 16 byte challenge the os key part of `key_material` was derived from,
+    /// see [`OsDerivedKey::iv_b64`]. It is stored with the secret, and read back with
+    /// [`stored_iv`], to derive the same key material again.
+    #[allow(async_fn_in_trait)]
+    async fn set_biometric_secret(
         service: &str,
         account: &str,
         secret: &str,
         key_material: Option<KeyMaterial>,
         iv_b64: &str,
     ) -> Result<String>;
-    fn get_biometric_secret(
+    #[allow(async_fn_in_trait)]
+    async fn get_biometric_secret(
         service: &str,
         account: &str,
         key_material: Option<KeyMaterial>,
-    ) -> Result<String>;
+    ) -> Result<SecretString>;
 }
 
-fn encrypt(secret: &str, key_material: &KeyMaterial, iv_b64: &str) -> Result<String> {
-    let iv = base64_engine
-        .decode(iv_b64)?
+/// A biometric secret as it is persisted in the password store.
+///
+/// Legacy secrets are a bare `AesCbc256_B64` or `AesCbc256_HmacSha256_B64` cipher string
+/// encrypted with [`KeyMaterial::derive_key`], their iv is the challenge the os key part was
+/// derived from. `AesCbc256_HmacSha256_B64` secrets are still written by
+/// [`encrypt_authenticated`]. Current secrets are stored as
+/// `<version>$<iv_b64>$<cipher string>`, with an `AesGcm256_B64` cipher string encrypted with
+/// [`KeyMaterial::derive_salted_key`] salted with the challenge.
+enum StoredSecret {
+    Legacy(CipherString),
+    V1 { iv: [u8; 16], secret: CipherString },
+}
+
+fn decode_b64<const N: usize>(value_b64: &str) -> Result<[u8; N]> {
+    base64_engine
+        .decode(value_b64)
+        .map_err(|e| Error::InvalidInput(e.to_string()))?
         .try_into()
-        .map_err(|e: Vec<_>| anyhow!("Expected length {}, got {}", 16, e.len()))?;
+        .map_err(|e: Vec<_>| Error::InvalidInput(format!("Expected length {}, got {}", N, e.len())))
+}
+
+impl FromStr for StoredSecret {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.splitn(3, '$').collect::<Vec<_>>()[..] {
+            [secret] => match CipherString::from_str(secret)? {
+                secret @ (CipherString::AesCbc256_B64 { .. }
+                | CipherString::AesCbc256_HmacSha256_B64 { .. }) => {
+                    Ok(StoredSecret::Legacy(secret))
+                }
+                _ => Err(CryptoError::InvalidEncType.into()),
+            },
+            ["1", iv_b64, secret] => match CipherString::from_str(secret)? {
+                secret @ CipherString::AesGcm256_B64 { .. } => Ok(StoredSecret::V1 {
+                    iv: decode_b64(iv_b64)?,
+                    secret,
+                }),
+                _ => Err(CryptoError::InvalidEncType.into()),
+            },
+            [version, ..] => Err(Error::Unsupported(format!(
+                "Unsupported key derivation version {}",
+                version
+            ))),
+            [] => Err(Error::InvalidInput("Invalid stored secret".to_string())),
+        }
+    }
+}
+
+impl Display for StoredSecret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StoredSecret::Legacy(secret) => write!(f, "{}", secret),
+            StoredSecret::V1 { iv, secret } => {
+                write!(f, "1${}${}", base64_engine.encode(iv), secret)
+            }
+        }
+    }
+}
+
+impl StoredSecret {
+    /// Whether the secret is authenticated, unauthenticated secrets are migrated when read.
+    fn is_current(&self) -> bool {
+        !matches!(
+            self,
+            StoredSecret::Legacy(CipherString::AesCbc256_B64 { .. })
+        )
+    }
+
+    /// The challenge the os key part of the key material was derived from.
+    fn iv(&self) -> Result<[u8; 16]> {
+        match self {
+            StoredSecret::Legacy(CipherString::AesCbc256_B64 { iv, .. })
+            | StoredSecret::Legacy(CipherString::AesCbc256_HmacSha256_B64 { iv, .. })
+            | StoredSecret::V1 { iv, .. } => Ok(*iv),
+            StoredSecret::Legacy(_) => Err(CryptoError::InvalidEncType.into()),
+        }
+    }
+}
+
+/// Read the base64 encoded iv of a secret stored by [`BiometricTrait::set_biometric_secret`], to
+/// pass to [`BiometricTrait::derive_key_material`] before reading the secret.
+pub fn stored_iv(encrypted_secret: &str) -> Result<String> {
+    let iv = StoredSecret::from_str(encrypted_secret)?.iv()?;
+    Ok(base64_engine.encode(iv))
+}
+
+/// Decode the `iv_b64` passed to [`BiometricTrait::set_biometric_secret`].
+fn decode_iv(iv_b64: &str) -> Result<[u8; 16]> {
+    decode_b64(iv_b64)
+}
 
-    let encrypted = crypto::encrypt_aes256(secret.as_bytes(), iv, key_material.derive_key()?)?;
+/// Encrypt the secret into an `AesGcm256_B64` cipher string bound to `associated_data`, using a
+/// key derived from `key_material` salted with `iv`.
+fn encrypt(
+    secret: &str,
+    key_material: &KeyMaterial,
+    iv: &[u8; 16],
+    associated_data: &[u8],
+) -> Result<String> {
+    let secret = crypto::encrypt_aes256_gcm(
+        secret.as_bytes(),
+        &key_material.derive_salted_key(iv)?,
+        associated_data,
+    )?;
+
+    Ok(StoredSecret::V1 { iv: *iv, secret }.to_string())
+}
+
+/// Encrypt the secret into an authenticated `AesCbc256_HmacSha256_B64` cipher string, with the
+/// challenge `iv_b64` as its iv, instead of the AES-256-GCM format. Use it for secrets that are
+/// also read by clients which parse the stored value as a cipher string, these secrets are not
+/// migrated when they are read.
+pub fn encrypt_authenticated(
+    secret: &str,
+    key_material: &KeyMaterial,
+    iv_b64: &str,
+) -> Result<String> {
+    let key = crypto::kdf::stretch_key(&key_material.derive_key()?)?;
+    let encrypted = crypto::encrypt_aes256_hmac(secret.as_bytes(), decode_iv(iv_b64)?, &key)?;
 
     Ok(encrypted.to_string())
 }
 
-fn decrypt(secret: &CipherString, key_material: &KeyMaterial) -> Result<String> {
-    if let CipherString::AesCbc256_B64 { iv, data } = secret {
-        let decrypted = crypto::decrypt_aes256(&iv, &data, key_material.derive_key()?)?;
+/// Decrypt a secret written by [`encrypt`] or [`encrypt_authenticated`], or by the legacy format.
+///
+/// `associated_data` is only used by current secrets.
+fn decrypt(
+    secret: &StoredSecret,
+    key_material: &KeyMaterial,
+    associated_data: &[u8],
+) -> Result<SecretString> {
+    let decrypted = match secret {
+        StoredSecret::Legacy(CipherString::AesCbc256_B64 { iv, data }) => {
+            crypto::decrypt_aes256(iv, data, &key_material.derive_key()?)?
+        }
+        StoredSecret::Legacy(CipherString::AesCbc256_HmacSha256_B64 { iv, mac, data }) => {
+            let key = crypto::kdf::stretch_key(&key_material.derive_key()?)?;
+            crypto::decrypt_aes256_hmac(iv, mac, data, &key)?
+        }
+        StoredSecret::V1 {
+            iv,
+            secret: CipherString::AesGcm256_B64 { nonce, data },
+        } => crypto::decrypt_aes256_gcm(
+            nonce,
+            data,
+            &key_material.derive_salted_key(iv)?,
+            associated_data,
+        )?,
+        _ => return Err(CryptoError::InvalidEncType.into()),
+    };
+
+    Ok(SecretString::try_from(decrypted).map_err(|_| CryptoError::KeyDecrypt)?)
+}
+
+/// The associated data binding a biometric secret to the entry it is stored in, so that it can't
+/// be swapped with the secret of another account.
+fn associated_data(service: &str, account: &str) -> Vec<u8> {
+    format!("{}/{}", service, account).into_bytes()
+}
+
+/// Encrypt the secret with [`encrypt`] and store it in the password store, returning the stored
+/// value. Shared by the implementations of [`BiometricTrait::set_biometric_secret`].
+async fn set_encrypted_secret(
+    service: &str,
+    account: &str,
+    secret: &str,
+    key_material: &KeyMaterial,
+    iv_b64: &str,
+) -> Result<String> {
+    let encrypted_secret = encrypt(
+        secret,
+        key_material,
+        &decode_iv(iv_b64)?,
+        &associated_data(service, account),
+    )?;
+    crate::password::set_password(service, account, &encrypted_secret).await?;
+    Ok(encrypted_secret)
+}
+
+/// Read a secret stored by [`set_encrypted_secret`] and decrypt it, migrating it when it uses a
+/// legacy format. Shared by the implementations of [`BiometricTrait::get_biometric_secret`].
+///
+/// With `allow_unencrypted` a stored value that is not an encrypted secret is returned as it is.
+async fn get_decrypted_secret(
+    service: &str,
+    account: &str,
+    key_material: &KeyMaterial,
+    allow_unencrypted: bool,
+) -> Result<SecretString> {
+    let stored = crate::password::get_password(service, account).await?;
+    let encrypted_secret = match StoredSecret::from_str(&stored) {
+        Ok(encrypted_secret) => encrypted_secret,
+        Err(_) if allow_unencrypted => return Ok(stored),
+        Err(e) => return Err(e),
+    };
+
+    let secret = decrypt(
+        &encrypted_secret,
+        key_material,
+        &associated_data(service, account),
+    )?;
+    migrate_legacy_secret(service, account, &encrypted_secret, &secret, key_material).await;
+    Ok(secret)
+}
+
+/// Re-encrypt a secret that was read from the unauthenticated legacy format with [`encrypt`],
+/// keeping its iv so that the same key material is derived for it.
+///
+/// Failing to migrate is not fatal, the secret will be migrated on the next successful read.
+async fn migrate_legacy_secret(
+    service: &str,
+    account: &str,
+    encrypted_secret: &StoredSecret,
+    secret: &str,
+    key_material: &KeyMaterial,
+) {
+    if encrypted_secret.is_current() {
+        return;
+    }
 
-        Ok(String::from_utf8(decrypted)?)
-    } else {
-        Err(anyhow!("Invalid cipher string"))
+    let encrypted = encrypted_secret.iv().and_then(|iv| {
+        encrypt(
+            secret,
+            key_material,
+            &iv,
+            &associated_data(service, account),
+        )
+    });
+    let result = match encrypted {
+        Ok(encrypted) => crate::password::set_password(service, account, &encrypted).await,
+        Err(e) => Err(e),
+    };
+    if let Err(e) = result {
+        warn!("Failed to migrate legacy biometric secret: {}", e);
     }
 }
 
+/// Domain separation label for [`KeyMaterial::derive_salted_key`], followed by the version byte.
+const KEY_DERIVATION_LABEL: &[u8] = b"bitwarden-desktop-biometric-key";
+const KEY_DERIVATION_VERSION: u8 = 1;
+
 impl KeyMaterial {
     fn digest_material(&self) -> String {
         match self.client_key_part_b64.as_deref() {
@@ -73,7 +308,158 @@ impl KeyMaterial {
         }
     }
 
-    pub fn derive_key(&self) -> Result<GenericArray<u8, typenum::U32>> {
-        Ok(Sha256::digest(self.digest_material()))
+    /// Derive the legacy, unsalted key. Only used to read secrets written before key derivation
+    /// was versioned.
+    pub fn derive_key(&self) -> Result<SymmetricKey> {
+        Ok(SymmetricKey::from(Sha256::digest(self.digest_material())))
+    }
+
+    /// Derive the key for the current key derivation version using HKDF-SHA256.
+    pub fn derive_salted_key(&self, salt: &[u8; 16]) -> Result<SymmetricKey> {
+        let mut info = KEY_DERIVATION_LABEL.to_vec();
+        info.push(KEY_DERIVATION_VERSION);
+
+        crypto::kdf::hkdf(self.digest_material().as_bytes(), salt, &info)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key_material() -> KeyMaterial {
+        KeyMaterial {
+            os_key_part_b64: "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=".to_owned(),
+            client_key_part_b64: Some("AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=".to_owned()),
+        }
+    }
+
+    const IV: [u8; 16] = [7u8; 16];
+
+    #[test]
+    fn encrypt_decrypt_roundtrip() {
+        let encrypted = encrypt("secret", &key_material(), &IV, b"service/account").unwrap();
+        let stored = StoredSecret::from_str(&encrypted).unwrap();
+
+        assert!(stored.is_current());
+        assert_eq!(stored.to_string(), encrypted);
+        assert_eq!(
+            &*decrypt(&stored, &key_material(), b"service/account").unwrap(),
+            "secret"
+        );
+    }
+
+    #[test]
+    fn encrypt_stores_gcm_cipher_string() {
+        let encrypted = encrypt("secret", &key_material(), &IV, b"").unwrap();
+        let (prefix, secret) = encrypted.rsplit_once('$').unwrap();
+
+        assert_eq!(prefix, format!("1${}", base64_engine.encode(IV)));
+        assert!(matches!(
+            CipherString::from_str(secret).unwrap(),
+            CipherString::AesGcm256_B64 { .. }
+        ));
+    }
+
+    #[test]
+    fn encrypt_decrypt_rejects_other_entry() {
+        let encrypted = encrypt("secret", &key_material(), &IV, b"service/account").unwrap();
+        let stored = StoredSecret::from_str(&encrypted).unwrap();
+
+        assert!(decrypt(&stored, &key_material(), b"service/other").is_err());
+    }
+
+    #[test]
+    fn stored_iv_returns_encryption_iv() {
+        let encrypted = encrypt("secret", &key_material(), &IV, b"").unwrap();
+        assert_eq!(stored_iv(&encrypted).unwrap(), base64_engine.encode(IV));
+
+        let legacy = "0.l9fhDUP/wDJcKwmEzcb/3w==|uP4LcqoCCj5FxBDP77NV6Q==";
+        assert_eq!(stored_iv(legacy).unwrap(), "l9fhDUP/wDJcKwmEzcb/3w==");
+    }
+
+    #[test]
+    fn decode_iv_rejects_invalid_length() {
+        assert!(matches!(decode_iv(""), Err(Error::InvalidInput(_))));
+        assert!(matches!(
+            decode_iv("AAAAAAAAAAAAAAAA"),
+            Err(Error::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn decrypt_legacy_secret() {
+        let stored =
+            StoredSecret::from_str("0.l9fhDUP/wDJcKwmEzcb/3w==|uP4LcqoCCj5FxBDP77NV6Q==").unwrap();
+
+        assert!(!stored.is_current());
+        assert_eq!(&*decrypt(&stored, &key_material(), b"").unwrap(), "secret");
+    }
+
+    #[test]
+    fn encrypt_authenticated_roundtrip() {
+        let iv_b64 = base64_engine.encode(IV);
+        let encrypted = encrypt_authenticated("secret", &key_material(), &iv_b64).unwrap();
+        let stored = StoredSecret::from_str(&encrypted).unwrap();
+
+        assert!(matches!(
+            stored,
+            StoredSecret::Legacy(CipherString::AesCbc256_HmacSha256_B64 { .. })
+        ));
+        assert!(stored.is_current());
+        assert_eq!(stored_iv(&encrypted).unwrap(), iv_b64);
+        assert_eq!(&*decrypt(&stored, &key_material(), b"").unwrap(), "secret");
+    }
+
+    #[test]
+    fn decrypt_authenticated_rejects_tampered_secret() {
+        let iv_b64 = base64_engine.encode(IV);
+        let encrypted = encrypt_authenticated("secret", &key_material(), &iv_b64).unwrap();
+        let mut stored = StoredSecret::from_str(&encrypted).unwrap();
+        if let StoredSecret::Legacy(CipherString::AesCbc256_HmacSha256_B64 { data, .. }) =
+            &mut stored
+        {
+            data[0] ^= 1;
+        }
+
+        assert!(matches!(
+            decrypt(&stored, &key_material(), b""),
+            Err(Error::Crypto(CryptoError::InvalidMac))
+        ));
+    }
+
+    #[test]
+    fn stored_secret_rejects_versioned_cbc_cipher_string() {
+        let result =
+            StoredSecret::from_str("1$l9fhDUP/wDJcKwmEzcb/3w==$0.l9fhDUP/wDJcKwmEzcb/3w==|AA==");
+        assert!(matches!(
+            result,
+            Err(Error::Crypto(CryptoError::InvalidEncType))
+        ));
+    }
+
+    #[test]
+    fn stored_secret_rejects_unknown_version() {
+        let result = StoredSecret::from_str("2$AAAAAAAAAAAAAAAAAAAAAA==$AAECAwQFBgcICQoL|AA==");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn stored_secret_rejects_gcm_cipher_string_without_iv() {
+        let result = StoredSecret::from_str("7.AAECAwQFBgcICQoL|AA==");
+        assert!(matches!(
+            result,
+            Err(Error::Crypto(CryptoError::InvalidEncType))
+        ));
+    }
+
+    #[test]
+    fn salted_key_differs_from_legacy_key() {
+        let salt = [0u8; 16];
+        let key = key_material().derive_salted_key(&salt).unwrap();
+
+        assert_ne!(key, key_material().derive_key().unwrap());
+        assert_ne!(key, key_material().derive_salted_key(&[1u8; 16]).unwrap());
+        assert_eq!(key, key_material().derive_salted_key(&salt).unwrap());
     }
 }
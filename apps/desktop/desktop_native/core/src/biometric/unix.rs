@@ -1,17 +1,16 @@
-use std::str::FromStr;
-
-use anyhow::Result;
-use base64::Engine;
+use base64::{engine::general_purpose::STANDARD as base64_engine, Engine};
 use rand::RngCore;
 use sha2::{Digest, Sha256};
 
-use crate::biometric::{base64_engine, KeyMaterial, OsDerivedKey};
+use crate::biometric::{KeyMaterial, OsDerivedKey};
 use zbus::Connection;
 use zbus_polkit::policykit1::*;
 
-use super::{decrypt, encrypt};
-use crate::crypto::CipherString;
-use anyhow::anyhow;
+use super::{get_decrypted_secret, set_encrypted_secret};
+use crate::{
+    crypto::SecretString,
+    error::{Error, Result},
+};
 
 /// The Unix implementation of the biometric trait.
 pub struct Biometric {}
@@ -58,9 +57,12 @@ impl super::BiometricTrait for Biometric {
     fn derive_key_material(challenge_str: Option<&str>) -> Result<OsDerivedKey> {
         let challenge: [u8; 16] = match challenge_str {
             Some(challenge_str) => base64_engine
-                .decode(challenge_str)?
+                .decode(challenge_str)
+                .map_err(|e| Error::InvalidInput(e.to_string()))?
                 .try_into()
-                .map_err(|e: Vec<_>| anyhow!("Expect length {}, got {}", 16, e.len()))?,
+                .map_err(|e: Vec<_>| {
+                    Error::InvalidInput(format!("Expect length {}, got {}", 16, e.len()))
+                })?,
             None => random_challenge(),
         };
 
@@ -73,34 +75,30 @@ impl super::BiometricTrait for Biometric {
         Ok(OsDerivedKey { key_b64, iv_b64 })
     }
 
-    fn set_biometric_secret(
+    async fn set_biometric_secret(
         service: &str,
         account: &str,
         secret: &str,
         key_material: Option<KeyMaterial>,
         iv_b64: &str,
     ) -> Result<String> {
-        let key_material = key_material.ok_or(anyhow!(
-            "Key material is required for polkit protected keys"
+        let key_material = key_material.ok_or(Error::InvalidInput(
+            "Key material is required for polkit protected keys".to_string(),
         ))?;
 
-        let encrypted_secret = encrypt(secret, &key_material, iv_b64)?;
-        crate::password::set_password(service, account, &encrypted_secret)?;
-        Ok(encrypted_secret)
+        set_encrypted_secret(service, account, secret, &key_material, iv_b64).await
     }
 
-    fn get_biometric_secret(
+    async fn get_biometric_secret(
         service: &str,
         account: &str,
         key_material: Option<KeyMaterial>,
-    ) -> Result<String> {
-        let key_material = key_material.ok_or(anyhow!(
-            "Key material is required for polkit protected keys"
+    ) -> Result<SecretString> {
+        let key_material = key_material.ok_or(Error::InvalidInput(
+            "Key material is required for polkit protected keys".to_string(),
         ))?;
 
-        let encrypted_secret = crate::password::get_password(service, account)?;
-        let secret = CipherString::from_str(&encrypted_secret)?;
-        return Ok(decrypt(&secret, &key_material)?);
+        get_decrypted_secret(service, account, &key_material, false).await
     }
 }
 
@@ -109,3 +107,92 @@ fn random_challenge() -> [u8; 16] {
     rand::thread_rng().fill_bytes(&mut challenge);
     challenge
 }
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use futures::executor::block_on;
+
+    use super::*;
+    use crate::biometric::{BiometricTrait, StoredSecret};
+
+    fn key_material() -> KeyMaterial {
+        KeyMaterial {
+            os_key_part_b64: "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=".to_owned(),
+            client_key_part_b64: Some("AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=".to_owned()),
+        }
+    }
+
+    #[test]
+    fn set_get_biometric_secret() {
+        let _backend = crate::password::use_memory_backend();
+
+        let encrypted = block_on(Biometric::set_biometric_secret(
+            "BitwardenTestBiometric",
+            "BitwardenTest",
+            "secret",
+            Some(key_material()),
+            "l9fhDUP/wDJcKwmEzcb/3w==",
+        ))
+        .unwrap();
+        assert_eq!(
+            crate::biometric::stored_iv(&encrypted).unwrap(),
+            "l9fhDUP/wDJcKwmEzcb/3w=="
+        );
+        assert_eq!(
+            "secret",
+            &*block_on(Biometric::get_biometric_secret(
+                "BitwardenTestBiometric",
+                "BitwardenTest",
+                Some(key_material())
+            ))
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn get_biometric_secret_migrates_legacy_secret() {
+        let _backend = crate::password::use_memory_backend();
+        block_on(crate::password::set_password(
+            "BitwardenTestBiometricLegacy",
+            "BitwardenTest",
+            "0.l9fhDUP/wDJcKwmEzcb/3w==|uP4LcqoCCj5FxBDP77NV6Q==",
+        ))
+        .unwrap();
+
+        assert_eq!(
+            "secret",
+            &*block_on(Biometric::get_biometric_secret(
+                "BitwardenTestBiometricLegacy",
+                "BitwardenTest",
+                Some(key_material())
+            ))
+            .unwrap()
+        );
+        let stored = block_on(crate::password::get_password(
+            "BitwardenTestBiometricLegacy",
+            "BitwardenTest",
+        ))
+        .unwrap();
+        assert!(StoredSecret::from_str(&stored).unwrap().is_current());
+        assert_eq!(
+            crate::biometric::stored_iv(&stored).unwrap(),
+            "l9fhDUP/wDJcKwmEzcb/3w=="
+        );
+    }
+
+    #[test]
+    fn set_biometric_secret_requires_key() {
+        assert!(matches!(
+            block_on(Biometric::set_biometric_secret(
+                "BitwardenTestBiometric",
+                "BitwardenTest",
+                "",
+                None,
+                ""
+            )),
+            Err(Error::InvalidInput(_))
+        ));
+    }
+}
@@ -1,6 +1,5 @@
-use std::{ffi::c_void, str::FromStr};
+use std::ffi::c_void;
 
-use anyhow::{anyhow, Result};
 use base64::{engine::general_purpose::STANDARD as base64_engine, Engine};
 use rand::RngCore;
 use retry::delay::Fixed;
@@ -29,10 +28,11 @@ use windows::{
 
 use crate::{
     biometric::{KeyMaterial, OsDerivedKey},
-    crypto::CipherString,
+    crypto::SecretString,
+    error::{Error, Result},
 };
 
-use super::{decrypt, encrypt};
+use super::{get_decrypted_secret, set_encrypted_secret};
 
 /// The Windows OS implementation of the biometric trait.
 pub struct Biometric {}
@@ -80,9 +80,12 @@ impl super::BiometricTrait for Biometric {
     fn derive_key_material(challenge_str: Option<&str>) -> Result<OsDerivedKey> {
         let challenge: [u8; 16] = match challenge_str {
             Some(challenge_str) => base64_engine
-                .decode(challenge_str)?
+                .decode(challenge_str)
+                .map_err(|e| Error::InvalidInput(e.to_string()))?
                 .try_into()
-                .map_err(|e: Vec<_>| anyhow!("Expect length {}, got {}", 16, e.len()))?,
+                .map_err(|e: Vec<_>| {
+                    Error::InvalidInput(format!("Expect length {}, got {}", 16, e.len()))
+                })?,
             None => random_challenge(),
         };
         let bitwarden = h!("Bitwarden");
@@ -98,7 +101,7 @@ impl super::BiometricTrait for Biometric {
                 KeyCredentialManager::OpenAsync(&bitwarden)?.get()?
             }
             KeyCredentialStatus::Success => result,
-            _ => return Err(anyhow!("Failed to create key credential")),
+            status => return Err(convert_status(status, "Failed to create key credential")),
         };
 
         let challenge_buffer = CryptographicBuffer::CreateFromByteArray(&challenge)?;
@@ -106,8 +109,9 @@ impl super::BiometricTrait for Biometric {
         focus_security_prompt()?;
         let signature = async_operation.get()?;
 
-        if signature.Status()? != KeyCredentialStatus::Success {
-            return Err(anyhow!("Failed to sign data"));
+        match signature.Status()? {
+            KeyCredentialStatus::Success => {}
+            status => return Err(convert_status(status, "Failed to sign data")),
         }
 
         let signature_buffer = signature.Result()?;
@@ -121,44 +125,31 @@ impl super::BiometricTrait for Biometric {
         Ok(OsDerivedKey { key_b64, iv_b64 })
     }
 
-    fn set_biometric_secret(
+    async fn set_biometric_secret(
         service: &str,
         account: &str,
         secret: &str,
         key_material: Option<KeyMaterial>,
         iv_b64: &str,
     ) -> Result<String> {
-        let key_material = key_material.ok_or(anyhow!(
-            "Key material is required for Windows Hello protected keys"
+        let key_material = key_material.ok_or(Error::InvalidInput(
+            "Key material is required for Windows Hello protected keys".to_string(),
         ))?;
 
-        let encrypted_secret = encrypt(secret, &key_material, iv_b64)?;
-        crate::password::set_password(service, account, &encrypted_secret)?;
-        Ok(encrypted_secret)
+        set_encrypted_secret(service, account, secret, &key_material, iv_b64).await
     }
 
-    fn get_biometric_secret(
+    async fn get_biometric_secret(
         service: &str,
         account: &str,
         key_material: Option<KeyMaterial>,
-    ) -> Result<String> {
-        let key_material = key_material.ok_or(anyhow!(
-            "Key material is required for Windows Hello protected keys"
+    ) -> Result<SecretString> {
+        let key_material = key_material.ok_or(Error::InvalidInput(
+            "Key material is required for Windows Hello protected keys".to_string(),
         ))?;
 
-        let encrypted_secret = crate::password::get_password(service, account)?;
-        match CipherString::from_str(&encrypted_secret) {
-            Ok(secret) => {
-                // If the secret is a CipherString, it is encrypted and we need to decrypt it.
-                let secret = decrypt(&secret, &key_material)?;
-                return Ok(secret);
-            }
-            Err(_) => {
-                // If the secret is not a CipherString, it is not encrypted and we can return it
-                //  directly.
-                return Ok(encrypted_secret);
-            }
-        }
+        // Secrets stored before they were encrypted are returned as they are
+        get_decrypted_secret(service, account, &key_material, true).await
     }
 }
 
@@ -191,7 +182,17 @@ fn focus_security_prompt() -> Result<()> {
 
         unsafe { try_find_and_set_focus(class_name) }
     })
-    .map_err(|_| anyhow!("Failed to find security prompt"))
+    .map_err(|_| Error::NotFound("Failed to find security prompt".to_string()))
+}
+
+// Convert a failed key credential status to its matching error kind
+fn convert_status(status: KeyCredentialStatus, message: &str) -> Error {
+    match status {
+        KeyCredentialStatus::UserCanceled => Error::UserCancelled,
+        KeyCredentialStatus::SecurityDeviceLocked => Error::AccessDenied(message.to_string()),
+        KeyCredentialStatus::NotFound => Error::NotFound(message.to_string()),
+        _ => Error::Platform(message.to_string()),
+    }
 }
 
 fn set_focus(window: HWND) {
@@ -221,9 +222,18 @@ fn set_focus(window: HWND) {
 
 #[cfg(test)]
 mod tests {
+    use std::str::FromStr;
+
+    use futures::executor::block_on;
+
     use super::*;
 
-    use crate::biometric::{encrypt, BiometricTrait};
+    use crate::{
+        biometric::{
+            associated_data, decode_iv, decrypt, encrypt, stored_iv, BiometricTrait, StoredSecret,
+        },
+        crypto::CipherString,
+    };
 
     #[test]
     #[cfg(feature = "manual_test")]
@@ -267,34 +277,41 @@ mod tests {
             os_key_part_b64: "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=".to_owned(),
             client_key_part_b64: Some("AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=".to_owned()),
         };
-        let iv_b64 = "l9fhDUP/wDJcKwmEzcb/3w==".to_owned();
-        let secret = encrypt("secret", &key_material, &iv_b64)
-            .unwrap()
-            .parse::<CipherString>()
-            .unwrap();
-
-        match secret {
-            CipherString::AesCbc256_B64 { iv, data: _ } => {
-                assert_eq!(iv_b64, base64_engine.encode(&iv));
-            }
-            _ => panic!("Invalid cipher string"),
-        }
+        let iv_b64 = "l9fhDUP/wDJcKwmEzcb/3w==";
+        let associated_data = associated_data("service", "account");
+        let encrypted = encrypt(
+            "secret",
+            &key_material,
+            &decode_iv(iv_b64).unwrap(),
+            &associated_data,
+        )
+        .unwrap();
+        assert_eq!(stored_iv(&encrypted).unwrap(), iv_b64);
+
+        let secret = encrypted.parse::<StoredSecret>().unwrap();
+        assert!(secret.is_current());
+        assert_eq!(
+            &*decrypt(&secret, &key_material, &associated_data).unwrap(),
+            "secret"
+        );
     }
 
     #[test]
     fn test_decrypt() {
         let secret =
-            CipherString::from_str("0.l9fhDUP/wDJcKwmEzcb/3w==|uP4LcqoCCj5FxBDP77NV6Q==").unwrap(); // output from test_encrypt
+            StoredSecret::from_str("0.l9fhDUP/wDJcKwmEzcb/3w==|uP4LcqoCCj5FxBDP77NV6Q==").unwrap(); // legacy AesCbc256_B64 secret
         let key_material = KeyMaterial {
             os_key_part_b64: "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=".to_owned(),
             client_key_part_b64: Some("AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=".to_owned()),
         };
-        assert_eq!(decrypt(&secret, &key_material).unwrap(), "secret")
+        assert_eq!(&*decrypt(&secret, &key_material, &[]).unwrap(), "secret")
     }
 
     #[test]
     fn get_biometric_secret_requires_key() {
-        let result = <Biometric as BiometricTrait>::get_biometric_secret("", "", None);
+        let result = block_on(<Biometric as BiometricTrait>::get_biometric_secret(
+            "", "", None,
+        ));
         assert!(result.is_err());
         assert_eq!(
             result.unwrap_err().to_string(),
@@ -304,45 +321,84 @@ mod tests {
 
     #[test]
     fn get_biometric_secret_handles_unencrypted_secret() {
-        scopeguard::defer! {
-            crate::password::delete_password("test", "test").unwrap();
-        }
+        let _backend = crate::password::use_memory_backend();
         let test = "test";
         let secret = "password";
         let key_material = KeyMaterial {
             os_key_part_b64: "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=".to_owned(),
             client_key_part_b64: Some("AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=".to_owned()),
         };
-        crate::password::set_password(test, test, secret).unwrap();
-        let result =
-            <Biometric as BiometricTrait>::get_biometric_secret(test, test, Some(key_material))
-                .unwrap();
-        assert_eq!(result, secret);
+        block_on(crate::password::set_password(test, test, secret)).unwrap();
+        let result = block_on(<Biometric as BiometricTrait>::get_biometric_secret(
+            test,
+            test,
+            Some(key_material),
+        ))
+        .unwrap();
+        assert_eq!(&*result, secret);
     }
 
     #[test]
     fn get_biometric_secret_handles_encrypted_secret() {
-        scopeguard::defer! {
-            crate::password::delete_password("test", "test").unwrap();
-        }
+        let _backend = crate::password::use_memory_backend();
         let test = "test";
         let secret =
-            CipherString::from_str("0.l9fhDUP/wDJcKwmEzcb/3w==|uP4LcqoCCj5FxBDP77NV6Q==").unwrap(); // output from test_encrypt
+            CipherString::from_str("0.l9fhDUP/wDJcKwmEzcb/3w==|uP4LcqoCCj5FxBDP77NV6Q==").unwrap(); // legacy AesCbc256_B64 secret
         let key_material = KeyMaterial {
             os_key_part_b64: "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=".to_owned(),
             client_key_part_b64: Some("AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=".to_owned()),
         };
-        crate::password::set_password(test, test, &secret.to_string()).unwrap();
+        block_on(crate::password::set_password(
+            test,
+            test,
+            &secret.to_string(),
+        ))
+        .unwrap();
+
+        let result = block_on(<Biometric as BiometricTrait>::get_biometric_secret(
+            test,
+            test,
+            Some(key_material),
+        ))
+        .unwrap();
+        assert_eq!(&*result, "secret");
+    }
+
+    #[test]
+    fn get_biometric_secret_migrates_legacy_secret() {
+        let _backend = crate::password::use_memory_backend();
+        let test = "test";
+        block_on(crate::password::set_password(
+            test,
+            test,
+            "0.l9fhDUP/wDJcKwmEzcb/3w==|uP4LcqoCCj5FxBDP77NV6Q==",
+        ))
+        .unwrap();
 
-        let result =
-            <Biometric as BiometricTrait>::get_biometric_secret(test, test, Some(key_material))
-                .unwrap();
-        assert_eq!(result, "secret");
+        let result = block_on(<Biometric as BiometricTrait>::get_biometric_secret(
+            test,
+            test,
+            Some(key_material()),
+        ))
+        .unwrap();
+        assert_eq!(&*result, "secret");
+
+        let stored = block_on(crate::password::get_password(test, test)).unwrap();
+        assert!(StoredSecret::from_str(&stored).unwrap().is_current());
+        let result = block_on(<Biometric as BiometricTrait>::get_biometric_secret(
+            test,
+            test,
+            Some(key_material()),
+        ))
+        .unwrap();
+        assert_eq!(&*result, "secret");
     }
 
     #[test]
     fn set_biometric_secret_requires_key() {
-        let result = <Biometric as BiometricTrait>::set_biometric_secret("", "", "", None, "");
+        let result = block_on(<Biometric as BiometricTrait>::set_biometric_secret(
+            "", "", "", None, "",
+        ));
         assert!(result.is_err());
         assert_eq!(
             result.unwrap_err().to_string(),
@@ -360,7 +416,7 @@ mod tests {
     #[test]
     fn key_material_produces_valid_key() {
         let result = key_material().derive_key().unwrap();
-        assert_eq!(result.len(), 32);
+        assert_eq!(result.as_slice().len(), 32);
     }
 
     #[test]
@@ -388,12 +444,11 @@ mod tests {
         key_material.client_key_part_b64 = None;
         let result = key_material.derive_key().unwrap();
         assert_eq!(
-            result,
+            result.as_slice(),
             [
                 81, 100, 62, 172, 151, 119, 182, 58, 123, 38, 129, 116, 209, 253, 66, 118, 218,
                 237, 236, 155, 201, 234, 11, 198, 229, 171, 246, 144, 71, 188, 84, 246
             ]
-            .into()
         );
     }
 
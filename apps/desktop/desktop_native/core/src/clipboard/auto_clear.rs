@@ -0,0 +1,276 @@
+//! Clearing secrets from the clipboard after a timeout.
+
+use std::{
+    future::Future,
+    sync::{Arc, Condvar, Mutex, MutexGuard, OnceLock},
+    time::{Duration, Instant},
+};
+
+use hmac::{Hmac, Mac};
+use log::error;
+use rand::RngCore;
+use sha2::Sha256;
+
+use crate::error::{Error, Result};
+
+use super::{ClipboardBackend, Format, Target};
+
+/// Clears the text written to a clipboard after a timeout, unless it was replaced in the meantime.
+///
+/// Only the text written last is cleared, writing again or cancelling replaces the pending clear.
+/// The clears are run by a single background thread, started by the first write.
+pub struct AutoClear {
+    /// Never held while the clipboard is accessed, so that cancelling doesn't wait for it.
+    state: Mutex<State>,
+    changed: Condvar,
+    /// Held while writing and while clearing, so that a clear can't remove a write made after
+    /// it compared the content. Held across the awaited write, the clears lock it blocking.
+    clipboard: tokio::sync::Mutex<()>,
+    /// Keys the tags of the written texts. Random for each process, so that a tag can't be
+    /// matched against guessed passwords.
+    key: OnceLock<[u8; 32]>,
+}
+
+struct State {
+    /// Incremented whenever the pending clear is replaced or cancelled, a clear only runs while
+    /// its generation is the current one.
+    generation: u64,
+    pending: Option<PendingClear>,
+    worker_started: bool,
+}
+
+struct PendingClear {
+    generation: u64,
+    deadline: Instant,
+    backend: Arc<dyn ClipboardBackend>,
+    /// Only a tag is kept, the secret itself doesn't outlive the write.
+    tag: Vec<u8>,
+}
+
+impl AutoClear {
+    pub const fn new() -> Self {
+        AutoClear {
+            state: Mutex::new(State {
+                generation: 0,
+                pending: None,
+                worker_started: false,
+            }),
+            changed: Condvar::new(),
+            clipboard: tokio::sync::Mutex::const_new(()),
+            key: OnceLock::new(),
+        }
+    }
+
+    /// Write the text to [`Target::Clipboard`] with `write`, and clear it with the backend after
+    /// the timeout if the clipboard still holds it.
+    pub async fn write(
+        &'static self,
+        backend: Arc<dyn ClipboardBackend>,
+        text: &str,
+        timeout: Duration,
+        write: impl Future<Output = Result<()>>,
+    ) -> Result<()> {
+        let deadline = Instant::now() + timeout;
+        let tag = self.tag(text.as_bytes());
+
+        let _clipboard = self.clipboard.lock().await;
+        write.await?;
+
+        let mut state = self.lock();
+        if !state.worker_started {
+            std::thread::Builder::new()
+                .name("clipboard-clear".to_string())
+                .spawn(move || self.run())?;
+            state.worker_started = true;
+        }
+        state.generation += 1;
+        state.pending = Some(PendingClear {
+            generation: state.generation,
+            deadline,
+            backend,
+            tag,
+        });
+        self.changed.notify_all();
+        Ok(())
+    }
+
+    /// Cancel the pending clear, if any.
+    pub fn cancel(&self) {
+        let mut state = self.lock();
+        state.generation += 1;
+        state.pending = None;
+        self.changed.notify_all();
+    }
+
+    /// Run the pending clear now instead of after its timeout, if any.
+    pub fn clear_pending(&self) {
+        let mut state = self.lock();
+        if let Some(pending) = &mut state.pending {
+            pending.deadline = Instant::now();
+        }
+        self.changed.notify_all();
+    }
+
+    // The background thread, clearing the clipboard whenever the pending clear is due
+    fn run(&self) {
+        loop {
+            let pending = self.next_due();
+
+            let _clipboard = self.clipboard.blocking_lock();
+            // Written or cancelled since it was due
+            if self.lock().generation != pending.generation {
+                continue;
+            }
+            if let Err(e) = self.clear_if_unchanged(&*pending.backend, &pending.tag) {
+                error!("Failed to clear the clipboard: {}", e);
+            }
+        }
+    }
+
+    // Wait until the pending clear is due and take it
+    fn next_due(&self) -> PendingClear {
+        let mut state = self.lock();
+        loop {
+            let now = Instant::now();
+            state = match &state.pending {
+                Some(pending) if pending.deadline <= now => {
+                    return state.pending.take().expect("The pending clear is set");
+                }
+                Some(pending) => {
+                    let timeout = pending.deadline - now;
+                    self.changed
+                        .wait_timeout(state, timeout)
+                        .expect("Mutex is not poisoned")
+                        .0
+                }
+                None => self.changed.wait(state).expect("Mutex is not poisoned"),
+            };
+        }
+    }
+
+    fn clear_if_unchanged(&self, backend: &dyn ClipboardBackend, tag: &[u8]) -> Result<()> {
+        match backend.read(Target::Clipboard, Format::Text) {
+            Ok(content) if self.tag(content.data()) == tag => backend.clear(Target::Clipboard),
+            // Replaced, e.g. the user copied something else
+            Ok(_) | Err(Error::NotFound(_)) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn tag(&self, data: &[u8]) -> Vec<u8> {
+        let key = self.key.get_or_init(|| {
+            let mut key = [0u8; 32];
+            rand::thread_rng().fill_bytes(&mut key);
+            key
+        });
+        <Hmac<Sha256> as Mac>::new_from_slice(key)
+            .expect("HMAC accepts keys of any length")
+            .chain_update(data)
+            .finalize()
+            .into_bytes()
+            .to_vec()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().expect("Mutex is not poisoned")
+    }
+}
+
+impl Default for AutoClear {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clipboard::{Content, MemoryClipboard};
+
+    const TIMEOUT: Duration = Duration::from_millis(50);
+
+    fn read(clipboard: &MemoryClipboard) -> Result<String> {
+        clipboard.read(Target::Clipboard, Format::Text)?.into_text()
+    }
+
+    fn auto_clear() -> &'static AutoClear {
+        Box::leak(Box::new(AutoClear::new()))
+    }
+
+    // Write a password with the auto clear
+    fn write(
+        auto_clear: &'static AutoClear,
+        clipboard: &Arc<MemoryClipboard>,
+        text: &str,
+        timeout: Duration,
+    ) -> Result<()> {
+        let content = Content::Text(text.to_string());
+        let write = async { clipboard.write(Target::Clipboard, &content, true) };
+        futures::executor::block_on(auto_clear.write(clipboard.clone(), text, timeout, write))
+    }
+
+    // Wait until the clears scheduled with TIMEOUT ran
+    fn wait() {
+        std::thread::sleep(TIMEOUT * 4);
+    }
+
+    #[test]
+    fn clears_after_timeout() {
+        let clipboard = Arc::new(MemoryClipboard::new());
+        write(auto_clear(), &clipboard, "secret", TIMEOUT).unwrap();
+        assert_eq!("secret", read(&clipboard).unwrap());
+        assert!(clipboard.is_password());
+
+        wait();
+        assert!(matches!(read(&clipboard), Err(Error::NotFound(_))));
+    }
+
+    #[test]
+    fn keeps_replaced_content() {
+        let clipboard = Arc::new(MemoryClipboard::new());
+        write(auto_clear(), &clipboard, "secret", TIMEOUT).unwrap();
+        clipboard
+            .write(
+                Target::Clipboard,
+                &Content::Text("copied by the user".to_string()),
+                false,
+            )
+            .unwrap();
+
+        wait();
+        assert_eq!("copied by the user", read(&clipboard).unwrap());
+    }
+
+    #[test]
+    fn cancel() {
+        let clipboard = Arc::new(MemoryClipboard::new());
+        let auto_clear = auto_clear();
+        write(auto_clear, &clipboard, "secret", TIMEOUT).unwrap();
+        auto_clear.cancel();
+
+        wait();
+        assert_eq!("secret", read(&clipboard).unwrap());
+    }
+
+    #[test]
+    fn clear_pending() {
+        let clipboard = Arc::new(MemoryClipboard::new());
+        let auto_clear = auto_clear();
+        write(auto_clear, &clipboard, "secret", Duration::from_secs(60)).unwrap();
+        auto_clear.clear_pending();
+
+        wait();
+        assert!(matches!(read(&clipboard), Err(Error::NotFound(_))));
+    }
+
+    #[test]
+    fn write_replaces_pending_clear() {
+        let clipboard = Arc::new(MemoryClipboard::new());
+        let auto_clear = auto_clear();
+        write(auto_clear, &clipboard, "secret", TIMEOUT).unwrap();
+        write(auto_clear, &clipboard, "secret", Duration::from_secs(60)).unwrap();
+
+        wait();
+        assert_eq!("secret", read(&clipboard).unwrap());
+    }
+}
@@ -0,0 +1,230 @@
+use std::borrow::Cow;
+
+use arboard::{Get, ImageData, Set};
+use zeroize::Zeroize;
+
+use crate::error::{Error, Result};
+
+use super::convert_error;
+
+/// The clipboard to read from or write to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Target {
+    /// The regular clipboard, used by copy and paste.
+    Clipboard,
+    /// The X11 and Wayland PRIMARY selection, holding the selected text which is pasted with the
+    /// middle mouse button. Only available on Linux.
+    Primary,
+}
+
+/// The format of the content to read.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Text,
+    Html,
+    Image,
+}
+
+#[derive(Clone, PartialEq, Eq)]
+pub enum Content {
+    Text(String),
+    /// HTML with the plain text offered to the applications which don't paste HTML.
+    Html {
+        html: String,
+        alt_text: Option<String>,
+    },
+    Image(Image),
+}
+
+/// An image with its pixels in RGBA order, 4 bytes per pixel.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub bytes: Vec<u8>,
+}
+
+impl Image {
+    /// Fails with [`Error::InvalidInput`] unless the bytes hold all the pixels of the image.
+    fn validate(&self) -> Result<()> {
+        let expected = self
+            .width
+            .checked_mul(self.height)
+            .and_then(|pixels| pixels.checked_mul(4))
+            .ok_or_else(|| {
+                Error::InvalidInput(format!(
+                    "A {}x{} image is too large",
+                    self.width, self.height
+                ))
+            })?;
+
+        if self.bytes.len() != expected {
+            return Err(Error::InvalidInput(format!(
+                "Expected {} bytes for a {}x{} image, got {}",
+                expected,
+                self.width,
+                self.height,
+                self.bytes.len()
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl Content {
+    /// Fails with [`Error::InvalidInput`] for content which can't be written to a clipboard.
+    pub(super) fn validate(&self) -> Result<()> {
+        match self {
+            Content::Image(image) => image.validate(),
+            Content::Text(_) | Content::Html { .. } => Ok(()),
+        }
+    }
+
+    pub fn format(&self) -> Format {
+        match self {
+            Content::Text(_) => Format::Text,
+            Content::Html { .. } => Format::Html,
+            Content::Image(_) => Format::Image,
+        }
+    }
+
+    /// The text of text content, [`Error::InvalidInput`] for the other formats.
+    pub fn into_text(self) -> Result<String> {
+        match self {
+            Content::Text(text) => Ok(text),
+            _ => Err(Error::InvalidInput(format!(
+                "Expected text, got {:?}",
+                self.format()
+            ))),
+        }
+    }
+
+    /// The bytes identifying the content, compared to tell whether the clipboard was replaced.
+    pub(super) fn data(&self) -> &[u8] {
+        match self {
+            Content::Text(text) => text.as_bytes(),
+            Content::Html { html, .. } => html.as_bytes(),
+            Content::Image(image) => &image.bytes,
+        }
+    }
+}
+
+// The content can be a password, only its size is printed
+impl std::fmt::Debug for Content {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Content::Text(text) => f.debug_struct("Text").field("len", &text.len()).finish(),
+            Content::Html { html, alt_text } => f
+                .debug_struct("Html")
+                .field("len", &html.len())
+                .field("alt_text_len", &alt_text.as_ref().map(String::len))
+                .finish(),
+            Content::Image(image) => image.fmt(f),
+        }
+    }
+}
+
+impl std::fmt::Debug for Image {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Image")
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .finish_non_exhaustive()
+    }
+}
+
+// The content can be a password
+impl Zeroize for Content {
+    fn zeroize(&mut self) {
+        match self {
+            Content::Text(text) => text.zeroize(),
+            Content::Html { html, alt_text } => {
+                html.zeroize();
+                alt_text.zeroize();
+            }
+            Content::Image(image) => image.bytes.zeroize(),
+        }
+    }
+}
+
+pub(super) fn get(get: Get, format: Format) -> Result<Content> {
+    match format {
+        Format::Text => get.text().map(Content::Text),
+        Format::Html => get.html().map(|html| Content::Html {
+            html,
+            alt_text: None,
+        }),
+        Format::Image => get.image().map(|image| {
+            Content::Image(Image {
+                width: image.width,
+                height: image.height,
+                bytes: image.bytes.into_owned(),
+            })
+        }),
+    }
+    .map_err(convert_error)
+}
+
+pub(super) fn set(set: Set, content: &Content) -> Result<()> {
+    content.validate()?;
+    match content {
+        Content::Text(text) => set.text(text),
+        Content::Html { html, alt_text } => set.html(html, alt_text.as_ref()),
+        Content::Image(image) => set.image(ImageData {
+            width: image.width,
+            height: image.height,
+            bytes: Cow::Borrowed(&image.bytes),
+        }),
+    }
+    .map_err(convert_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn debug_is_redacted() {
+        let text = Content::Text("hunter2".to_string());
+        assert_eq!(format!("{:?}", text), "Text { len: 7 }");
+
+        let html = Content::Html {
+            html: "<b>hunter2</b>".to_string(),
+            alt_text: Some("hunter2".to_string()),
+        };
+        assert!(!format!("{:?}", html).contains("hunter2"));
+
+        let image = Content::Image(Image {
+            width: 1,
+            height: 1,
+            bytes: vec![0xAB; 4],
+        });
+        assert_eq!(format!("{:?}", image), "Image { width: 1, height: 1, .. }");
+    }
+
+    #[test]
+    fn image_size_is_validated() {
+        let image = |width, height, len| Image {
+            width,
+            height,
+            bytes: vec![0; len],
+        };
+
+        assert!(image(2, 3, 24).validate().is_ok());
+        assert!(image(0, 0, 0).validate().is_ok());
+        assert!(matches!(
+            image(2, 3, 23).validate(),
+            Err(Error::InvalidInput(_))
+        ));
+        // Wraps to 0 bytes without the overflow checks
+        let half = 1 << (usize::BITS / 2);
+        assert!(matches!(
+            image(half, half, 0).validate(),
+            Err(Error::InvalidInput(_))
+        ));
+        assert!(matches!(
+            image(usize::MAX, 1, 0).validate(),
+            Err(Error::InvalidInput(_))
+        ));
+    }
+}
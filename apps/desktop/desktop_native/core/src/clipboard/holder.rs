@@ -0,0 +1,246 @@
+//! Owning the clipboard on Linux.
+//!
+//! On X11 and Wayland the clipboard content is not handed to the display server, the application
+//! owning the selection serves it to the applications pasting until another application takes the
+//! selection over. A single long-lived [`Clipboard`] on a dedicated thread owns the selection, so
+//! writing returns as soon as the selection is taken instead of waiting for it to be lost.
+
+use std::{
+    sync::{mpsc, Mutex, OnceLock},
+    thread,
+};
+
+use arboard::{ClearExtLinux, Clipboard, GetExtLinux, LinuxClipboardKind, SetExtLinux};
+use futures::channel::oneshot;
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+
+use crate::error::{Error, Result};
+
+use super::{content, convert_error, Content, Format, Target};
+
+enum Command {
+    Read {
+        target: Target,
+        format: Format,
+        reply: oneshot::Sender<Result<Content>>,
+    },
+    Write {
+        target: Target,
+        content: Zeroizing<Content>,
+        password: bool,
+        reply: oneshot::Sender<Result<()>>,
+    },
+    Clear {
+        target: Target,
+        reply: oneshot::Sender<Result<()>>,
+    },
+    IsOwner(oneshot::Sender<Result<bool>>),
+}
+
+/// A handle to the thread owning the clipboard.
+pub struct ClipboardHolder {
+    commands: mpsc::Sender<Command>,
+}
+
+/// The holder of this process, the thread is started on first use. Starting it is retried by the
+/// next call when it failed.
+pub fn holder() -> Result<&'static ClipboardHolder> {
+    static HOLDER: OnceLock<ClipboardHolder> = OnceLock::new();
+    static STARTING: Mutex<()> = Mutex::new(());
+
+    if let Some(holder) = HOLDER.get() {
+        return Ok(holder);
+    }
+    let _starting = STARTING.lock().expect("Mutex is not poisoned");
+    if let Some(holder) = HOLDER.get() {
+        return Ok(holder);
+    }
+    let (commands, receiver) = mpsc::channel();
+    thread::Builder::new()
+        .name("clipboard".to_string())
+        .spawn(move || Owner::default().serve(receiver))?;
+    Ok(HOLDER.get_or_init(|| ClipboardHolder { commands }))
+}
+
+impl ClipboardHolder {
+    pub async fn read(&self, target: Target, format: Format) -> Result<Content> {
+        self.request(|reply| Command::Read {
+            target,
+            format,
+            reply,
+        })
+        .await
+    }
+
+    /// Take the selection over with the content. Passwords are offered with the
+    /// `x-kde-passwordManagerHint: secret` MIME type, which the clipboard managers on X11 and
+    /// Wayland, such as Klipper and cliphist, don't add to their history.
+    pub async fn write(&self, target: Target, content: &Content, password: bool) -> Result<()> {
+        let content = Zeroizing::new(content.clone());
+        self.request(|reply| Command::Write {
+            target,
+            content,
+            password,
+            reply,
+        })
+        .await
+    }
+
+    pub async fn clear(&self, target: Target) -> Result<()> {
+        self.request(|reply| Command::Clear { target, reply }).await
+    }
+
+    /// Whether [`Target::Clipboard`] still holds the content written last, i.e. no other
+    /// application took the selection over and it wasn't cleared since.
+    pub async fn is_owner(&self) -> Result<bool> {
+        self.request(Command::IsOwner).await
+    }
+
+    async fn request<T>(
+        &self,
+        command: impl FnOnce(oneshot::Sender<Result<T>>) -> Command,
+    ) -> Result<T> {
+        let (reply, response) = oneshot::channel();
+        self.commands.send(command(reply)).map_err(|_| stopped())?;
+        response.await.map_err(|_| stopped())?
+    }
+}
+
+fn stopped() -> Error {
+    Error::ServiceUnavailable("The clipboard thread is not running".to_string())
+}
+
+#[derive(Default)]
+struct Owner {
+    /// Connected on first use, and again after a failure, e.g. when the display server restarted.
+    clipboard: Option<Clipboard>,
+    /// The format and hash of the content written last to [`Target::Clipboard`], the content
+    /// itself isn't kept.
+    written: Option<(Format, [u8; 32])>,
+}
+
+impl Owner {
+    fn serve(mut self, commands: mpsc::Receiver<Command>) {
+        // The replies are not sent when the requester is gone, e.g. a dropped future
+        for command in commands {
+            match command {
+                Command::Read {
+                    target,
+                    format,
+                    reply,
+                } => {
+                    let _ = reply.send(self.read(target, format));
+                }
+                Command::Write {
+                    target,
+                    content,
+                    password,
+                    reply,
+                } => {
+                    let _ = reply.send(self.write(target, &content, password));
+                }
+                Command::Clear { target, reply } => {
+                    let _ = reply.send(self.clear(target));
+                }
+                Command::IsOwner(reply) => {
+                    let _ = reply.send(self.is_owner());
+                }
+            }
+        }
+    }
+
+    fn read(&mut self, target: Target, format: Format) -> Result<Content> {
+        self.with_clipboard(|clipboard| {
+            content::get(clipboard.get().clipboard(selection(target)), format)
+        })
+    }
+
+    fn write(&mut self, target: Target, content: &Content, password: bool) -> Result<()> {
+        self.with_clipboard(|clipboard| {
+            let set = clipboard.set().clipboard(selection(target));
+            match password {
+                true => content::set(set.exclude_from_history(), content),
+                false => content::set(set, content),
+            }
+        })?;
+        if target == Target::Clipboard {
+            self.written = Some((content.format(), Sha256::digest(content.data()).into()));
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self, target: Target) -> Result<()> {
+        if target == Target::Clipboard {
+            self.written = None;
+        }
+        self.with_clipboard(|clipboard| {
+            clipboard
+                .clear_with()
+                .clipboard(selection(target))
+                .map_err(convert_error)
+        })
+    }
+
+    fn is_owner(&mut self) -> Result<bool> {
+        let Some((format, written)) = self.written else {
+            return Ok(false);
+        };
+
+        match self.read(Target::Clipboard, format) {
+            Ok(content) => Ok(Sha256::digest(content.data()).as_slice() == written),
+            Err(Error::NotFound(_)) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn with_clipboard<T>(&mut self, f: impl FnOnce(&mut Clipboard) -> Result<T>) -> Result<T> {
+        let clipboard = match &mut self.clipboard {
+            Some(clipboard) => clipboard,
+            None => self
+                .clipboard
+                .insert(Clipboard::new().map_err(convert_error)?),
+        };
+
+        f(clipboard).inspect_err(|e| {
+            // Only a platform error may be a lost connection, reconnect on the next request. An
+            // empty clipboard, invalid content or a busy clipboard keep the connection.
+            if matches!(e, Error::Platform(_)) {
+                self.clipboard = None;
+            }
+        })
+    }
+}
+
+fn selection(target: Target) -> LinuxClipboardKind {
+    match target {
+        Target::Clipboard => LinuxClipboardKind::Clipboard,
+        Target::Primary => LinuxClipboardKind::Primary,
+    }
+}
+
+#[cfg(all(test, feature = "manual_test"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_owner() {
+        let holder = holder().unwrap();
+        futures::executor::block_on(async {
+            let content = Content::Text("Hello world!".to_string());
+
+            holder
+                .write(Target::Clipboard, &content, false)
+                .await
+                .unwrap();
+            assert!(holder.is_owner().await.unwrap());
+            assert_eq!(
+                content,
+                holder.read(Target::Clipboard, Format::Text).await.unwrap()
+            );
+
+            holder.clear(Target::Clipboard).await.unwrap();
+            assert!(!holder.is_owner().await.unwrap());
+        })
+    }
+}
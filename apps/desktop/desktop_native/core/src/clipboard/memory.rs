@@ -0,0 +1,153 @@
+//! A clipboard kept in memory, to test the code copying to the clipboard without a display
+//! server.
+
+use std::{collections::HashMap, sync::Mutex};
+
+use crate::error::{Error, Result};
+
+use super::{ClipboardBackend, Content, Format, Target};
+
+#[derive(Default)]
+pub struct MemoryClipboard {
+    /// The content of each target and whether it was written as a password.
+    content: Mutex<HashMap<Target, (Content, bool)>>,
+}
+
+impl MemoryClipboard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether the content of [`Target::Clipboard`] was written as a password, i.e. excluded from
+    /// the clipboard history.
+    pub fn is_password(&self) -> bool {
+        self.content
+            .lock()
+            .expect("Mutex is not poisoned")
+            .get(&Target::Clipboard)
+            .is_some_and(|(_, password)| *password)
+    }
+}
+
+impl ClipboardBackend for MemoryClipboard {
+    fn read(&self, target: Target, format: Format) -> Result<Content> {
+        self.content
+            .lock()
+            .expect("Mutex is not poisoned")
+            .get(&target)
+            .map(|(content, _)| content)
+            .filter(|content| content.format() == format)
+            .cloned()
+            .ok_or(Error::NotFound(format!(
+                "The clipboard holds no {:?}",
+                format
+            )))
+    }
+
+    fn write(&self, target: Target, content: &Content, password: bool) -> Result<()> {
+        content.validate()?;
+        self.content
+            .lock()
+            .expect("Mutex is not poisoned")
+            .insert(target, (content.clone(), password));
+        Ok(())
+    }
+
+    fn clear(&self, target: Target) -> Result<()> {
+        self.content
+            .lock()
+            .expect("Mutex is not poisoned")
+            .remove(&target);
+        Ok(())
+    }
+
+    // There are no other applications to replace the content
+    fn is_owner(&self) -> Result<bool> {
+        Ok(self
+            .content
+            .lock()
+            .expect("Mutex is not poisoned")
+            .contains_key(&Target::Clipboard))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clipboard::Image;
+
+    #[test]
+    fn html_round_trip() {
+        let clipboard = MemoryClipboard::new();
+        let content = Content::Html {
+            html: "<b>Hello</b>".to_string(),
+            alt_text: Some("Hello".to_string()),
+        };
+
+        clipboard.write(Target::Clipboard, &content, false).unwrap();
+        assert_eq!(
+            content,
+            clipboard.read(Target::Clipboard, Format::Html).unwrap()
+        );
+        assert!(matches!(
+            clipboard.read(Target::Clipboard, Format::Text),
+            Err(Error::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn image_round_trip() {
+        let clipboard = MemoryClipboard::new();
+        let content = Content::Image(Image {
+            width: 2,
+            height: 1,
+            bytes: vec![0xFF, 0, 0, 0xFF, 0, 0xFF, 0, 0xFF],
+        });
+
+        clipboard.write(Target::Clipboard, &content, false).unwrap();
+        assert_eq!(
+            content,
+            clipboard.read(Target::Clipboard, Format::Image).unwrap()
+        );
+    }
+
+    #[test]
+    fn invalid_image_is_rejected() {
+        let clipboard = MemoryClipboard::new();
+        let content = Content::Image(Image {
+            width: 2,
+            height: 2,
+            bytes: vec![0; 4],
+        });
+
+        assert!(matches!(
+            clipboard.write(Target::Clipboard, &content, false),
+            Err(Error::InvalidInput(_))
+        ));
+        assert!(!clipboard.is_owner().unwrap());
+    }
+
+    #[test]
+    fn primary_is_separate() {
+        let clipboard = MemoryClipboard::new();
+        let primary = Content::Text("selected".to_string());
+        let copied = Content::Text("copied".to_string());
+
+        clipboard.write(Target::Primary, &primary, false).unwrap();
+        clipboard.write(Target::Clipboard, &copied, true).unwrap();
+        assert_eq!(
+            primary,
+            clipboard.read(Target::Primary, Format::Text).unwrap()
+        );
+
+        clipboard.clear(Target::Clipboard).unwrap();
+        assert_eq!(
+            primary,
+            clipboard.read(Target::Primary, Format::Text).unwrap()
+        );
+        assert!(matches!(
+            clipboard.read(Target::Clipboard, Format::Text),
+            Err(Error::NotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,262 @@
+use std::{
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+#[cfg(not(target_os = "linux"))]
+use arboard::{Clipboard, Set};
+
+use crate::error::{Error, Result};
+
+mod auto_clear;
+use auto_clear::AutoClear;
+mod content;
+pub use content::{Content, Format, Image, Target};
+#[cfg(target_os = "linux")]
+mod holder;
+#[cfg(target_os = "linux")]
+pub use holder::{holder, ClipboardHolder};
+#[cfg(any(test, feature = "mock"))]
+mod memory;
+#[cfg(any(test, feature = "mock"))]
+pub use memory::MemoryClipboard;
+
+/// A clipboard holding text, HTML or images.
+pub trait ClipboardBackend: Send + Sync {
+    /// Read the content in the format, [`Error::NotFound`] if the clipboard holds none.
+    fn read(&self, target: Target, format: Format) -> Result<Content>;
+    /// Replace the content of the clipboard. Passwords are excluded from the clipboard history
+    /// where the platform supports it.
+    fn write(&self, target: Target, content: &Content, password: bool) -> Result<()>;
+    fn clear(&self, target: Target) -> Result<()>;
+    /// Whether [`Target::Clipboard`] still holds the content written last, i.e. it wasn't
+    /// replaced by another application or cleared since.
+    fn is_owner(&self) -> Result<bool>;
+}
+
+/// The clipboard of the OS. On Linux the clipboard is owned by the [`holder`] thread.
+pub struct OsClipboard;
+
+#[cfg(not(target_os = "linux"))]
+impl ClipboardBackend for OsClipboard {
+    fn read(&self, target: Target, format: Format) -> Result<Content> {
+        check_target(target)?;
+        let mut clipboard = Clipboard::new().map_err(convert_error)?;
+
+        content::get(clipboard.get(), format)
+    }
+
+    fn write(&self, target: Target, content: &Content, password: bool) -> Result<()> {
+        check_target(target)?;
+        let mut clipboard = Clipboard::new().map_err(convert_error)?;
+
+        content::set(clipboard_set(clipboard.set(), password), content)
+    }
+
+    fn clear(&self, target: Target) -> Result<()> {
+        check_target(target)?;
+        let mut clipboard = Clipboard::new().map_err(convert_error)?;
+
+        clipboard.clear().map_err(convert_error)
+    }
+
+    fn is_owner(&self) -> Result<bool> {
+        Err(Error::Unsupported(
+            "The clipboard owner is only tracked on Linux".to_string(),
+        ))
+    }
+}
+
+// Blocks until the holder thread replied, the free functions below await it instead
+#[cfg(target_os = "linux")]
+impl ClipboardBackend for OsClipboard {
+    fn read(&self, target: Target, format: Format) -> Result<Content> {
+        futures::executor::block_on(holder()?.read(target, format))
+    }
+
+    fn write(&self, target: Target, content: &Content, password: bool) -> Result<()> {
+        futures::executor::block_on(holder()?.write(target, content, password))
+    }
+
+    fn clear(&self, target: Target) -> Result<()> {
+        futures::executor::block_on(holder()?.clear(target))
+    }
+
+    fn is_owner(&self) -> Result<bool> {
+        futures::executor::block_on(holder()?.is_owner())
+    }
+}
+
+// The backend used by the free functions below, the OS clipboard when unset
+static BACKEND: RwLock<Option<Arc<dyn ClipboardBackend>>> = RwLock::new(None);
+
+/// Select the backend used by the free functions of this module.
+pub fn set_backend(backend: Arc<dyn ClipboardBackend>) {
+    *BACKEND.write().expect("RwLock is not poisoned") = Some(backend);
+}
+
+/// Go back to the OS clipboard.
+pub fn reset_backend() {
+    *BACKEND.write().expect("RwLock is not poisoned") = None;
+}
+
+// The backend set with set_backend, the OS clipboard is used when there is none
+fn selected_backend() -> Option<Arc<dyn ClipboardBackend>> {
+    BACKEND.read().expect("RwLock is not poisoned").clone()
+}
+
+fn backend() -> Arc<dyn ClipboardBackend> {
+    selected_backend().unwrap_or_else(|| Arc::new(OsClipboard))
+}
+
+/// Read the text of the clipboard.
+pub async fn read() -> Result<String> {
+    read_content(Target::Clipboard, Format::Text)
+        .await?
+        .into_text()
+}
+
+/// Replace the content of the clipboard with the text.
+pub async fn write(text: &str, password: bool) -> Result<()> {
+    write_content(
+        Target::Clipboard,
+        &Content::Text(text.to_string()),
+        password,
+    )
+    .await
+}
+
+// On Linux the OS clipboard is served by the holder thread, whose replies are awaited. arboard
+// only has a blocking API on the other platforms.
+
+pub async fn read_content(target: Target, format: Format) -> Result<Content> {
+    match selected_backend() {
+        Some(backend) => backend.read(target, format),
+        #[cfg(target_os = "linux")]
+        None => holder()?.read(target, format).await,
+        #[cfg(not(target_os = "linux"))]
+        None => OsClipboard.read(target, format),
+    }
+}
+
+pub async fn write_content(target: Target, content: &Content, password: bool) -> Result<()> {
+    match selected_backend() {
+        Some(backend) => backend.write(target, content, password),
+        #[cfg(target_os = "linux")]
+        None => holder()?.write(target, content, password).await,
+        #[cfg(not(target_os = "linux"))]
+        None => OsClipboard.write(target, content, password),
+    }
+}
+
+pub async fn clear(target: Target) -> Result<()> {
+    match selected_backend() {
+        Some(backend) => backend.clear(target),
+        #[cfg(target_os = "linux")]
+        None => holder()?.clear(target).await,
+        #[cfg(not(target_os = "linux"))]
+        None => OsClipboard.clear(target),
+    }
+}
+
+/// Whether the clipboard still holds the text written last. Only supported on Linux, where this
+/// process owns the clipboard content until another application replaces it, the other
+/// platforms fail with [`Error::Unsupported`].
+pub async fn is_owner() -> Result<bool> {
+    match selected_backend() {
+        Some(backend) => backend.is_owner(),
+        #[cfg(target_os = "linux")]
+        None => holder()?.is_owner().await,
+        #[cfg(not(target_os = "linux"))]
+        None => OsClipboard.is_owner(),
+    }
+}
+
+// Clears the text written by write_with_timeout
+static AUTO_CLEAR: AutoClear = AutoClear::new();
+
+/// Like [`write`], clearing the clipboard after the timeout unless its content was replaced in
+/// the meantime, e.g. by the user copying something else. Replaces the clear scheduled by the
+/// previous call.
+pub async fn write_with_timeout(text: &str, password: bool, timeout: Duration) -> Result<()> {
+    AUTO_CLEAR
+        .write(backend(), text, timeout, write(text, password))
+        .await
+}
+
+/// Cancel the clear scheduled by [`write_with_timeout`], if any.
+pub fn cancel_clear() {
+    AUTO_CLEAR.cancel();
+}
+
+/// Run the clear scheduled by [`write_with_timeout`] now, e.g. when the vault is locked. The
+/// clipboard is only cleared if it still holds the text.
+pub fn clear_pending() {
+    AUTO_CLEAR.clear_pending();
+}
+
+// Convert the arboard errors to their matching error kinds
+fn convert_error(e: arboard::Error) -> Error {
+    match e {
+        arboard::Error::ContentNotAvailable => Error::NotFound(e.to_string()),
+        arboard::Error::ClipboardNotSupported => Error::Unsupported(e.to_string()),
+        arboard::Error::ClipboardOccupied => Error::ServiceUnavailable(e.to_string()),
+        arboard::Error::ConversionFailure => Error::InvalidInput(e.to_string()),
+        _ => Error::Platform(e.to_string()),
+    }
+}
+
+// The PRIMARY selection only exists on X11 and Wayland
+#[cfg(not(target_os = "linux"))]
+fn check_target(target: Target) -> Result<()> {
+    match target {
+        Target::Clipboard => Ok(()),
+        Target::Primary => Err(Error::Unsupported(
+            "The primary selection is only available on Linux".to_string(),
+        )),
+    }
+}
+
+// Exclude from windows clipboard history
+#[cfg(target_os = "windows")]
+fn clipboard_set(set: Set, password: bool) -> Set {
+    use arboard::SetExtWindows;
+
+    if password {
+        set.exclude_from_cloud().exclude_from_history()
+    } else {
+        set
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn clipboard_set(set: Set, password: bool) -> Set {
+    use arboard::SetExtApple;
+
+    if password {
+        set.exclude_from_history()
+    } else {
+        set
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(any(feature = "manual_test", not(target_os = "linux")))]
+    fn test_write_read() {
+        let message = "Hello world!";
+
+        let content = Content::Text(message.to_string());
+
+        OsClipboard
+            .write(Target::Clipboard, &content, false)
+            .unwrap();
+        assert_eq!(
+            content,
+            OsClipboard.read(Target::Clipboard, Format::Text).unwrap()
+        );
+    }
+}
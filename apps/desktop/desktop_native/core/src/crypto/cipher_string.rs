@@ -41,6 +41,11 @@ pub enum CipherString {
         mac: [u8; 32],
         data: Vec<u8>,
     },
+    // 7
+    AesGcm256_B64 {
+        nonce: [u8; 12],
+        data: Vec<u8>,
+    },
 }
 
 // We manually implement these to make sure we don't print any sensitive data
@@ -123,7 +128,41 @@ impl FromStr for CipherString {
                 }
             }
             ("5" | "6", 2) => {
-                unimplemented!()
+                let data_str = parts[0];
+                let mac_str = parts[1];
+
+                let mac = base64_engine
+                    .decode(mac_str)
+                    .map_err(CSParseError::InvalidBase64)?
+                    .try_into()
+                    .map_err(invalid_len_error(32))?;
+
+                let data = base64_engine
+                    .decode(data_str)
+                    .map_err(CSParseError::InvalidBase64)?;
+
+                if enc_type == "5" {
+                    Ok(CipherString::Rsa2048_OaepSha256_HmacSha256_B64 { mac, data })
+                } else {
+                    Ok(CipherString::Rsa2048_OaepSha1_HmacSha256_B64 { mac, data })
+                }
+            }
+
+            ("7", 2) => {
+                let nonce_str = parts[0];
+                let data_str = parts[1];
+
+                let nonce = base64_engine
+                    .decode(nonce_str)
+                    .map_err(CSParseError::InvalidBase64)?
+                    .try_into()
+                    .map_err(invalid_len_error(12))?;
+
+                let data = base64_engine
+                    .decode(data_str)
+                    .map_err(CSParseError::InvalidBase64)?;
+
+                Ok(CipherString::AesGcm256_B64 { nonce, data })
             }
 
             (enc_type, parts) => Err(CSParseError::InvalidType {
@@ -170,6 +209,10 @@ impl Display for CipherString {
                 parts.push(data);
                 parts.push(mac);
             }
+            CipherString::AesGcm256_B64 { nonce, data } => {
+                parts.push(nonce);
+                parts.push(data);
+            }
         }
 
         for i in 0..parts.len() {
@@ -194,6 +237,7 @@ impl CipherString {
             CipherString::Rsa2048_OaepSha1_B64 { .. } => 4,
             CipherString::Rsa2048_OaepSha256_HmacSha256_B64 { .. } => 5,
             CipherString::Rsa2048_OaepSha1_HmacSha256_B64 { .. } => 6,
+            CipherString::AesGcm256_B64 { .. } => 7,
         }
     }
 
@@ -206,7 +250,148 @@ impl CipherString {
             4 => "Rsa2048_OaepSha1_B64",
             5 => "Rsa2048_OaepSha256_HmacSha256_B64",
             6 => "Rsa2048_OaepSha1_HmacSha256_B64",
+            7 => "AesGcm256_B64",
             _ => "Unknown",
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IV: &str = "l9fhDUP/wDJcKwmEzcb/3w==";
+    const MAC: &str = "A+ZQjJMbvMIn10PLr/4aLOIFSRpcdUOtrGhFa9qmJvo=";
+    const DATA: &str = "uP4LcqoCCj5FxBDP77NV6Q==";
+
+    fn assert_roundtrip(cipher_string: &str) -> CipherString {
+        let parsed = CipherString::from_str(cipher_string).unwrap();
+        assert_eq!(parsed.to_string(), cipher_string);
+        parsed
+    }
+
+    #[test]
+    fn parse_aes_cbc256_b64() {
+        let parsed = assert_roundtrip(&format!("0.{IV}|{DATA}"));
+        assert!(matches!(parsed, CipherString::AesCbc256_B64 { .. }));
+    }
+
+    #[test]
+    fn parse_aes_cbc128_hmac_sha256_b64() {
+        let parsed = assert_roundtrip(&format!("1.{IV}|{DATA}|{MAC}"));
+        assert!(matches!(
+            parsed,
+            CipherString::AesCbc128_HmacSha256_B64 { .. }
+        ));
+    }
+
+    #[test]
+    fn parse_aes_cbc256_hmac_sha256_b64() {
+        let parsed = assert_roundtrip(&format!("2.{IV}|{DATA}|{MAC}"));
+        assert!(matches!(
+            parsed,
+            CipherString::AesCbc256_HmacSha256_B64 { .. }
+        ));
+    }
+
+    #[test]
+    fn parse_rsa2048_oaep_sha256_b64() {
+        let parsed = assert_roundtrip(&format!("3.{DATA}"));
+        assert!(matches!(
+            parsed,
+            CipherString::Rsa2048_OaepSha256_B64 { .. }
+        ));
+    }
+
+    #[test]
+    fn parse_rsa2048_oaep_sha1_b64() {
+        let parsed = assert_roundtrip(&format!("4.{DATA}"));
+        assert!(matches!(parsed, CipherString::Rsa2048_OaepSha1_B64 { .. }));
+    }
+
+    #[test]
+    fn parse_rsa2048_oaep_sha256_hmac_sha256_b64() {
+        let parsed = assert_roundtrip(&format!("5.{DATA}|{MAC}"));
+        match parsed {
+            CipherString::Rsa2048_OaepSha256_HmacSha256_B64 { mac, data } => {
+                assert_eq!(base64_engine.encode(mac), MAC);
+                assert_eq!(base64_engine.encode(data), DATA);
+            }
+            _ => panic!("Invalid cipher string"),
+        }
+    }
+
+    #[test]
+    fn parse_rsa2048_oaep_sha1_hmac_sha256_b64() {
+        let parsed = assert_roundtrip(&format!("6.{DATA}|{MAC}"));
+        match parsed {
+            CipherString::Rsa2048_OaepSha1_HmacSha256_B64 { mac, data } => {
+                assert_eq!(base64_engine.encode(mac), MAC);
+                assert_eq!(base64_engine.encode(data), DATA);
+            }
+            _ => panic!("Invalid cipher string"),
+        }
+    }
+
+    #[test]
+    fn parse_aes_gcm256_b64() {
+        let nonce = "AAECAwQFBgcICQoL";
+        let parsed = assert_roundtrip(&format!("7.{nonce}|{DATA}"));
+        match parsed {
+            CipherString::AesGcm256_B64 { nonce, data } => {
+                assert_eq!(nonce, [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11]);
+                assert_eq!(base64_engine.encode(data), DATA);
+            }
+            _ => panic!("Invalid cipher string"),
+        }
+    }
+
+    #[test]
+    fn parse_aes_gcm_rejects_invalid_nonce_length() {
+        let result = CipherString::from_str(&format!("7.{IV}|{DATA}"));
+        assert!(matches!(
+            result,
+            Err(Error::InvalidCipherString(
+                CSParseError::InvalidBase64Length {
+                    expected: 12,
+                    got: 16
+                }
+            ))
+        ));
+    }
+
+    #[test]
+    fn parse_rsa_hmac_rejects_invalid_mac_length() {
+        let result = CipherString::from_str(&format!("5.{DATA}|{IV}"));
+        assert!(matches!(
+            result,
+            Err(Error::InvalidCipherString(
+                CSParseError::InvalidBase64Length {
+                    expected: 32,
+                    got: 16
+                }
+            ))
+        ));
+    }
+
+    #[test]
+    fn parse_rejects_wrong_part_count() {
+        let result = CipherString::from_str(&format!("6.{DATA}"));
+        assert!(matches!(
+            result,
+            Err(Error::InvalidCipherString(CSParseError::InvalidType {
+                parts: 1,
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn parse_rejects_missing_type() {
+        let result = CipherString::from_str(DATA);
+        assert!(matches!(
+            result,
+            Err(Error::InvalidCipherString(CSParseError::NoType))
+        ));
+    }
+}
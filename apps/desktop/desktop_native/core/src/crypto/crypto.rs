@@ -1,39 +1,384 @@
 //! Cryptographic primitives used in the SDK
 
 use aes::cipher::{
-    block_padding::Pkcs7, generic_array::GenericArray, typenum::U32, BlockDecryptMut,
-    BlockEncryptMut, KeyIvInit,
+    block_padding::Pkcs7,
+    generic_array::GenericArray,
+    typenum::{U32, U64},
+    BlockDecryptMut, BlockEncryptMut, KeyIvInit,
 };
+use aes_gcm::{
+    aead::{Aead, Payload},
+    AeadCore, Aes256Gcm, KeyInit, Nonce,
+};
+use hmac::{Mac, SimpleHmac};
+use rsa::{
+    pkcs8::{DecodePrivateKey, DecodePublicKey},
+    Oaep, RsaPrivateKey, RsaPublicKey,
+};
+use sha1::Sha1;
+use sha2::Sha256;
+use subtle::ConstantTimeEq;
 
 use crate::error::{CryptoError, Result};
 
-use super::CipherString;
+use super::{CipherString, SecretBytes, SymmetricKey};
+
+type HmacSha256 = SimpleHmac<Sha256>;
+
+pub fn decrypt_aes256(iv: &[u8; 16], data: &Vec<u8>, key: &SymmetricKey) -> Result<SecretBytes> {
+    decrypt_aes256_cbc(iv, data, key.as_array())
+}
+
+pub fn encrypt_aes256(data_dec: &[u8], iv: [u8; 16], key: &SymmetricKey) -> Result<CipherString> {
+    let data = cbc::Encryptor::<aes::Aes256>::new(key.as_array(), &iv.into())
+        .encrypt_padded_vec_mut::<Pkcs7>(data_dec);
+
+    Ok(CipherString::AesCbc256_B64 { iv, data })
+}
 
-pub fn decrypt_aes256(
+/// Decrypt an `AesCbc256_HmacSha256_B64` payload.
+///
+/// The first half of `key` is used as the AES-256 key and the second half as the HMAC-SHA256
+/// key. The MAC over `iv || data` is verified in constant time before any decryption happens.
+pub fn decrypt_aes256_hmac(
     iv: &[u8; 16],
+    mac: &[u8; 32],
     data: &Vec<u8>,
-    key: GenericArray<u8, U32>,
-) -> Result<Vec<u8>> {
+    key: &SymmetricKey<U64>,
+) -> Result<SecretBytes> {
+    let (enc_key, mac_key) = split_key(key);
+
+    let computed_mac = generate_mac(mac_key, iv, data)?;
+    if computed_mac.ct_ne(mac).into() {
+        return Err(CryptoError::InvalidMac.into());
+    }
+
+    decrypt_aes256_cbc(iv, data, enc_key)
+}
+
+/// Encrypt `data_dec` into an `AesCbc256_HmacSha256_B64` cipher string.
+///
+/// See [`decrypt_aes256_hmac`] for how the 64 byte key is split.
+pub fn encrypt_aes256_hmac(
+    data_dec: &[u8],
+    iv: [u8; 16],
+    key: &SymmetricKey<U64>,
+) -> Result<CipherString> {
+    let (enc_key, mac_key) = split_key(key);
+
+    let data = cbc::Encryptor::<aes::Aes256>::new(enc_key, &iv.into())
+        .encrypt_padded_vec_mut::<Pkcs7>(data_dec);
+    let mac = generate_mac(mac_key, &iv, &data)?;
+
+    Ok(CipherString::AesCbc256_HmacSha256_B64 { iv, mac, data })
+}
+
+/// Encrypt `data_dec` into an `AesGcm256_B64` cipher string using a random nonce.
+///
+/// `associated_data` is authenticated but not encrypted, and the same value must be provided to
+/// [`decrypt_aes256_gcm`]. Use it to bind the cipher string to the context it is stored in.
+pub fn encrypt_aes256_gcm(
+    data_dec: &[u8],
+    key: &SymmetricKey,
+    associated_data: &[u8],
+) -> Result<CipherString> {
+    let nonce = Aes256Gcm::generate_nonce(&mut rand::thread_rng());
+    let data = Aes256Gcm::new(key.as_array())
+        .encrypt(
+            &nonce,
+            Payload {
+                msg: data_dec,
+                aad: associated_data,
+            },
+        )
+        .map_err(|_| CryptoError::KeyEncrypt)?;
+
+    Ok(CipherString::AesGcm256_B64 {
+        nonce: nonce.into(),
+        data,
+    })
+}
+
+/// Decrypt an `AesGcm256_B64` payload, verifying its tag and `associated_data`.
+pub fn decrypt_aes256_gcm(
+    nonce: &[u8; 12],
+    data: &[u8],
+    key: &SymmetricKey,
+    associated_data: &[u8],
+) -> Result<SecretBytes> {
+    let decrypted = Aes256Gcm::new(key.as_array())
+        .decrypt(
+            Nonce::from_slice(nonce),
+            Payload {
+                msg: data,
+                aad: associated_data,
+            },
+        )
+        .map_err(|_| CryptoError::InvalidMac)?;
+
+    Ok(decrypted.into())
+}
+
+/// The hash function used for RSA-OAEP padding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OaepHash {
+    Sha1,
+    Sha256,
+}
+
+impl OaepHash {
+    fn padding(self) -> Oaep {
+        match self {
+            OaepHash::Sha1 => Oaep::new::<Sha1>(),
+            OaepHash::Sha256 => Oaep::new::<Sha256>(),
+        }
+    }
+}
+
+/// Decrypt an RSA-OAEP cipher string (types 3 to 6) using a DER encoded PKCS#8 private key.
+///
+/// The MAC of the deprecated `*_HmacSha256_B64` variants is not verified, matching the behaviour
+/// of the other Bitwarden clients.
+pub fn decrypt_rsa(secret: &CipherString, private_key_der: &[u8]) -> Result<SecretBytes> {
+    let (data, hash) = match secret {
+        CipherString::Rsa2048_OaepSha256_B64 { data }
+        | CipherString::Rsa2048_OaepSha256_HmacSha256_B64 { data, .. } => (data, OaepHash::Sha256),
+        CipherString::Rsa2048_OaepSha1_B64 { data }
+        | CipherString::Rsa2048_OaepSha1_HmacSha256_B64 { data, .. } => (data, OaepHash::Sha1),
+        _ => return Err(CryptoError::InvalidEncType.into()),
+    };
+
+    let private_key =
+        RsaPrivateKey::from_pkcs8_der(private_key_der).map_err(|_| CryptoError::InvalidKey)?;
+
+    Ok(private_key
+        .decrypt(hash.padding(), data)
+        .map_err(|_| CryptoError::KeyDecrypt)?
+        .into())
+}
+
+/// Encrypt `data_dec` with a DER encoded SubjectPublicKeyInfo RSA public key.
+///
+/// Produces `Rsa2048_OaepSha1_B64` or `Rsa2048_OaepSha256_B64` depending on `hash`.
+pub fn encrypt_rsa(data_dec: &[u8], public_key_der: &[u8], hash: OaepHash) -> Result<CipherString> {
+    let public_key =
+        RsaPublicKey::from_public_key_der(public_key_der).map_err(|_| CryptoError::InvalidKey)?;
+
+    let data = public_key
+        .encrypt(&mut rand::thread_rng(), hash.padding(), data_dec)
+        .map_err(|_| CryptoError::KeyEncrypt)?;
+
+    Ok(match hash {
+        OaepHash::Sha1 => CipherString::Rsa2048_OaepSha1_B64 { data },
+        OaepHash::Sha256 => CipherString::Rsa2048_OaepSha256_B64 { data },
+    })
+}
+
+fn decrypt_aes256_cbc(
+    iv: &[u8; 16],
+    data: &[u8],
+    key: &GenericArray<u8, U32>,
+) -> Result<SecretBytes> {
     let iv = GenericArray::from_slice(iv);
-    let mut data = data.clone();
-    let decrypted_key_slice = cbc::Decryptor::<aes::Aes256>::new(&key, iv)
-        .decrypt_padded_mut::<Pkcs7>(&mut data)
+    let mut data = SecretBytes::from(data.to_vec());
+    let decrypted_key_slice = cbc::Decryptor::<aes::Aes256>::new(key, iv)
+        .decrypt_padded_mut::<Pkcs7>(data.as_mut_vec())
         .map_err(|_| CryptoError::KeyDecrypt)?;
 
     // Data is decrypted in place and returns a subslice of the original Vec, to avoid cloning it, we truncate to the subslice length
     let decrypted_len = decrypted_key_slice.len();
-    data.truncate(decrypted_len);
+    data.as_mut_vec().truncate(decrypted_len);
 
     Ok(data)
 }
 
-pub fn encrypt_aes256(
-    data_dec: &[u8],
-    iv: [u8; 16],
-    key: GenericArray<u8, U32>,
-) -> Result<CipherString> {
-    let data = cbc::Encryptor::<aes::Aes256>::new(&key, &iv.into())
-        .encrypt_padded_vec_mut::<Pkcs7>(data_dec);
+fn split_key(key: &SymmetricKey<U64>) -> (&GenericArray<u8, U32>, &GenericArray<u8, U32>) {
+    (
+        GenericArray::from_slice(&key.as_slice()[..32]),
+        GenericArray::from_slice(&key.as_slice()[32..]),
+    )
+}
 
-    Ok(CipherString::AesCbc256_B64 { iv, data })
+fn generate_mac(mac_key: &[u8], iv: &[u8], data: &[u8]) -> Result<[u8; 32]> {
+    let mut hmac =
+        <HmacSha256 as Mac>::new_from_slice(mac_key).map_err(|_| CryptoError::InvalidKeyLength)?;
+    hmac.update(iv);
+    hmac.update(data);
+    Ok(hmac.finalize().into_bytes().into())
+}
+
+#[cfg(test)]
+mod tests {
+    use rsa::pkcs8::{EncodePrivateKey, EncodePublicKey};
+
+    use super::*;
+
+    fn key() -> SymmetricKey<U64> {
+        SymmetricKey::from(GenericArray::from_iter(0..64))
+    }
+
+    #[test]
+    fn encrypt_decrypt_aes256_hmac_roundtrip() {
+        let iv = [7u8; 16];
+        let encrypted = encrypt_aes256_hmac(b"secret", iv, &key()).unwrap();
+
+        match encrypted {
+            CipherString::AesCbc256_HmacSha256_B64 { iv, mac, data } => {
+                let decrypted = decrypt_aes256_hmac(&iv, &mac, &data, &key()).unwrap();
+                assert_eq!(&*decrypted, b"secret");
+            }
+            _ => panic!("Invalid cipher string"),
+        }
+    }
+
+    #[test]
+    fn decrypt_aes256_hmac_rejects_tampered_data() {
+        let iv = [7u8; 16];
+        let encrypted = encrypt_aes256_hmac(b"secret", iv, &key()).unwrap();
+
+        match encrypted {
+            CipherString::AesCbc256_HmacSha256_B64 { iv, mac, mut data } => {
+                data[0] ^= 1;
+                let result = decrypt_aes256_hmac(&iv, &mac, &data, &key());
+                assert!(matches!(
+                    result,
+                    Err(crate::error::Error::Crypto(CryptoError::InvalidMac))
+                ));
+            }
+            _ => panic!("Invalid cipher string"),
+        }
+    }
+
+    #[test]
+    fn decrypt_aes256_hmac_rejects_wrong_key() {
+        let iv = [7u8; 16];
+        let encrypted = encrypt_aes256_hmac(b"secret", iv, &key()).unwrap();
+        let mut wrong_key = key();
+        wrong_key.as_mut_slice()[63] ^= 1;
+
+        match encrypted {
+            CipherString::AesCbc256_HmacSha256_B64 { iv, mac, data } => {
+                let result = decrypt_aes256_hmac(&iv, &mac, &data, &wrong_key);
+                assert!(matches!(
+                    result,
+                    Err(crate::error::Error::Crypto(CryptoError::InvalidMac))
+                ));
+            }
+            _ => panic!("Invalid cipher string"),
+        }
+    }
+
+    #[test]
+    fn encrypt_decrypt_aes256_gcm_roundtrip() {
+        let key = SymmetricKey::from(GenericArray::<u8, U32>::from_iter(0..32));
+        let encrypted = encrypt_aes256_gcm(b"secret", &key, b"service/account").unwrap();
+        let parsed: CipherString = encrypted.to_string().parse().unwrap();
+
+        match parsed {
+            CipherString::AesGcm256_B64 { nonce, data } => {
+                let decrypted = decrypt_aes256_gcm(&nonce, &data, &key, b"service/account");
+                assert_eq!(&*decrypted.unwrap(), b"secret");
+            }
+            _ => panic!("Invalid cipher string"),
+        }
+    }
+
+    #[test]
+    fn decrypt_aes256_gcm_rejects_wrong_associated_data() {
+        let key = SymmetricKey::from(GenericArray::<u8, U32>::from_iter(0..32));
+        let encrypted = encrypt_aes256_gcm(b"secret", &key, b"service/account").unwrap();
+
+        match encrypted {
+            CipherString::AesGcm256_B64 { nonce, data } => {
+                let result = decrypt_aes256_gcm(&nonce, &data, &key, b"service/other");
+                assert!(matches!(
+                    result,
+                    Err(crate::error::Error::Crypto(CryptoError::InvalidMac))
+                ));
+            }
+            _ => panic!("Invalid cipher string"),
+        }
+    }
+
+    #[test]
+    fn decrypt_aes256_gcm_rejects_tampered_data() {
+        let key = SymmetricKey::from(GenericArray::<u8, U32>::from_iter(0..32));
+        let encrypted = encrypt_aes256_gcm(b"secret", &key, b"").unwrap();
+
+        match encrypted {
+            CipherString::AesGcm256_B64 { nonce, mut data } => {
+                data[0] ^= 1;
+                let result = decrypt_aes256_gcm(&nonce, &data, &key, b"");
+                assert!(matches!(
+                    result,
+                    Err(crate::error::Error::Crypto(CryptoError::InvalidMac))
+                ));
+            }
+            _ => panic!("Invalid cipher string"),
+        }
+    }
+
+    fn rsa_keypair() -> (Vec<u8>, Vec<u8>) {
+        let private_key = RsaPrivateKey::new(&mut rand::thread_rng(), 2048).unwrap();
+        let private_der = private_key.to_pkcs8_der().unwrap().as_bytes().to_vec();
+        let public_der = private_key
+            .to_public_key()
+            .to_public_key_der()
+            .unwrap()
+            .as_bytes()
+            .to_vec();
+        (private_der, public_der)
+    }
+
+    #[test]
+    fn encrypt_decrypt_rsa_roundtrip() {
+        let (private_der, public_der) = rsa_keypair();
+
+        for hash in [OaepHash::Sha1, OaepHash::Sha256] {
+            let encrypted = encrypt_rsa(b"secret", &public_der, hash).unwrap();
+            let parsed: CipherString = encrypted.to_string().parse().unwrap();
+            assert_eq!(&*decrypt_rsa(&parsed, &private_der).unwrap(), b"secret");
+        }
+    }
+
+    #[test]
+    fn decrypt_rsa_handles_hmac_variants() {
+        let (private_der, public_der) = rsa_keypair();
+
+        let data = match encrypt_rsa(b"secret", &public_der, OaepHash::Sha1).unwrap() {
+            CipherString::Rsa2048_OaepSha1_B64 { data } => data,
+            _ => panic!("Invalid cipher string"),
+        };
+        let secret = CipherString::Rsa2048_OaepSha1_HmacSha256_B64 {
+            mac: [0u8; 32],
+            data,
+        };
+        assert_eq!(&*decrypt_rsa(&secret, &private_der).unwrap(), b"secret");
+    }
+
+    #[test]
+    fn decrypt_rsa_rejects_mismatched_hash() {
+        let (private_der, public_der) = rsa_keypair();
+
+        let data = match encrypt_rsa(b"secret", &public_der, OaepHash::Sha1).unwrap() {
+            CipherString::Rsa2048_OaepSha1_B64 { data } => data,
+            _ => panic!("Invalid cipher string"),
+        };
+        let secret = CipherString::Rsa2048_OaepSha256_B64 { data };
+        assert!(matches!(
+            decrypt_rsa(&secret, &private_der),
+            Err(crate::error::Error::Crypto(CryptoError::KeyDecrypt))
+        ));
+    }
+
+    #[test]
+    fn decrypt_rsa_rejects_symmetric_cipher_string() {
+        let (private_der, _) = rsa_keypair();
+        let key = SymmetricKey::from(GenericArray::default());
+        let secret = encrypt_aes256(b"secret", [0u8; 16], &key).unwrap();
+        assert!(matches!(
+            decrypt_rsa(&secret, &private_der),
+            Err(crate::error::Error::Crypto(CryptoError::InvalidEncType))
+        ));
+    }
 }
@@ -0,0 +1,238 @@
+//! Key derivation functions used to derive and stretch master keys.
+
+use std::num::NonZeroU32;
+
+use aes::cipher::{
+    generic_array::{ArrayLength, GenericArray},
+    typenum::{U32, U64},
+};
+use argon2::{Algorithm, Argon2, Params, Version};
+use hkdf::Hkdf;
+use sha2::{Digest, Sha256};
+
+use crate::error::{CryptoError, Result};
+
+use super::SymmetricKey;
+
+pub const PBKDF2_MIN_ITERATIONS: u32 = 5000;
+
+pub const ARGON2_MIN_ITERATIONS: u32 = 2;
+pub const ARGON2_MIN_MEMORY_MIB: u32 = 16;
+
+/// The key derivation function and its parameters used to derive a master key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kdf {
+    Pbkdf2 {
+        iterations: NonZeroU32,
+    },
+    Argon2id {
+        iterations: NonZeroU32,
+        /// Memory cost in MiB
+        memory: NonZeroU32,
+        parallelism: NonZeroU32,
+    },
+}
+
+/// Derive a 32 byte master key from `password` and `salt` (usually the account email).
+///
+/// For Argon2id the salt is hashed with SHA-256 first, matching the other Bitwarden clients.
+pub fn derive_master_key(password: &[u8], salt: &[u8], kdf: &Kdf) -> Result<SymmetricKey> {
+    match *kdf {
+        Kdf::Pbkdf2 { iterations } => pbkdf2_sha256(password, salt, iterations.get()),
+        Kdf::Argon2id {
+            iterations,
+            memory,
+            parallelism,
+        } => argon2id(
+            password,
+            &Sha256::digest(salt),
+            iterations.get(),
+            memory.get(),
+            parallelism.get(),
+        ),
+    }
+}
+
+pub fn pbkdf2_sha256(password: &[u8], salt: &[u8], iterations: u32) -> Result<SymmetricKey> {
+    if iterations < PBKDF2_MIN_ITERATIONS {
+        return Err(CryptoError::InsufficientKdfParameters.into());
+    }
+
+    let mut key = SymmetricKey::from(GenericArray::<u8, U32>::default());
+    pbkdf2::pbkdf2_hmac::<Sha256>(password, salt, iterations, key.as_mut_slice());
+    Ok(key)
+}
+
+/// Derive a 32 byte key using Argon2id, with `memory` given in MiB.
+pub fn argon2id(
+    password: &[u8],
+    salt: &[u8],
+    iterations: u32,
+    memory: u32,
+    parallelism: u32,
+) -> Result<SymmetricKey> {
+    if iterations < ARGON2_MIN_ITERATIONS || memory < ARGON2_MIN_MEMORY_MIB {
+        return Err(CryptoError::InsufficientKdfParameters.into());
+    }
+
+    let memory_kib = memory
+        .checked_mul(1024)
+        .ok_or(CryptoError::InsufficientKdfParameters)?;
+    let params = Params::new(memory_kib, iterations, parallelism, Some(32))
+        .map_err(|_| CryptoError::InsufficientKdfParameters)?;
+
+    let mut key = SymmetricKey::from(GenericArray::<u8, U32>::default());
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(password, salt, key.as_mut_slice())
+        .map_err(|_| CryptoError::InsufficientKdfParameters)?;
+    Ok(key)
+}
+
+/// HKDF (extract and expand) `ikm` with `salt` and `info` into a key of `N` bytes.
+pub fn hkdf<N: ArrayLength<u8>>(ikm: &[u8], salt: &[u8], info: &[u8]) -> Result<SymmetricKey<N>> {
+    let hkdf = Hkdf::<Sha256>::new(Some(salt), ikm);
+
+    let mut key = SymmetricKey::from(GenericArray::<u8, N>::default());
+    hkdf.expand(info, key.as_mut_slice())
+        .map_err(|_| CryptoError::InvalidKeyLength)?;
+    Ok(key)
+}
+
+/// HKDF-Expand `prk` with `info` into a key of `N` bytes.
+pub fn hkdf_expand<N: ArrayLength<u8>>(prk: &[u8], info: &[u8]) -> Result<SymmetricKey<N>> {
+    let hkdf = Hkdf::<Sha256>::from_prk(prk).map_err(|_| CryptoError::InvalidKeyLength)?;
+
+    let mut key = SymmetricKey::from(GenericArray::<u8, N>::default());
+    hkdf.expand(info, key.as_mut_slice())
+        .map_err(|_| CryptoError::InvalidKeyLength)?;
+    Ok(key)
+}
+
+/// Stretch a 32 byte key into a 64 byte encryption + MAC key.
+///
+/// The encryption and MAC keys are HKDF-Expanded from the key using `"enc"` and `"mac"` as the
+/// info, which is the same stretching the Bitwarden clients use for master keys.
+pub fn stretch_key(key: &SymmetricKey) -> Result<SymmetricKey<U64>> {
+    let enc_key = hkdf_expand::<U32>(key.as_slice(), b"enc")?;
+    let mac_key = hkdf_expand::<U32>(key.as_slice(), b"mac")?;
+
+    let mut stretched = SymmetricKey::from(GenericArray::<u8, U64>::default());
+    stretched.as_mut_slice()[..32].copy_from_slice(enc_key.as_slice());
+    stretched.as_mut_slice()[32..].copy_from_slice(mac_key.as_slice());
+    Ok(stretched)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    // RFC 7914, section 11
+    #[test]
+    fn pbkdf2_sha256_matches_rfc7914() {
+        let key = pbkdf2_sha256(b"Password", b"NaCl", 80000).unwrap();
+        assert_eq!(
+            key.as_slice(),
+            hex("4ddcd8f60b98be21830cee5ef22701f9641a4418d04c0414aeff08876b34ab56")
+        );
+    }
+
+    #[test]
+    fn pbkdf2_sha256_rejects_low_iterations() {
+        assert!(matches!(
+            pbkdf2_sha256(b"password", b"salt", 1),
+            Err(crate::error::Error::Crypto(
+                CryptoError::InsufficientKdfParameters
+            ))
+        ));
+    }
+
+    #[test]
+    fn argon2id_depends_on_salt() {
+        let key = argon2id(b"password", b"saltsaltsalt", 2, 16, 1).unwrap();
+        assert_eq!(
+            key,
+            argon2id(b"password", b"saltsaltsalt", 2, 16, 1).unwrap()
+        );
+        assert_ne!(
+            key,
+            argon2id(b"password", b"othersaltsalt", 2, 16, 1).unwrap()
+        );
+    }
+
+    #[test]
+    fn argon2id_rejects_low_memory() {
+        assert!(matches!(
+            argon2id(b"password", b"saltsaltsalt", 2, 1, 1),
+            Err(crate::error::Error::Crypto(
+                CryptoError::InsufficientKdfParameters
+            ))
+        ));
+    }
+
+    #[test]
+    fn derive_master_key_uses_kdf() {
+        let pbkdf2 = Kdf::Pbkdf2 {
+            iterations: NonZeroU32::new(5000).unwrap(),
+        };
+        let argon2 = Kdf::Argon2id {
+            iterations: NonZeroU32::new(2).unwrap(),
+            memory: NonZeroU32::new(16).unwrap(),
+            parallelism: NonZeroU32::new(1).unwrap(),
+        };
+
+        assert_eq!(
+            derive_master_key(b"password", b"test@example.com", &pbkdf2).unwrap(),
+            pbkdf2_sha256(b"password", b"test@example.com", 5000).unwrap()
+        );
+        assert_eq!(
+            derive_master_key(b"password", b"test@example.com", &argon2).unwrap(),
+            argon2id(b"password", &Sha256::digest(b"test@example.com"), 2, 16, 1).unwrap()
+        );
+    }
+
+    // RFC 5869, test case 1
+    #[test]
+    fn hkdf_expand_matches_rfc5869() {
+        let prk = hex("077709362c2e32df0ddc3f0dc47bba6390b6c73bb50f9c3122ec844ad7c2b3e5");
+        let info = hex("f0f1f2f3f4f5f6f7f8f9");
+        let key = hkdf_expand::<U32>(&prk, &info).unwrap();
+        assert_eq!(
+            key.as_slice(),
+            hex("3cb25f25faacd57a90434f64d0362f2a2d2d0a90cf1a5a4c5db02d56ecc4c5bf")
+        );
+    }
+
+    // RFC 5869, test case 1
+    #[test]
+    fn hkdf_matches_rfc5869() {
+        let ikm = hex("0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b");
+        let salt = hex("000102030405060708090a0b0c");
+        let info = hex("f0f1f2f3f4f5f6f7f8f9");
+        let key = hkdf::<U32>(&ikm, &salt, &info).unwrap();
+        assert_eq!(
+            key.as_slice(),
+            hex("3cb25f25faacd57a90434f64d0362f2a2d2d0a90cf1a5a4c5db02d56ecc4c5bf")
+        );
+    }
+
+    #[test]
+    fn stretch_key_is_deterministic_and_split() {
+        let key = SymmetricKey::from(GenericArray::<u8, U32>::from_iter(0..32));
+        let stretched = stretch_key(&key).unwrap();
+        assert_eq!(stretched, stretch_key(&key).unwrap());
+        assert_eq!(
+            &stretched.as_slice()[..32],
+            hkdf_expand::<U32>(key.as_slice(), b"enc")
+                .unwrap()
+                .as_slice()
+        );
+        assert_ne!(stretched.as_slice()[..32], stretched.as_slice()[32..]);
+    }
+}
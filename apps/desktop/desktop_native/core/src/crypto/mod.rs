@@ -1,5 +1,9 @@
 pub use cipher_string::*;
 pub use crypto::*;
+pub use secret::*;
+
+pub mod kdf;
 
 mod cipher_string;
 mod crypto;
+mod secret;
@@ -0,0 +1,175 @@
+//! Containers for key material and plaintexts that are wiped from memory when dropped.
+
+use std::ops::Deref;
+
+use aes::cipher::{
+    generic_array::{ArrayLength, GenericArray},
+    typenum::U32,
+};
+use subtle::ConstantTimeEq;
+use zeroize::Zeroize;
+
+use crate::error::{CryptoError, Result};
+
+/// A heap allocated symmetric key of `N` bytes which is zeroized on drop.
+///
+/// Defaults to a 32 byte key, a 64 byte `SymmetricKey<U64>` holds an encryption key followed by a
+/// MAC key.
+pub struct SymmetricKey<N: ArrayLength<u8> = U32>(Box<GenericArray<u8, N>>);
+
+impl<N: ArrayLength<u8>> SymmetricKey<N> {
+    pub fn from_slice(key: &[u8]) -> Result<Self> {
+        if key.len() != N::USIZE {
+            return Err(CryptoError::InvalidKeyLength.into());
+        }
+        Ok(SymmetricKey(Box::new(GenericArray::clone_from_slice(key))))
+    }
+
+    pub fn as_array(&self) -> &GenericArray<u8, N> {
+        &self.0
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        self.0.as_slice()
+    }
+
+    pub(crate) fn as_mut_slice(&mut self) -> &mut [u8] {
+        self.0.as_mut_slice()
+    }
+}
+
+impl<N: ArrayLength<u8>> From<GenericArray<u8, N>> for SymmetricKey<N> {
+    fn from(mut key: GenericArray<u8, N>) -> Self {
+        let boxed = Box::new(key.clone());
+        key.as_mut_slice().zeroize();
+        SymmetricKey(boxed)
+    }
+}
+
+impl<N: ArrayLength<u8>> Drop for SymmetricKey<N> {
+    fn drop(&mut self) {
+        self.0.as_mut_slice().zeroize();
+    }
+}
+
+impl<N: ArrayLength<u8>> PartialEq for SymmetricKey<N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_slice().ct_eq(other.as_slice()).into()
+    }
+}
+
+impl<N: ArrayLength<u8>> std::fmt::Debug for SymmetricKey<N> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SymmetricKey")
+            .field("len", &N::USIZE)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A byte buffer holding sensitive data, such as a decrypted plaintext, which is zeroized on drop.
+#[derive(PartialEq)]
+pub struct SecretBytes(Vec<u8>);
+
+impl From<Vec<u8>> for SecretBytes {
+    fn from(bytes: Vec<u8>) -> Self {
+        SecretBytes(bytes)
+    }
+}
+
+impl SecretBytes {
+    pub(crate) fn as_mut_vec(&mut self) -> &mut Vec<u8> {
+        &mut self.0
+    }
+}
+
+impl Deref for SecretBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Drop for SecretBytes {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl std::fmt::Debug for SecretBytes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SecretBytes").finish_non_exhaustive()
+    }
+}
+
+/// A UTF-8 string holding sensitive data, such as a password, which is zeroized on drop.
+#[derive(PartialEq)]
+pub struct SecretString(String);
+
+impl From<String> for SecretString {
+    fn from(string: String) -> Self {
+        SecretString(string)
+    }
+}
+
+impl TryFrom<SecretBytes> for SecretString {
+    type Error = std::str::Utf8Error;
+
+    fn try_from(mut bytes: SecretBytes) -> Result<Self, Self::Error> {
+        std::str::from_utf8(&bytes)?;
+        let bytes = std::mem::take(&mut bytes.0);
+        Ok(SecretString(
+            String::from_utf8(bytes).expect("bytes were validated as UTF-8"),
+        ))
+    }
+}
+
+impl Deref for SecretString {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Drop for SecretString {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl std::fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SecretString").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn debug_is_redacted() {
+        let key = SymmetricKey::<U32>::from_slice(&[7u8; 32]).unwrap();
+        let bytes = SecretBytes::from(b"secret".to_vec());
+        let string = SecretString::from("secret".to_owned());
+
+        assert!(!format!("{key:?}").contains('7'));
+        assert!(!format!("{bytes:?}").contains("115"));
+        assert!(!format!("{string:?}").contains("secret"));
+    }
+
+    #[test]
+    fn symmetric_key_rejects_invalid_length() {
+        assert!(SymmetricKey::<U32>::from_slice(&[0u8; 16]).is_err());
+    }
+
+    #[test]
+    fn secret_string_from_secret_bytes() {
+        let bytes = SecretBytes::from(b"secret".to_vec());
+        assert_eq!(&*SecretString::try_from(bytes).unwrap(), "secret");
+
+        let bytes = SecretBytes::from(vec![0xff, 0xfe]);
+        assert!(SecretString::try_from(bytes).is_err());
+    }
+}
@@ -9,6 +9,70 @@ pub enum Error {
 
     #[error("Cryptography Error, {0}")]
     Crypto(#[from] CryptoError),
+
+    /// The requested item, such as a password or clipboard content, does not exist.
+    #[error("{0}")]
+    NotFound(String),
+    /// The operating system or the user denied access, e.g. a locked keychain.
+    #[error("{0}")]
+    AccessDenied(String),
+    /// A required system service, such as the Secret Service or polkit, could not be reached.
+    #[error("{0}")]
+    ServiceUnavailable(String),
+    #[error("The operation was cancelled by the user")]
+    UserCancelled,
+    #[error("{0}")]
+    InvalidInput(String),
+    /// The operation is not valid in the current state, e.g. the SSH agent is not running.
+    #[error("{0}")]
+    InvalidState(String),
+    /// The operation is not supported on this platform or for this input.
+    #[error("{0}")]
+    Unsupported(String),
+    /// Any other error reported by the operating system or an underlying library.
+    #[error("{0}")]
+    Platform(String),
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+#[cfg(all(feature = "sys", target_os = "windows"))]
+impl From<windows::core::Error> for Error {
+    fn from(e: windows::core::Error) -> Self {
+        Error::Platform(e.to_string())
+    }
+}
+
+#[cfg(all(feature = "sys", target_os = "linux"))]
+impl From<zbus::Error> for Error {
+    fn from(e: zbus::Error) -> Self {
+        match e {
+            zbus::Error::FDO(e) => Error::from(*e),
+            e => Error::ServiceUnavailable(e.to_string()),
+        }
+    }
+}
+
+#[cfg(all(feature = "sys", target_os = "linux"))]
+impl From<zbus::fdo::Error> for Error {
+    fn from(e: zbus::fdo::Error) -> Self {
+        match e {
+            zbus::fdo::Error::AccessDenied(message) => Error::AccessDenied(message),
+            e => Error::ServiceUnavailable(e.to_string()),
+        }
+    }
+}
+
+#[cfg(all(feature = "sys", target_os = "linux"))]
+impl From<zbus_polkit::Error> for Error {
+    fn from(e: zbus_polkit::Error) -> Self {
+        match e {
+            zbus_polkit::Error::Io(e) => Error::Io(e),
+            zbus_polkit::Error::BadSender(e) => Error::from(e),
+            e => Error::Platform(e.to_string()),
+        }
+    }
 }
 
 #[derive(Debug, Error)]
@@ -27,6 +91,18 @@ pub enum CSParseError {
 pub enum CryptoError {
     #[error("Error while decrypting cipher string")]
     KeyDecrypt,
+    #[error("Error while encrypting data")]
+    KeyEncrypt,
+    #[error("Invalid key")]
+    InvalidKey,
+    #[error("Unsupported encryption type for this operation")]
+    InvalidEncType,
+    #[error("The MAC of the cipher string is invalid")]
+    InvalidMac,
+    #[error("Invalid key length")]
+    InvalidKeyLength,
+    #[error("Insufficient or invalid KDF parameters")]
+    InsufficientKdfParameters,
 }
 
 // Ensure that the error messages implement Send and Sync
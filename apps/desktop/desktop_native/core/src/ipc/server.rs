@@ -1,11 +1,7 @@
-use std::{
-    error::Error,
-    path::{Path, PathBuf},
-};
+use std::path::{Path, PathBuf};
 
 use futures::{SinkExt, StreamExt, TryFutureExt};
 
-use anyhow::Result;
 use interprocess::local_socket::{tokio::prelude::*, GenericFilePath, ListenerOptions};
 use log::{error, info};
 use tokio::{
@@ -14,6 +10,8 @@ use tokio::{
 };
 use tokio_util::sync::CancellationToken;
 
+use crate::error::{Error, Result};
+
 use super::MESSAGE_CHANNEL_BUFFER;
 
 #[derive(Debug)]
@@ -44,10 +42,7 @@ impl Server {
     ///
     /// - `name`: The endpoint name to listen on. This name uniquely identifies the IPC connection and must be the same for both the server and client.
     /// - `client_to_server_send`: This [`mpsc::Sender<Message>`] will receive all the [`Message`]'s that the clients send to this server.
-    pub fn start(
-        path: &Path,
-        client_to_server_send: mpsc::Sender<Message>,
-    ) -> Result<Self, Box<dyn Error>> {
+    pub fn start(path: &Path, client_to_server_send: mpsc::Sender<Message>) -> Result<Self> {
         // If the unix socket file already exists, we get an error when trying to bind to it. So we remove it first.
         // Any processes that were using the old socket should remain connected to it but any new connections will use the new socket.
         if !cfg!(windows) {
@@ -92,7 +87,10 @@ impl Server {
     /// sent may be less than the number of connected clients if some clients disconnect while
     /// the message is being sent.
     pub fn send(&self, message: String) -> Result<usize> {
-        let sent = self.server_to_clients_send.send(message)?;
+        let sent = self
+            .server_to_clients_send
+            .send(message)
+            .map_err(|_| Error::InvalidState("The IPC server is not running".to_string()))?;
         Ok(sent)
     }
 
@@ -162,7 +160,7 @@ async fn handle_connection(
     mut server_to_clients_recv: broadcast::Receiver<String>,
     cancel_token: CancellationToken,
     client_id: u32,
-) -> Result<(), Box<dyn Error>> {
+) -> Result<(), Box<dyn std::error::Error>> {
     client_to_server_send
         .send(Message {
             client_id,
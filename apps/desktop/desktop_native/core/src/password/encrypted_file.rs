@@ -0,0 +1,464 @@
+//! A password store backed by an encrypted file, for systems where the platform secure storage
+//! is unavailable, e.g. Linux without a Secret Service provider.
+
+use std::{
+    collections::HashMap,
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Mutex,
+};
+
+use base64::{engine::general_purpose::STANDARD as base64_engine, Engine};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroize;
+
+use crate::{
+    crypto::{self, kdf, CipherString, SecretBytes, SecretString, SymmetricKey},
+    error::{CryptoError, Error, Result},
+};
+
+use super::{PasswordBackend, SecretMetadata, StoredSecret};
+
+const FILE_VERSION: u32 = 1;
+const FILE_NAME: &str = "secrets.json";
+
+/// Binds the encrypted entries to this file format.
+const ASSOCIATED_DATA: &[u8] = b"bitwarden-desktop-encrypted-file-v1";
+
+// Argon2id parameters used to derive the file key from a secret, the Bitwarden client defaults
+const ARGON2_ITERATIONS: u32 = 3;
+const ARGON2_MEMORY_MIB: u32 = 64;
+const ARGON2_PARALLELISM: u32 = 4;
+
+#[cfg(target_os = "linux")]
+const KEYRING_DESCRIPTION: &str = "bitwarden-desktop:encrypted-file-key";
+
+/// The source of the key the file is encrypted with.
+pub enum FileKey {
+    /// Derive the key from a user supplied secret using Argon2id.
+    Secret(SecretString),
+    /// A random key stored in the kernel user keyring.
+    ///
+    /// The user keyring is destroyed when the last session of the user ends, after which the
+    /// entries in the file can no longer be decrypted. Only use this for short-lived secrets.
+    #[cfg(target_os = "linux")]
+    KernelKeyring,
+}
+
+/// The file as it is persisted on disk. `data` is an `AesGcm256_B64` cipher string of the JSON
+/// encoded entries.
+#[derive(Serialize, Deserialize)]
+struct EncryptedFile {
+    version: u32,
+    salt: String,
+    data: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    service: String,
+    account: String,
+    /// Base64 encoded, passwords are stored as their UTF-8 bytes.
+    secret: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    label: Option<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    attributes: HashMap<String, String>,
+}
+
+impl Entry {
+    fn secret(&self) -> Result<SecretBytes> {
+        let secret = base64_engine
+            .decode(&self.secret)
+            .map_err(|e| Error::InvalidInput(e.to_string()))?;
+        Ok(SecretBytes::from(secret))
+    }
+}
+
+impl Drop for Entry {
+    fn drop(&mut self) {
+        self.secret.zeroize();
+    }
+}
+
+pub struct EncryptedFileBackend {
+    path: PathBuf,
+    salt: [u8; 16],
+    key: SymmetricKey,
+    // Serializes the read-modify-write cycles of the file
+    lock: Mutex<()>,
+}
+
+impl EncryptedFileBackend {
+    /// Open the store at `path`. The file is created on the first write.
+    ///
+    /// Fails with [`Error::AccessDenied`] if the file exists but can't be decrypted with `key`.
+    pub fn new(path: PathBuf, key: FileKey) -> Result<Self> {
+        let file = read_file(&path)?;
+        let salt = match &file {
+            Some(file) => base64_engine
+                .decode(&file.salt)
+                .ok()
+                .and_then(|salt| salt.try_into().ok())
+                .ok_or(Error::InvalidInput(
+                    "Invalid salt in the encrypted secret store".to_string(),
+                ))?,
+            None => {
+                let mut salt = [0u8; 16];
+                rand::thread_rng().fill_bytes(&mut salt);
+                salt
+            }
+        };
+
+        let key = match key {
+            FileKey::Secret(secret) => kdf::argon2id(
+                secret.as_bytes(),
+                &salt,
+                ARGON2_ITERATIONS,
+                ARGON2_MEMORY_MIB,
+                ARGON2_PARALLELISM,
+            )?,
+            #[cfg(target_os = "linux")]
+            FileKey::KernelKeyring => keyring_key(file.is_some())?,
+        };
+
+        let backend = EncryptedFileBackend {
+            path,
+            salt,
+            key,
+            lock: Mutex::new(()),
+        };
+        // Fail early on a wrong key rather than on the first read
+        backend.read_entries()?;
+        Ok(backend)
+    }
+
+    /// The default location of the store, in the Bitwarden directory of the user's config
+    /// directory.
+    pub fn default_path() -> Result<PathBuf> {
+        dirs::config_dir()
+            .map(|dir| dir.join("Bitwarden").join(FILE_NAME))
+            .ok_or(Error::NotFound(
+                "Could not determine the config directory".to_string(),
+            ))
+    }
+
+    fn read_entries(&self) -> Result<Vec<Entry>> {
+        let Some(file) = read_file(&self.path)? else {
+            return Ok(Vec::new());
+        };
+
+        let (nonce, data) = match CipherString::from_str(&file.data)? {
+            CipherString::AesGcm256_B64 { nonce, data } => (nonce, data),
+            _ => return Err(CryptoError::InvalidEncType.into()),
+        };
+        let decrypted = crypto::decrypt_aes256_gcm(&nonce, &data, &self.key, ASSOCIATED_DATA)
+            .map_err(|e| match e {
+                Error::Crypto(CryptoError::InvalidMac) => Error::AccessDenied(
+                    "The encrypted secret store can't be decrypted with the given key".to_string(),
+                ),
+                e => e,
+            })?;
+
+        serde_json::from_slice(&decrypted).map_err(|e| Error::InvalidInput(e.to_string()))
+    }
+
+    fn write_entries(&self, entries: &[Entry]) -> Result<()> {
+        let json = SecretBytes::from(
+            serde_json::to_vec(entries).map_err(|e| Error::InvalidInput(e.to_string()))?,
+        );
+        let encrypted = crypto::encrypt_aes256_gcm(&json, &self.key, ASSOCIATED_DATA)?;
+
+        let file = EncryptedFile {
+            version: FILE_VERSION,
+            salt: base64_engine.encode(self.salt),
+            data: encrypted.to_string(),
+        };
+        let contents = serde_json::to_vec(&file).map_err(|e| Error::InvalidInput(e.to_string()))?;
+        write_file(&self.path, &contents)
+    }
+}
+
+impl PasswordBackend for EncryptedFileBackend {
+    fn get_password(&self, service: &str, account: &str) -> Result<SecretString> {
+        let secret = self.get_secret(service, account)?.secret;
+        SecretString::try_from(secret).map_err(|e| Error::InvalidInput(e.to_string()))
+    }
+
+    fn set_password(&self, service: &str, account: &str, password: &str) -> Result<()> {
+        self.set_secret(
+            service,
+            account,
+            password.as_bytes(),
+            &SecretMetadata::default(),
+        )
+    }
+
+    fn delete_password(&self, service: &str, account: &str) -> Result<()> {
+        let _lock = self.lock.lock().expect("Mutex is not poisoned");
+
+        let mut entries = self.read_entries()?;
+        let index = entries
+            .iter()
+            .position(|entry| entry.service == service && entry.account == account)
+            .ok_or(Error::NotFound("No password found".to_string()))?;
+        entries.remove(index);
+        self.write_entries(&entries)
+    }
+
+    fn get_secret(&self, service: &str, account: &str) -> Result<StoredSecret> {
+        let _lock = self.lock.lock().expect("Mutex is not poisoned");
+
+        let entries = self.read_entries()?;
+        let entry = entries
+            .iter()
+            .find(|entry| entry.service == service && entry.account == account)
+            .ok_or(Error::NotFound("No password found".to_string()))?;
+
+        Ok(StoredSecret {
+            secret: entry.secret()?,
+            metadata: SecretMetadata {
+                label: entry.label.clone(),
+                attributes: entry.attributes.clone(),
+            },
+        })
+    }
+
+    fn set_secret(
+        &self,
+        service: &str,
+        account: &str,
+        secret: &[u8],
+        metadata: &SecretMetadata,
+    ) -> Result<()> {
+        let _lock = self.lock.lock().expect("Mutex is not poisoned");
+
+        let mut entries = self.read_entries()?;
+        entries.retain(|entry| entry.service != service || entry.account != account);
+        entries.push(Entry {
+            service: service.to_string(),
+            account: account.to_string(),
+            secret: base64_engine.encode(secret),
+            label: metadata.label.clone(),
+            attributes: metadata.attributes.clone(),
+        });
+        self.write_entries(&entries)
+    }
+
+    fn list_accounts(&self, service: &str) -> Result<Vec<String>> {
+        let _lock = self.lock.lock().expect("Mutex is not poisoned");
+
+        Ok(self
+            .read_entries()?
+            .iter()
+            .filter(|entry| entry.service == service)
+            .map(|entry| entry.account.clone())
+            .collect())
+    }
+
+    fn delete_all(&self, service: &str) -> Result<()> {
+        let _lock = self.lock.lock().expect("Mutex is not poisoned");
+
+        let mut entries = self.read_entries()?;
+        entries.retain(|entry| entry.service != service);
+        self.write_entries(&entries)
+    }
+
+    fn is_available(&self) -> Result<bool> {
+        Ok(true)
+    }
+}
+
+fn read_file(path: &Path) -> Result<Option<EncryptedFile>> {
+    let contents = match fs::read(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+
+    let file: EncryptedFile = serde_json::from_slice(&contents)
+        .map_err(|e| Error::InvalidInput(format!("Invalid encrypted secret store: {}", e)))?;
+    if file.version != FILE_VERSION {
+        return Err(Error::Unsupported(format!(
+            "Unsupported encrypted secret store version {}",
+            file.version
+        )));
+    }
+    Ok(Some(file))
+}
+
+/// Atomically replace the file, only readable by the current user.
+fn write_file(path: &Path, contents: &[u8]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let temp_path = path.with_extension("tmp");
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let mut file = options.open(&temp_path)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    fs::rename(&temp_path, path)?;
+    Ok(())
+}
+
+/// Read the file key from the kernel keyring, or create it if the file doesn't exist yet.
+#[cfg(target_os = "linux")]
+fn keyring_key(file_exists: bool) -> Result<SymmetricKey> {
+    use super::keyutils::{self, Keyring};
+
+    match keyutils::search(Keyring::User, KEYRING_DESCRIPTION)? {
+        Some(key) => SymmetricKey::from_slice(&keyutils::read(key)?),
+        None if file_exists => Err(Error::NotFound(
+            "The key of the encrypted secret store is not in the kernel keyring".to_string(),
+        )),
+        None => {
+            let mut key = [0u8; 32];
+            rand::thread_rng().fill_bytes(&mut key);
+            let result = SymmetricKey::from_slice(&key);
+            keyutils::add(Keyring::User, KEYRING_DESCRIPTION, &key)?;
+            key.zeroize();
+            result
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path() -> PathBuf {
+        let mut name = [0u8; 8];
+        rand::thread_rng().fill_bytes(&mut name);
+        std::env::temp_dir()
+            .join(format!("bitwarden-test-{}", hex(&name)))
+            .join(FILE_NAME)
+    }
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    fn secret(secret: &str) -> FileKey {
+        FileKey::Secret(SecretString::from(secret.to_string()))
+    }
+
+    #[test]
+    fn set_get_delete() {
+        let path = temp_path();
+        scopeguard::defer!(let _ = fs::remove_dir_all(path.parent().unwrap()););
+        let backend = EncryptedFileBackend::new(path.clone(), secret("secret")).unwrap();
+
+        backend
+            .set_password("service", "account", "Random")
+            .unwrap();
+        backend.set_password("service", "other", "Other").unwrap();
+        assert_eq!(
+            "Random",
+            &*backend.get_password("service", "account").unwrap()
+        );
+
+        backend
+            .set_password("service", "account", "Updated")
+            .unwrap();
+        assert_eq!(
+            "Updated",
+            &*backend.get_password("service", "account").unwrap()
+        );
+
+        backend.delete_password("service", "account").unwrap();
+        assert!(matches!(
+            backend.get_password("service", "account"),
+            Err(Error::NotFound(_))
+        ));
+        assert_eq!("Other", &*backend.get_password("service", "other").unwrap());
+    }
+
+    #[test]
+    fn secret_with_metadata() {
+        let path = temp_path();
+        scopeguard::defer!(let _ = fs::remove_dir_all(path.parent().unwrap()););
+        let backend = EncryptedFileBackend::new(path.clone(), secret("secret")).unwrap();
+
+        let metadata = SecretMetadata {
+            label: Some("Label".to_string()),
+            attributes: HashMap::from([("version".to_string(), "2".to_string())]),
+        };
+        backend
+            .set_secret("service", "account", &[0, 159, 146, 150], &metadata)
+            .unwrap();
+
+        let stored = backend.get_secret("service", "account").unwrap();
+        assert_eq!(&[0, 159, 146, 150], &*stored.secret);
+        assert_eq!(metadata, stored.metadata);
+        assert!(matches!(
+            backend.get_password("service", "account"),
+            Err(Error::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn list_accounts_delete_all() {
+        let path = temp_path();
+        scopeguard::defer!(let _ = fs::remove_dir_all(path.parent().unwrap()););
+        let backend = EncryptedFileBackend::new(path.clone(), secret("secret")).unwrap();
+
+        backend
+            .set_password("service", "account1", "Random")
+            .unwrap();
+        backend
+            .set_password("service", "account2", "Random")
+            .unwrap();
+        backend.set_password("other", "account3", "Random").unwrap();
+        assert_eq!(
+            vec!["account1", "account2"],
+            backend.list_accounts("service").unwrap()
+        );
+
+        backend.delete_all("service").unwrap();
+        assert!(backend.list_accounts("service").unwrap().is_empty());
+        assert_eq!(vec!["account3"], backend.list_accounts("other").unwrap());
+    }
+
+    #[test]
+    fn persists_encrypted() {
+        let path = temp_path();
+        scopeguard::defer!(let _ = fs::remove_dir_all(path.parent().unwrap()););
+        EncryptedFileBackend::new(path.clone(), secret("secret"))
+            .unwrap()
+            .set_password("service", "account", "Random")
+            .unwrap();
+
+        assert!(!fs::read_to_string(&path).unwrap().contains("Random"));
+        let backend = EncryptedFileBackend::new(path.clone(), secret("secret")).unwrap();
+        assert_eq!(
+            "Random",
+            &*backend.get_password("service", "account").unwrap()
+        );
+    }
+
+    #[test]
+    fn rejects_wrong_secret() {
+        let path = temp_path();
+        scopeguard::defer!(let _ = fs::remove_dir_all(path.parent().unwrap()););
+        EncryptedFileBackend::new(path.clone(), secret("secret"))
+            .unwrap()
+            .set_password("service", "account", "Random")
+            .unwrap();
+
+        assert!(matches!(
+            EncryptedFileBackend::new(path.clone(), secret("wrong")),
+            Err(Error::AccessDenied(_))
+        ));
+    }
+}
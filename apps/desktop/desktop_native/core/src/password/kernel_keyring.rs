@@ -0,0 +1,224 @@
+//! A password store in the Linux kernel keyring, for short-lived secrets such as the biometric
+//! unlock key. It works without a D-Bus session and the entries are cleared on logout, but they
+//! don't survive a reboot.
+
+use crate::{
+    crypto::SecretString,
+    error::{Error, Result},
+};
+
+use super::{keyutils, PasswordBackend, SecretMetadata, StoredSecret};
+
+pub use super::keyutils::Keyring;
+
+pub struct KernelKeyringBackend {
+    keyring: Keyring,
+}
+
+impl KernelKeyringBackend {
+    pub fn new(keyring: Keyring) -> Self {
+        KernelKeyringBackend { keyring }
+    }
+}
+
+impl KernelKeyringBackend {
+    // The keys of the service in the keyring with their accounts
+    fn service_keys(&self, service: &str) -> Result<Vec<(keyutils::KeySerial, String)>> {
+        let prefix = description(service, "");
+        let mut keys = Vec::new();
+        for key in keyutils::list(self.keyring)? {
+            let (key_type, description) = match keyutils::describe(key) {
+                Ok(description) => description,
+                // Keys we aren't allowed to view or that were removed in the meantime
+                Err(Error::AccessDenied(_) | Error::NotFound(_)) => continue,
+                Err(e) => return Err(e),
+            };
+            if key_type != "user" {
+                continue;
+            }
+            if let Some(account) = description.strip_prefix(&prefix) {
+                keys.push((key, account.to_string()));
+            }
+        }
+
+        Ok(keys)
+    }
+}
+
+impl PasswordBackend for KernelKeyringBackend {
+    fn get_password(&self, service: &str, account: &str) -> Result<SecretString> {
+        let key = keyutils::search(self.keyring, &description(service, account))?
+            .ok_or(Error::NotFound("No password found".to_string()))?;
+        SecretString::try_from(keyutils::read(key)?).map_err(|e| Error::InvalidInput(e.to_string()))
+    }
+
+    fn set_password(&self, service: &str, account: &str, password: &str) -> Result<()> {
+        keyutils::add(
+            self.keyring,
+            &description(service, account),
+            password.as_bytes(),
+        )?;
+        Ok(())
+    }
+
+    fn delete_password(&self, service: &str, account: &str) -> Result<()> {
+        let key = keyutils::search(self.keyring, &description(service, account))?
+            .ok_or(Error::NotFound("No password found".to_string()))?;
+        keyutils::invalidate(key)
+    }
+
+    fn get_secret(&self, service: &str, account: &str) -> Result<StoredSecret> {
+        let key = keyutils::search(self.keyring, &description(service, account))?
+            .ok_or(Error::NotFound("No password found".to_string()))?;
+        Ok(StoredSecret {
+            secret: keyutils::read(key)?,
+            metadata: SecretMetadata::default(),
+        })
+    }
+
+    fn set_secret(
+        &self,
+        service: &str,
+        account: &str,
+        secret: &[u8],
+        metadata: &SecretMetadata,
+    ) -> Result<()> {
+        // Keys only have a description and a payload
+        if *metadata != SecretMetadata::default() {
+            return Err(Error::Unsupported(
+                "The kernel keyring can't store metadata".to_string(),
+            ));
+        }
+
+        keyutils::add(self.keyring, &description(service, account), secret)?;
+        Ok(())
+    }
+
+    fn list_accounts(&self, service: &str) -> Result<Vec<String>> {
+        Ok(self
+            .service_keys(service)?
+            .into_iter()
+            .map(|(_, account)| account)
+            .collect())
+    }
+
+    fn delete_all(&self, service: &str) -> Result<()> {
+        for (key, _) in self.service_keys(service)? {
+            keyutils::invalidate(key)?;
+        }
+
+        Ok(())
+    }
+
+    fn is_available(&self) -> Result<bool> {
+        // Fails when the keyring doesn't exist, e.g. without a login session, or keyctl is
+        // blocked by a sandbox
+        Ok(keyutils::exists(self.keyring).is_ok())
+    }
+}
+
+// The keys of all applications share the keyring, prefix them to avoid collisions. The length of
+// the service keeps a `/` in the service or account from making two entries collide.
+fn description(service: &str, account: &str) -> String {
+    format!("bitwarden:{}:{}/{}", service.len(), service, account)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_description_is_unambiguous() {
+        assert_ne!(description("a/b", "c"), description("a", "b/c"));
+        assert!(description("a", "b/c").starts_with(&description("a", "")));
+        assert!(!description("a/b", "c").starts_with(&description("a", "")));
+    }
+
+    // The tests below store keys in the keyring of the user running them
+
+    #[test]
+    #[cfg(feature = "manual_test")]
+    fn test() {
+        let backend = KernelKeyringBackend::new(Keyring::User);
+        scopeguard::defer!(let _ = backend.delete_password("BitwardenTest", "BitwardenTest"););
+        backend
+            .set_password("BitwardenTest", "BitwardenTest", "Random")
+            .unwrap();
+        assert_eq!(
+            "Random",
+            &*backend
+                .get_password("BitwardenTest", "BitwardenTest")
+                .unwrap()
+        );
+        backend
+            .delete_password("BitwardenTest", "BitwardenTest")
+            .unwrap();
+
+        // Ensure password is deleted
+        match backend.get_password("BitwardenTest", "BitwardenTest") {
+            Ok(_) => panic!("Got a result"),
+            Err(e) => assert!(matches!(e, Error::NotFound(_))),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "manual_test")]
+    fn test_error_no_password() {
+        let backend = KernelKeyringBackend::new(Keyring::User);
+        match backend.get_password("BitwardenTest", "BitwardenTestMissing") {
+            Ok(_) => panic!("Got a result"),
+            Err(e) => assert!(matches!(e, Error::NotFound(_))),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "manual_test")]
+    fn test_secret() {
+        let backend = KernelKeyringBackend::new(Keyring::User);
+        scopeguard::defer!(let _ = backend.delete_password("BitwardenTestSecret", "BitwardenTest"););
+        backend
+            .set_secret(
+                "BitwardenTestSecret",
+                "BitwardenTest",
+                &[0, 159, 146, 150],
+                &SecretMetadata::default(),
+            )
+            .unwrap();
+        let stored = backend
+            .get_secret("BitwardenTestSecret", "BitwardenTest")
+            .unwrap();
+        assert_eq!(&[0, 159, 146, 150], &*stored.secret);
+
+        let metadata = SecretMetadata {
+            label: Some("Label".to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            backend.set_secret("BitwardenTestSecret", "BitwardenTest", &[0], &metadata),
+            Err(Error::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "manual_test")]
+    fn test_list_accounts_delete_all() {
+        let backend = KernelKeyringBackend::new(Keyring::User);
+        scopeguard::defer!(let _ = backend.delete_all("BitwardenTestList"););
+        backend
+            .set_password("BitwardenTestList", "Account1", "Random")
+            .unwrap();
+        backend
+            .set_password("BitwardenTestList", "Account2", "Random")
+            .unwrap();
+
+        let mut accounts = backend.list_accounts("BitwardenTestList").unwrap();
+        accounts.sort();
+        assert_eq!(vec!["Account1", "Account2"], accounts);
+
+        backend.delete_all("BitwardenTestList").unwrap();
+        assert!(backend
+            .list_accounts("BitwardenTestList")
+            .unwrap()
+            .is_empty());
+    }
+}
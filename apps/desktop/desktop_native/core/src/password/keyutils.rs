@@ -0,0 +1,191 @@
+//! Minimal wrappers around the Linux kernel key retention service syscalls.
+//!
+//! https://man7.org/linux/man-pages/man7/keyrings.7.html
+
+use std::ffi::CString;
+
+use libc::{c_long, syscall, SYS_add_key, SYS_keyctl};
+
+use crate::{
+    crypto::SecretBytes,
+    error::{Error, Result},
+};
+
+// https://github.com/torvalds/linux/blob/master/include/uapi/linux/keyctl.h
+const KEY_SPEC_SESSION_KEYRING: c_long = -3;
+const KEY_SPEC_USER_KEYRING: c_long = -4;
+const KEYCTL_GET_KEYRING_ID: c_long = 0;
+const KEYCTL_DESCRIBE: c_long = 6;
+const KEYCTL_SEARCH: c_long = 10;
+const KEYCTL_READ: c_long = 11;
+const KEYCTL_INVALIDATE: c_long = 21;
+
+const KEY_TYPE_USER: &str = "user";
+
+/// The serial number of a key in the kernel keyring.
+pub type KeySerial = i32;
+
+/// The keyring keys are stored in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Keyring {
+    /// Shared by all processes of the user, cleared when the last session of the user ends.
+    User,
+    /// Shared by the processes of the login session, cleared when the session ends.
+    Session,
+}
+
+impl Keyring {
+    fn id(self) -> c_long {
+        match self {
+            Keyring::User => KEY_SPEC_USER_KEYRING,
+            Keyring::Session => KEY_SPEC_SESSION_KEYRING,
+        }
+    }
+}
+
+/// Check that the keyring exists, without creating it.
+pub fn exists(keyring: Keyring) -> Result<()> {
+    let result = unsafe { syscall(SYS_keyctl, KEYCTL_GET_KEYRING_ID, keyring.id(), 0) };
+    if result < 0 {
+        return Err(convert_error(std::io::Error::last_os_error()));
+    }
+
+    Ok(())
+}
+
+/// Search the keyring for a `user` key with the given description.
+pub fn search(keyring: Keyring, description: &str) -> Result<Option<KeySerial>> {
+    let key_type = c_string(KEY_TYPE_USER)?;
+    let description = c_string(description)?;
+
+    let result = unsafe {
+        syscall(
+            SYS_keyctl,
+            KEYCTL_SEARCH,
+            keyring.id(),
+            key_type.as_ptr(),
+            description.as_ptr(),
+            0,
+        )
+    };
+    if result < 0 {
+        let e = std::io::Error::last_os_error();
+        return match e.raw_os_error() {
+            Some(libc::ENOKEY) | Some(libc::EKEYEXPIRED) | Some(libc::EKEYREVOKED) => Ok(None),
+            _ => Err(convert_error(e)),
+        };
+    }
+
+    Ok(Some(result as KeySerial))
+}
+
+/// Read the payload of a key.
+pub fn read(key: KeySerial) -> Result<SecretBytes> {
+    let mut payload = SecretBytes::from(Vec::new());
+    read_into(KEYCTL_READ, key as c_long, payload.as_mut_vec())?;
+    Ok(payload)
+}
+
+/// List the keys linked to the keyring.
+pub fn list(keyring: Keyring) -> Result<Vec<KeySerial>> {
+    let mut payload = Vec::new();
+    read_into(KEYCTL_READ, keyring.id(), &mut payload)?;
+
+    // The payload of a keyring is the array of the serial numbers of its keys
+    Ok(payload
+        .chunks_exact(std::mem::size_of::<KeySerial>())
+        .map(|serial| KeySerial::from_ne_bytes(serial.try_into().expect("chunk has the size")))
+        .collect())
+}
+
+/// The type and description of a key.
+pub fn describe(key: KeySerial) -> Result<(String, String)> {
+    let mut buffer = Vec::new();
+    read_into(KEYCTL_DESCRIBE, key as c_long, &mut buffer)?;
+
+    // Formatted as `type;uid;gid;perm;description\0`
+    let description = String::from_utf8_lossy(buffer.strip_suffix(&[0]).unwrap_or(&buffer));
+    let mut parts = description.splitn(5, ';');
+    match (parts.next(), parts.nth(3)) {
+        (Some(key_type), Some(description)) => Ok((key_type.to_string(), description.to_string())),
+        _ => Err(Error::Platform(format!(
+            "Invalid key description: {}",
+            description
+        ))),
+    }
+}
+
+/// Add a `user` key to the keyring, replacing the payload of an existing key with the same
+/// description.
+pub fn add(keyring: Keyring, description: &str, payload: &[u8]) -> Result<KeySerial> {
+    let key_type = c_string(KEY_TYPE_USER)?;
+    let description = c_string(description)?;
+
+    let result = unsafe {
+        syscall(
+            SYS_add_key,
+            key_type.as_ptr(),
+            description.as_ptr(),
+            payload.as_ptr(),
+            payload.len(),
+            keyring.id(),
+        )
+    };
+    if result < 0 {
+        return Err(convert_error(std::io::Error::last_os_error()));
+    }
+
+    Ok(result as KeySerial)
+}
+
+/// Invalidate a key, removing it from all keyrings and destroying its payload.
+pub fn invalidate(key: KeySerial) -> Result<()> {
+    let result = unsafe { syscall(SYS_keyctl, KEYCTL_INVALIDATE, key as c_long) };
+    if result < 0 {
+        return Err(convert_error(std::io::Error::last_os_error()));
+    }
+
+    Ok(())
+}
+
+/// Run a keyctl operation that copies its result into a buffer, growing the buffer until it fits.
+fn read_into(operation: c_long, key: c_long, buffer: &mut Vec<u8>) -> Result<()> {
+    loop {
+        let result = unsafe {
+            syscall(
+                SYS_keyctl,
+                operation,
+                key,
+                buffer.as_mut_ptr(),
+                buffer.len(),
+            )
+        };
+        if result < 0 {
+            return Err(convert_error(std::io::Error::last_os_error()));
+        }
+
+        // The call returns the size of the result, which might have grown since the last call
+        let size = result as usize;
+        if size <= buffer.len() {
+            buffer.truncate(size);
+            return Ok(());
+        }
+        buffer.resize(size, 0);
+    }
+}
+
+fn c_string(s: &str) -> Result<CString> {
+    CString::new(s).map_err(|e| Error::InvalidInput(e.to_string()))
+}
+
+// Convert the errno of a failed syscall to its matching error kind
+fn convert_error(e: std::io::Error) -> Error {
+    match e.raw_os_error() {
+        Some(libc::ENOKEY) | Some(libc::EKEYEXPIRED) | Some(libc::EKEYREVOKED) => {
+            Error::NotFound(e.to_string())
+        }
+        Some(libc::EACCES) | Some(libc::EPERM) => Error::AccessDenied(e.to_string()),
+        Some(libc::ENOSYS) => Error::ServiceUnavailable(e.to_string()),
+        _ => Error::Io(e),
+    }
+}
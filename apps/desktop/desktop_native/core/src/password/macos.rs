@@ -1,47 +1,208 @@
-use anyhow::Result;
-use security_framework::passwords::{
-    delete_generic_password, get_generic_password, set_generic_password,
+use core_foundation::{
+    base::{CFType, TCFType},
+    data::CFData,
+    dictionary::CFDictionary,
+    string::CFString,
+};
+use security_framework::{
+    item::{ItemClass, ItemSearchOptions, Limit},
+    passwords::{delete_generic_password, get_generic_password, set_generic_password},
+};
+use security_framework_sys::{
+    item::{kSecAttrAccount, kSecAttrService, kSecClass, kSecClassGenericPassword, kSecValueData},
+    keychain_item::{SecItemAdd, SecItemUpdate},
 };
 
-pub fn get_password(service: &str, account: &str) -> Result<String> {
-    let result = String::from_utf8(get_generic_password(&service, &account)?)?;
-    Ok(result)
+use crate::{
+    crypto::{SecretBytes, SecretString},
+    error::{Error, Result},
+};
+
+use super::{SecretMetadata, StoredSecret};
+
+// The short names of the item attributes, `kSecAttrLabel` and `kSecAttrGeneric`
+const ATTR_LABEL: &str = "labl";
+const ATTR_GENERIC: &str = "gena";
+
+// https://developer.apple.com/documentation/security/1542001-security_framework_result_codes
+const ERR_SEC_SUCCESS: i32 = 0;
+const ERR_SEC_USER_CANCELED: i32 = -128;
+const ERR_SEC_AUTH_FAILED: i32 = -25293;
+const ERR_SEC_DUPLICATE_ITEM: i32 = -25299;
+const ERR_SEC_ITEM_NOT_FOUND: i32 = -25300;
+const ERR_SEC_INTERACTION_NOT_ALLOWED: i32 = -25308;
+
+pub async fn get_password(service: &str, account: &str) -> Result<SecretString> {
+    let result =
+        SecretBytes::from(get_generic_password(&service, &account).map_err(convert_error)?);
+    SecretString::try_from(result).map_err(|e| Error::InvalidInput(e.to_string()))
 }
 
-pub fn get_password_keytar(service: &str, account: &str) -> Result<String> {
-    get_password(service, account)
+pub async fn get_password_keytar(service: &str, account: &str) -> Result<SecretString> {
+    get_password(service, account).await
 }
 
-pub fn set_password(service: &str, account: &str, password: &str) -> Result<()> {
-    let result = set_generic_password(&service, &account, password.as_bytes())?;
+pub async fn set_password(service: &str, account: &str, password: &str) -> Result<()> {
+    let result =
+        set_generic_password(&service, &account, password.as_bytes()).map_err(convert_error)?;
     Ok(result)
 }
 
-pub fn delete_password(service: &str, account: &str) -> Result<()> {
-    let result = delete_generic_password(&service, &account)?;
+pub async fn delete_password(service: &str, account: &str) -> Result<()> {
+    let result = delete_generic_password(&service, &account).map_err(convert_error)?;
     Ok(result)
 }
 
-pub fn is_available() -> Result<bool> {
+pub async fn get_secret(service: &str, account: &str) -> Result<StoredSecret> {
+    let secret = SecretBytes::from(get_generic_password(service, account).map_err(convert_error)?);
+
+    let items = ItemSearchOptions::new()
+        .class(ItemClass::generic_password())
+        .service(service)
+        .account(account)
+        .load_attributes(true)
+        .limit(Limit::Max(1))
+        .search()
+        .map_err(convert_error)?;
+    let mut item = items
+        .first()
+        .and_then(|item| item.simplify_dict())
+        .unwrap_or_default();
+
+    // Keychain items have no custom attributes, they are stored as JSON in the generic attribute
+    let attributes = match item.remove(ATTR_GENERIC) {
+        Some(generic) => serde_json::from_str(&generic)
+            .map_err(|e| Error::InvalidInput(format!("Invalid secret attributes: {}", e)))?,
+        None => Default::default(),
+    };
+
+    Ok(StoredSecret {
+        secret,
+        metadata: SecretMetadata {
+            label: item.remove(ATTR_LABEL),
+            attributes,
+        },
+    })
+}
+
+pub async fn set_secret(
+    service: &str,
+    account: &str,
+    secret: &[u8],
+    metadata: &SecretMetadata,
+) -> Result<()> {
+    // Keychain items have no custom attributes, they are stored as JSON in the generic attribute
+    let attributes =
+        serde_json::to_vec(&metadata.attributes).map_err(|e| Error::InvalidInput(e.to_string()))?;
+    // The keychain labels items with their service unless a label is given
+    let label = metadata.label.as_deref().unwrap_or(service);
+
+    // The keys are constant strings of the Security framework
+    let key = |name| unsafe { CFString::wrap_under_get_rule(name) };
+    let query: [(CFString, CFType); 3] = unsafe {
+        [
+            (key(kSecClass), key(kSecClassGenericPassword).into_CFType()),
+            (key(kSecAttrService), CFString::new(service).into_CFType()),
+            (key(kSecAttrAccount), CFString::new(account).into_CFType()),
+        ]
+    };
+    let update: [(CFString, CFType); 3] = [
+        (
+            CFString::from_static_string(ATTR_LABEL),
+            CFString::new(label).into_CFType(),
+        ),
+        (
+            CFString::from_static_string(ATTR_GENERIC),
+            CFData::from_buffer(&attributes).into_CFType(),
+        ),
+        (
+            unsafe { key(kSecValueData) },
+            CFData::from_buffer(secret).into_CFType(),
+        ),
+    ];
+
+    // The existing item is updated in place, so that it is kept if the update fails
+    let item = CFDictionary::from_CFType_pairs(&[&query[..], &update[..]].concat());
+    let status = match unsafe { SecItemAdd(item.as_concrete_TypeRef(), std::ptr::null_mut()) } {
+        ERR_SEC_DUPLICATE_ITEM => unsafe {
+            SecItemUpdate(
+                CFDictionary::from_CFType_pairs(&query).as_concrete_TypeRef(),
+                CFDictionary::from_CFType_pairs(&update).as_concrete_TypeRef(),
+            )
+        },
+        status => status,
+    };
+    match status {
+        ERR_SEC_SUCCESS => Ok(()),
+        status => Err(convert_error(security_framework::base::Error::from_code(
+            status,
+        ))),
+    }
+}
+
+pub async fn list_accounts(service: &str) -> Result<Vec<String>> {
+    let result = ItemSearchOptions::new()
+        .class(ItemClass::generic_password())
+        .service(service)
+        .load_attributes(true)
+        .limit(Limit::All)
+        .search();
+
+    let items = match result.map_err(convert_error) {
+        Ok(items) => items,
+        Err(Error::NotFound(_)) => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    // The attributes are keyed by their short names, `acct` is the account
+    Ok(items
+        .iter()
+        .filter_map(|item| item.simplify_dict()?.remove("acct"))
+        .collect())
+}
+
+pub async fn delete_all(service: &str) -> Result<()> {
+    for account in list_accounts(service).await? {
+        delete_password(service, &account).await?;
+    }
+
+    Ok(())
+}
+
+pub async fn is_available() -> Result<bool> {
     Ok(true)
 }
 
+// Convert the Security framework result codes to their matching error kinds
+fn convert_error(e: security_framework::base::Error) -> Error {
+    match e.code() {
+        ERR_SEC_ITEM_NOT_FOUND => Error::NotFound(e.to_string()),
+        ERR_SEC_USER_CANCELED => Error::UserCancelled,
+        ERR_SEC_AUTH_FAILED | ERR_SEC_INTERACTION_NOT_ALLOWED => Error::AccessDenied(e.to_string()),
+        _ => Error::Platform(e.to_string()),
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use futures::executor::block_on;
+
     use super::*;
 
     #[test]
     fn test() {
-        scopeguard::defer!(delete_password("BitwardenTest", "BitwardenTest").unwrap_or({}););
-        set_password("BitwardenTest", "BitwardenTest", "Random").unwrap();
+        scopeguard::defer!({
+            let _ = block_on(delete_password("BitwardenTest", "BitwardenTest"));
+        });
+        block_on(set_password("BitwardenTest", "BitwardenTest", "Random")).unwrap();
         assert_eq!(
             "Random",
-            get_password("BitwardenTest", "BitwardenTest").unwrap()
+            &*block_on(get_password("BitwardenTest", "BitwardenTest")).unwrap()
         );
-        delete_password("BitwardenTest", "BitwardenTest").unwrap();
+        block_on(delete_password("BitwardenTest", "BitwardenTest")).unwrap();
 
         // Ensure password is deleted
-        match get_password("BitwardenTest", "BitwardenTest") {
+        match block_on(get_password("BitwardenTest", "BitwardenTest")) {
             Ok(_) => panic!("Got a result"),
             Err(e) => assert_eq!(
                 "The specified item could not be found in the keychain.",
@@ -52,12 +213,55 @@ mod tests {
 
     #[test]
     fn test_error_no_password() {
-        match get_password("Unknown", "Unknown") {
+        match block_on(get_password("Unknown", "Unknown")) {
             Ok(_) => panic!("Got a result"),
-            Err(e) => assert_eq!(
-                "The specified item could not be found in the keychain.",
-                e.to_string()
-            ),
+            Err(e) => {
+                assert!(matches!(e, Error::NotFound(_)));
+                assert_eq!(
+                    "The specified item could not be found in the keychain.",
+                    e.to_string()
+                );
+            }
         }
     }
+
+    #[test]
+    fn test_list_accounts_delete_all() {
+        scopeguard::defer!({
+            let _ = block_on(delete_all("BitwardenTestList"));
+        });
+        block_on(set_password("BitwardenTestList", "Account1", "Random")).unwrap();
+        block_on(set_password("BitwardenTestList", "Account2", "Random")).unwrap();
+
+        let mut accounts = block_on(list_accounts("BitwardenTestList")).unwrap();
+        accounts.sort();
+        assert_eq!(vec!["Account1", "Account2"], accounts);
+
+        block_on(delete_all("BitwardenTestList")).unwrap();
+        assert!(block_on(list_accounts("BitwardenTestList"))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_secret_with_metadata() {
+        scopeguard::defer!({
+            let _ = block_on(delete_password("BitwardenTestSecret", "BitwardenTest"));
+        });
+        let metadata = SecretMetadata {
+            label: Some("Bitwarden test secret".to_string()),
+            attributes: std::collections::HashMap::from([("version".to_string(), "2".to_string())]),
+        };
+        block_on(set_secret(
+            "BitwardenTestSecret",
+            "BitwardenTest",
+            &[0, 159, 146, 150],
+            &metadata,
+        ))
+        .unwrap();
+
+        let stored = block_on(get_secret("BitwardenTestSecret", "BitwardenTest")).unwrap();
+        assert_eq!(&[0, 159, 146, 150], &*stored.secret);
+        assert_eq!(metadata, stored.metadata);
+    }
 }
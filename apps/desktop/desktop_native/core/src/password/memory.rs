@@ -0,0 +1,198 @@
+//! A password store kept in memory, to test the code storing secrets through this module without
+//! touching the secure storage of the OS.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, MutexGuard,
+    },
+};
+
+use crate::{
+    crypto::{SecretBytes, SecretString},
+    error::{Error, Result},
+};
+
+use super::{PasswordBackend, SecretMetadata, StoredSecret};
+
+struct Entry {
+    secret: SecretBytes,
+    metadata: SecretMetadata,
+}
+
+pub struct MemoryBackend {
+    entries: Mutex<HashMap<(String, String), Entry>>,
+    unavailable: AtomicBool,
+}
+
+impl MemoryBackend {
+    pub fn new() -> Self {
+        MemoryBackend {
+            entries: Mutex::new(HashMap::new()),
+            unavailable: AtomicBool::new(false),
+        }
+    }
+
+    /// Simulate a store which can't be reached, failing all calls with
+    /// [`Error::ServiceUnavailable`].
+    pub fn set_available(&self, available: bool) {
+        self.unavailable.store(!available, Ordering::SeqCst);
+    }
+
+    fn entries(&self) -> Result<MutexGuard<'_, HashMap<(String, String), Entry>>> {
+        if self.unavailable.load(Ordering::SeqCst) {
+            return Err(Error::ServiceUnavailable(
+                "The password store is unavailable".to_string(),
+            ));
+        }
+
+        Ok(self.entries.lock().expect("Mutex is not poisoned"))
+    }
+}
+
+impl Default for MemoryBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PasswordBackend for MemoryBackend {
+    fn get_password(&self, service: &str, account: &str) -> Result<SecretString> {
+        let secret = self.get_secret(service, account)?.secret;
+        SecretString::try_from(secret).map_err(|e| Error::InvalidInput(e.to_string()))
+    }
+
+    fn set_password(&self, service: &str, account: &str, password: &str) -> Result<()> {
+        self.set_secret(
+            service,
+            account,
+            password.as_bytes(),
+            &SecretMetadata::default(),
+        )
+    }
+
+    fn delete_password(&self, service: &str, account: &str) -> Result<()> {
+        self.entries()?
+            .remove(&(service.to_string(), account.to_string()))
+            .map(|_| ())
+            .ok_or(Error::NotFound("No password found".to_string()))
+    }
+
+    fn get_secret(&self, service: &str, account: &str) -> Result<StoredSecret> {
+        let entries = self.entries()?;
+        let entry = entries
+            .get(&(service.to_string(), account.to_string()))
+            .ok_or(Error::NotFound("No password found".to_string()))?;
+
+        Ok(StoredSecret {
+            secret: SecretBytes::from(entry.secret.to_vec()),
+            metadata: entry.metadata.clone(),
+        })
+    }
+
+    fn set_secret(
+        &self,
+        service: &str,
+        account: &str,
+        secret: &[u8],
+        metadata: &SecretMetadata,
+    ) -> Result<()> {
+        self.entries()?.insert(
+            (service.to_string(), account.to_string()),
+            Entry {
+                secret: SecretBytes::from(secret.to_vec()),
+                metadata: metadata.clone(),
+            },
+        );
+        Ok(())
+    }
+
+    fn list_accounts(&self, service: &str) -> Result<Vec<String>> {
+        Ok(self
+            .entries()?
+            .keys()
+            .filter(|(entry_service, _)| entry_service == service)
+            .map(|(_, account)| account.clone())
+            .collect())
+    }
+
+    fn delete_all(&self, service: &str) -> Result<()> {
+        self.entries()?
+            .retain(|(entry_service, _), _| entry_service != service);
+        Ok(())
+    }
+
+    fn is_available(&self) -> Result<bool> {
+        Ok(!self.unavailable.load(Ordering::SeqCst))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_get_delete() {
+        let backend = MemoryBackend::new();
+        backend
+            .set_password("BitwardenTest", "BitwardenTest", "Random")
+            .unwrap();
+        assert_eq!(
+            "Random",
+            &*backend
+                .get_password("BitwardenTest", "BitwardenTest")
+                .unwrap()
+        );
+
+        backend
+            .delete_password("BitwardenTest", "BitwardenTest")
+            .unwrap();
+        assert!(matches!(
+            backend.get_password("BitwardenTest", "BitwardenTest"),
+            Err(Error::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn list_accounts_delete_all() {
+        let backend = MemoryBackend::new();
+        backend
+            .set_password("BitwardenTestList", "Account1", "Random")
+            .unwrap();
+        backend
+            .set_password("BitwardenTestList", "Account2", "Random")
+            .unwrap();
+        backend
+            .set_password("BitwardenTest", "Account3", "Random")
+            .unwrap();
+
+        let mut accounts = backend.list_accounts("BitwardenTestList").unwrap();
+        accounts.sort();
+        assert_eq!(vec!["Account1", "Account2"], accounts);
+
+        backend.delete_all("BitwardenTestList").unwrap();
+        assert!(backend
+            .list_accounts("BitwardenTestList")
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            vec!["Account3"],
+            backend.list_accounts("BitwardenTest").unwrap()
+        );
+    }
+
+    #[test]
+    fn unavailable() {
+        let backend = MemoryBackend::new();
+        backend.set_available(false);
+        assert!(!backend.is_available().unwrap());
+        assert!(matches!(
+            backend.set_password("BitwardenTest", "BitwardenTest", "Random"),
+            Err(Error::ServiceUnavailable(_))
+        ));
+
+        backend.set_available(true);
+        assert!(backend.is_available().unwrap());
+    }
+}
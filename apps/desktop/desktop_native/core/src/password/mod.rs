@@ -1,5 +1,206 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use crate::{
+    crypto::{SecretBytes, SecretString},
+    error::{Error, Result},
+};
+
 #[cfg_attr(target_os = "linux", path = "unix.rs")]
 #[cfg_attr(target_os = "windows", path = "windows.rs")]
 #[cfg_attr(target_os = "macos", path = "macos.rs")]
 mod password;
-pub use password::*;
+pub use password::get_password_keytar;
+
+mod encrypted_file;
+pub use encrypted_file::{EncryptedFileBackend, FileKey};
+
+#[cfg(any(test, feature = "mock"))]
+mod memory;
+#[cfg(any(test, feature = "mock"))]
+pub use memory::MemoryBackend;
+
+#[cfg(target_os = "linux")]
+mod kernel_keyring;
+#[cfg(target_os = "linux")]
+pub use kernel_keyring::{KernelKeyringBackend, Keyring};
+#[cfg(target_os = "linux")]
+mod keyutils;
+#[cfg(target_os = "linux")]
+mod secret_service;
+
+// The attributes identifying an entry, which can't be set as metadata
+const RESERVED_ATTRIBUTES: [&str; 3] = ["service", "account", "xdg:schema"];
+
+/// Metadata stored alongside a secret, e.g. its creation time, the app version or the version
+/// of the key derivation it was stored with.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SecretMetadata {
+    /// The label shown by keyring managers, `service/account` if unset.
+    pub label: Option<String>,
+    pub attributes: HashMap<String, String>,
+}
+
+/// A binary secret and its metadata.
+#[derive(Debug)]
+pub struct StoredSecret {
+    pub secret: SecretBytes,
+    pub metadata: SecretMetadata,
+}
+
+/// A store for passwords, identified by a service and an account, which replaces the secure
+/// storage of the OS when selected with [`set_backend`].
+///
+/// The backends are local stores and are called synchronously, unlike the OS stores whose calls
+/// can wait on the user, e.g. to unlock the keyring.
+pub trait PasswordBackend: Send + Sync {
+    fn get_password(&self, service: &str, account: &str) -> Result<SecretString>;
+    /// Adds an entry if none exists otherwise updates the existing entry.
+    fn set_password(&self, service: &str, account: &str, password: &str) -> Result<()>;
+    fn delete_password(&self, service: &str, account: &str) -> Result<()>;
+    /// Like [`PasswordBackend::get_password`], for entries stored with
+    /// [`PasswordBackend::set_secret`].
+    fn get_secret(&self, service: &str, account: &str) -> Result<StoredSecret>;
+    /// Store a binary secret with metadata. Adds an entry if none exists otherwise replaces the
+    /// secret and metadata of the existing entry.
+    fn set_secret(
+        &self,
+        service: &str,
+        account: &str,
+        secret: &[u8],
+        metadata: &SecretMetadata,
+    ) -> Result<()>;
+    /// The accounts with a password stored for the service.
+    fn list_accounts(&self, service: &str) -> Result<Vec<String>>;
+    /// Delete the passwords of all accounts of the service.
+    fn delete_all(&self, service: &str) -> Result<()>;
+    fn is_available(&self) -> Result<bool>;
+}
+
+// The backend used by the free functions below, the secure storage of the OS when unset
+static BACKEND: RwLock<Option<Arc<dyn PasswordBackend>>> = RwLock::new(None);
+
+/// Select the backend used by the free functions of this module.
+pub fn set_backend(backend: Arc<dyn PasswordBackend>) {
+    *BACKEND.write().expect("RwLock is not poisoned") = Some(backend);
+}
+
+/// Go back to the secure storage of the OS.
+pub fn reset_backend() {
+    *BACKEND.write().expect("RwLock is not poisoned") = None;
+}
+
+/// Select an empty [`MemoryBackend`] until the guard is dropped, for the tests of the modules
+/// storing their secrets through the free functions of this module. The backend is global, so
+/// the tests holding a guard run one at a time.
+#[cfg(test)]
+pub(crate) fn use_memory_backend() -> MemoryBackendGuard {
+    static TESTS: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+    // A failed test doesn't affect the others, its guard restored the backend
+    let lock = TESTS
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+    let mut backend = BACKEND.write().expect("RwLock is not poisoned");
+    let previous = backend.replace(Arc::new(MemoryBackend::new()));
+    MemoryBackendGuard {
+        previous,
+        _lock: lock,
+    }
+}
+
+/// Restores the backend selected before [`use_memory_backend`] when dropped.
+#[cfg(test)]
+pub(crate) struct MemoryBackendGuard {
+    previous: Option<Arc<dyn PasswordBackend>>,
+    _lock: std::sync::MutexGuard<'static, ()>,
+}
+
+#[cfg(test)]
+impl Drop for MemoryBackendGuard {
+    fn drop(&mut self) {
+        *BACKEND.write().expect("RwLock is not poisoned") = self.previous.take();
+    }
+}
+
+fn backend() -> Option<Arc<dyn PasswordBackend>> {
+    BACKEND.read().expect("RwLock is not poisoned").clone()
+}
+
+// The functions below use the secure storage of the OS unless a backend was selected: the
+// Credential Manager on Windows, the Keychain on macOS and the Secret Service on Linux. Dropping
+// the returned futures cancels the calls to the Secret Service, dismissing the prompts they show.
+
+pub async fn get_password(service: &str, account: &str) -> Result<SecretString> {
+    match backend() {
+        Some(backend) => backend.get_password(service, account),
+        None => password::get_password(service, account).await,
+    }
+}
+
+pub async fn set_password(service: &str, account: &str, password: &str) -> Result<()> {
+    match backend() {
+        Some(backend) => backend.set_password(service, account, password),
+        None => password::set_password(service, account, password).await,
+    }
+}
+
+pub async fn delete_password(service: &str, account: &str) -> Result<()> {
+    match backend() {
+        Some(backend) => backend.delete_password(service, account),
+        None => password::delete_password(service, account).await,
+    }
+}
+
+pub async fn get_secret(service: &str, account: &str) -> Result<StoredSecret> {
+    match backend() {
+        Some(backend) => backend.get_secret(service, account),
+        None => password::get_secret(service, account).await,
+    }
+}
+
+pub async fn set_secret(
+    service: &str,
+    account: &str,
+    secret: &[u8],
+    metadata: &SecretMetadata,
+) -> Result<()> {
+    if let Some(name) = metadata
+        .attributes
+        .keys()
+        .find(|name| RESERVED_ATTRIBUTES.contains(&name.as_str()))
+    {
+        return Err(Error::InvalidInput(format!(
+            "The attribute {} is reserved",
+            name
+        )));
+    }
+
+    match backend() {
+        Some(backend) => backend.set_secret(service, account, secret, metadata),
+        None => password::set_secret(service, account, secret, metadata).await,
+    }
+}
+
+pub async fn list_accounts(service: &str) -> Result<Vec<String>> {
+    match backend() {
+        Some(backend) => backend.list_accounts(service),
+        None => password::list_accounts(service).await,
+    }
+}
+
+pub async fn delete_all(service: &str) -> Result<()> {
+    match backend() {
+        Some(backend) => backend.delete_all(service),
+        None => password::delete_all(service).await,
+    }
+}
+
+pub async fn is_available() -> Result<bool> {
+    match backend() {
+        Some(backend) => backend.is_available(),
+        None => password::is_available().await,
+    }
+}
@@ -0,0 +1,392 @@
+//! An in-memory Secret Service, served over a private peer-to-peer D-Bus connection, to test the
+//! client without a session bus.
+
+use std::{
+    collections::HashMap,
+    os::unix::net::UnixStream,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use zbus::{
+    fdo,
+    zvariant::{OwnedObjectPath, OwnedValue, Value},
+    Connection, Guid, ObjectServer, SignalContext,
+};
+
+use super::{
+    proxy::Secret,
+    session::{KeyExchange, SessionKey, ALGORITHM_DH, ALGORITHM_PLAIN},
+};
+
+const SERVICE_PATH: &str = "/org/freedesktop/secrets";
+const COLLECTION_PATH: &str = "/org/freedesktop/secrets/collection/login";
+
+struct MockItem {
+    label: String,
+    attributes: HashMap<String, String>,
+    secret: Vec<u8>,
+    content_type: String,
+}
+
+#[derive(Default)]
+struct State {
+    supports_dh: bool,
+    locked: bool,
+    dismiss_prompts: bool,
+    reject_items: bool,
+    next_id: u32,
+    /// The session keys by session path, `None` for plain sessions.
+    sessions: HashMap<OwnedObjectPath, Option<SessionKey>>,
+    items: HashMap<OwnedObjectPath, MockItem>,
+}
+
+impl State {
+    fn next_path(&mut self, prefix: &str) -> OwnedObjectPath {
+        self.next_id += 1;
+        OwnedObjectPath::try_from(format!("{}/{}", prefix, self.next_id)).expect("valid path")
+    }
+}
+
+/// A running mock service, which is stopped when dropped.
+pub struct MockSecretService {
+    state: Arc<Mutex<State>>,
+    _server: Connection,
+}
+
+impl MockSecretService {
+    /// Start the service, returning it with a client connection to it. Sessions are plain when
+    /// `supports_dh` is not set.
+    pub async fn start(supports_dh: bool) -> (Self, Connection) {
+        let state = Arc::new(Mutex::new(State {
+            supports_dh,
+            ..Default::default()
+        }));
+
+        let (server, client) = UnixStream::pair().unwrap();
+        let server = zbus::connection::Builder::unix_stream(server)
+            .server(Guid::generate())
+            .unwrap()
+            .p2p()
+            .serve_at(
+                SERVICE_PATH,
+                MockService {
+                    state: state.clone(),
+                },
+            )
+            .unwrap()
+            .serve_at(
+                COLLECTION_PATH,
+                MockCollection {
+                    state: state.clone(),
+                },
+            )
+            .unwrap()
+            .build();
+        let client = zbus::connection::Builder::unix_stream(client).p2p().build();
+        let (server, client) = futures::try_join!(server, client).unwrap();
+
+        (
+            MockSecretService {
+                state,
+                _server: server,
+            },
+            client,
+        )
+    }
+
+    pub fn lock(&self) {
+        self.state().locked = true;
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.state().locked
+    }
+
+    /// Whether the user dismisses the unlock prompts.
+    pub fn dismiss_prompts(&self, dismiss: bool) {
+        self.state().dismiss_prompts = dismiss;
+    }
+
+    /// Whether creating items fails, e.g. when the keyring can't be written.
+    pub fn reject_items(&self, reject: bool) {
+        self.state().reject_items = reject;
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+}
+
+struct MockService {
+    state: Arc<Mutex<State>>,
+}
+
+#[zbus::interface(name = "org.freedesktop.Secret.Service")]
+impl MockService {
+    fn open_session(
+        &self,
+        algorithm: &str,
+        input: Value<'_>,
+    ) -> fdo::Result<(Value<'static>, OwnedObjectPath)> {
+        let mut state = self.state.lock().unwrap();
+        let (output, key) = match algorithm {
+            ALGORITHM_DH if state.supports_dh => {
+                let peer_public_key = Vec::<u8>::try_from(input)
+                    .map_err(|e| fdo::Error::InvalidArgs(e.to_string()))?;
+                let key_exchange = KeyExchange::new();
+                let key = key_exchange
+                    .derive_key(&peer_public_key)
+                    .map_err(|e| fdo::Error::InvalidArgs(e.to_string()))?;
+                (Value::from(key_exchange.public_key()), Some(key))
+            }
+            ALGORITHM_PLAIN => (Value::from(""), None),
+            _ => return Err(fdo::Error::NotSupported(algorithm.to_string())),
+        };
+
+        let session = state.next_path("/org/freedesktop/secrets/session");
+        state.sessions.insert(session.clone(), key);
+        Ok((output, session))
+    }
+
+    fn create_collection(
+        &self,
+        _properties: HashMap<String, OwnedValue>,
+        _alias: &str,
+    ) -> fdo::Result<(OwnedObjectPath, OwnedObjectPath)> {
+        Err(fdo::Error::NotSupported(
+            "The mock has a default collection".to_string(),
+        ))
+    }
+
+    fn search_items(
+        &self,
+        attributes: HashMap<String, String>,
+    ) -> (Vec<OwnedObjectPath>, Vec<OwnedObjectPath>) {
+        let state = self.state.lock().unwrap();
+        let items = state
+            .items
+            .iter()
+            .filter(|(_, item)| {
+                attributes
+                    .iter()
+                    .all(|(name, value)| item.attributes.get(name) == Some(value))
+            })
+            .map(|(path, _)| path.clone())
+            .collect();
+
+        match state.locked {
+            true => (Vec::new(), items),
+            false => (items, Vec::new()),
+        }
+    }
+
+    async fn unlock(
+        &self,
+        objects: Vec<OwnedObjectPath>,
+        #[zbus(object_server)] server: &ObjectServer,
+    ) -> fdo::Result<(Vec<OwnedObjectPath>, OwnedObjectPath)> {
+        let prompt = {
+            let mut state = self.state.lock().unwrap();
+            if !state.locked {
+                return Ok((objects, OwnedObjectPath::try_from("/").unwrap()));
+            }
+            state.next_path("/org/freedesktop/secrets/prompt")
+        };
+
+        server
+            .at(
+                &prompt,
+                MockPrompt {
+                    state: self.state.clone(),
+                    objects,
+                },
+            )
+            .await?;
+        Ok((Vec::new(), prompt))
+    }
+
+    fn read_alias(&self, name: &str) -> OwnedObjectPath {
+        match name {
+            "default" => OwnedObjectPath::try_from(COLLECTION_PATH).unwrap(),
+            _ => OwnedObjectPath::try_from("/").unwrap(),
+        }
+    }
+}
+
+struct MockCollection {
+    state: Arc<Mutex<State>>,
+}
+
+#[zbus::interface(name = "org.freedesktop.Secret.Collection")]
+impl MockCollection {
+    async fn create_item(
+        &self,
+        mut properties: HashMap<String, OwnedValue>,
+        secret: Secret,
+        replace: bool,
+        #[zbus(object_server)] server: &ObjectServer,
+    ) -> fdo::Result<(OwnedObjectPath, OwnedObjectPath)> {
+        let invalid_args = |e: zbus::zvariant::Error| fdo::Error::InvalidArgs(e.to_string());
+        let label = properties
+            .remove("org.freedesktop.Secret.Item.Label")
+            .map(String::try_from)
+            .transpose()
+            .map_err(invalid_args)?
+            .unwrap_or_default();
+        let attributes = properties
+            .remove("org.freedesktop.Secret.Item.Attributes")
+            .map(HashMap::<String, String>::try_from)
+            .transpose()
+            .map_err(invalid_args)?
+            .unwrap_or_default();
+
+        let (path, created) = {
+            let mut state = self.state.lock().unwrap();
+            if state.locked {
+                return Err(fdo::Error::AccessDenied(
+                    "The collection is locked".to_string(),
+                ));
+            }
+            if state.reject_items {
+                return Err(fdo::Error::Failed("The item can't be stored".to_string()));
+            }
+            let value = match state.sessions.get(&secret.session) {
+                Some(Some(key)) => key
+                    .decrypt(&secret.parameters, &secret.value)
+                    .map_err(|e| fdo::Error::InvalidArgs(e.to_string()))?
+                    .to_vec(),
+                Some(None) => secret.value.clone(),
+                None => return Err(fdo::Error::InvalidArgs("No such session".to_string())),
+            };
+
+            let existing = state
+                .items
+                .iter()
+                .find(|(_, item)| replace && item.attributes == attributes)
+                .map(|(path, _)| path.clone());
+            let created = existing.is_none();
+            let path = existing.unwrap_or_else(|| state.next_path(COLLECTION_PATH));
+            state.items.insert(
+                path.clone(),
+                MockItem {
+                    label,
+                    attributes,
+                    secret: value,
+                    content_type: secret.content_type.clone(),
+                },
+            );
+            (path, created)
+        };
+
+        if created {
+            server
+                .at(
+                    &path,
+                    MockItemObject {
+                        state: self.state.clone(),
+                        path: path.clone(),
+                    },
+                )
+                .await?;
+        }
+        Ok((path, OwnedObjectPath::try_from("/").unwrap()))
+    }
+}
+
+struct MockItemObject {
+    state: Arc<Mutex<State>>,
+    path: OwnedObjectPath,
+}
+
+impl MockItemObject {
+    fn with_item<T>(&self, f: impl FnOnce(&State, &MockItem) -> fdo::Result<T>) -> fdo::Result<T> {
+        let state = self.state.lock().unwrap();
+        match state.items.get(&self.path) {
+            Some(item) => f(&state, item),
+            None => Err(fdo::Error::UnknownObject(self.path.to_string())),
+        }
+    }
+}
+
+#[zbus::interface(name = "org.freedesktop.Secret.Item")]
+impl MockItemObject {
+    async fn delete(
+        &self,
+        #[zbus(object_server)] server: &ObjectServer,
+    ) -> fdo::Result<OwnedObjectPath> {
+        self.state.lock().unwrap().items.remove(&self.path);
+        server.remove::<MockItemObject, _>(&self.path).await?;
+        Ok(OwnedObjectPath::try_from("/").unwrap())
+    }
+
+    fn get_secret(&self, session: OwnedObjectPath) -> fdo::Result<Secret> {
+        self.with_item(|state, item| {
+            let (parameters, value) = match state.sessions.get(&session) {
+                Some(Some(key)) => {
+                    let (iv, value) = key.encrypt(&item.secret);
+                    (iv.to_vec(), value)
+                }
+                Some(None) => (Vec::new(), item.secret.clone()),
+                None => return Err(fdo::Error::InvalidArgs("No such session".to_string())),
+            };
+
+            Ok(Secret {
+                session: session.clone(),
+                parameters,
+                value,
+                content_type: item.content_type.clone(),
+            })
+        })
+    }
+
+    #[zbus(property)]
+    fn label(&self) -> fdo::Result<String> {
+        self.with_item(|_, item| Ok(item.label.clone()))
+    }
+
+    #[zbus(property)]
+    fn attributes(&self) -> fdo::Result<HashMap<String, String>> {
+        self.with_item(|_, item| Ok(item.attributes.clone()))
+    }
+}
+
+struct MockPrompt {
+    state: Arc<Mutex<State>>,
+    objects: Vec<OwnedObjectPath>,
+}
+
+#[zbus::interface(name = "org.freedesktop.Secret.Prompt")]
+impl MockPrompt {
+    async fn prompt(
+        &self,
+        _window_id: &str,
+        #[zbus(signal_context)] context: SignalContext<'_>,
+    ) -> fdo::Result<()> {
+        let dismissed = {
+            let mut state = self.state.lock().unwrap();
+            if !state.dismiss_prompts {
+                state.locked = false;
+            }
+            state.dismiss_prompts
+        };
+
+        let result = match dismissed {
+            true => Vec::new(),
+            false => self.objects.clone(),
+        };
+        Self::completed(&context, dismissed, Value::from(result)).await?;
+        Ok(())
+    }
+
+    async fn dismiss(&self, #[zbus(signal_context)] context: SignalContext<'_>) -> fdo::Result<()> {
+        Self::completed(&context, true, Value::from(Vec::<OwnedObjectPath>::new())).await?;
+        Ok(())
+    }
+
+    #[zbus(signal)]
+    async fn completed(
+        context: &SignalContext<'_>,
+        dismissed: bool,
+        result: Value<'_>,
+    ) -> zbus::Result<()>;
+}
@@ -0,0 +1,500 @@
+//! A client for the freedesktop.org Secret Service, implemented by GNOME Keyring and KWallet,
+//! talking to the service over D-Bus.
+//!
+//! https://specifications.freedesktop.org/secret-service-spec/latest/
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use futures::StreamExt;
+use zbus::{
+    proxy::CacheProperties,
+    zvariant::{OwnedObjectPath, OwnedValue, Value},
+    Connection,
+};
+
+use crate::{
+    crypto::SecretBytes,
+    error::{Error, Result},
+};
+
+use proxy::{CollectionProxy, ItemProxy, PromptProxy, Secret, ServiceProxy};
+use session::{KeyExchange, SessionKey, ALGORITHM_DH, ALGORITHM_PLAIN};
+
+#[cfg(test)]
+pub(super) mod mock;
+mod proxy;
+mod session;
+
+const ITEM_LABEL: &str = "org.freedesktop.Secret.Item.Label";
+const ITEM_ATTRIBUTES: &str = "org.freedesktop.Secret.Item.Attributes";
+const COLLECTION_LABEL: &str = "org.freedesktop.Secret.Collection.Label";
+
+// The session shared by the callers of [`SecretService::shared`]
+static SHARED: Mutex<Option<Arc<SecretService>>> = Mutex::new(None);
+
+/// A session with the Secret Service, through which the secrets are transferred.
+pub struct SecretService {
+    connection: Connection,
+    service: ServiceProxy<'static>,
+    session: OwnedObjectPath,
+    /// The key the secrets are encrypted with, `None` when they are transferred unencrypted.
+    key: Option<SessionKey>,
+}
+
+impl SecretService {
+    /// The session shared by all callers, opened on the session bus by the first call. The
+    /// connection and the session are reused until [`SecretService::reset_shared`] is called.
+    pub async fn shared() -> Result<Arc<Self>> {
+        if let Some(client) = SHARED.lock().expect("Mutex is not poisoned").as_ref() {
+            return Ok(client.clone());
+        }
+
+        // Connecting can't hold the lock, another caller may have connected in the meantime
+        let client = Arc::new(Self::connect().await?);
+        Ok(SHARED
+            .lock()
+            .expect("Mutex is not poisoned")
+            .get_or_insert(client)
+            .clone())
+    }
+
+    /// Close the shared session, so that the next call to [`SecretService::shared`] reconnects,
+    /// e.g. after the connection was lost or the service was restarted.
+    pub fn reset_shared() {
+        SHARED.lock().expect("Mutex is not poisoned").take();
+    }
+
+    /// Open a session with the Secret Service on the session bus.
+    pub async fn connect() -> Result<Self> {
+        let connection = Connection::session()
+            .await
+            .map_err(|e| Error::ServiceUnavailable(e.to_string()))?;
+        Self::new(connection).await
+    }
+
+    /// Open a session with the Secret Service on the connection, encrypting the secrets when the
+    /// service supports it.
+    pub async fn new(connection: Connection) -> Result<Self> {
+        let service = ServiceProxy::builder(&connection)
+            .cache_properties(CacheProperties::No)
+            .build()
+            .await
+            .map_err(convert_error)?;
+
+        let key_exchange = KeyExchange::new();
+        let (session, key) = match service
+            .open_session(ALGORITHM_DH, &Value::from(key_exchange.public_key()))
+            .await
+        {
+            Ok((output, session)) => {
+                let peer_public_key =
+                    Vec::<u8>::try_from(Value::from(output)).map_err(invalid_reply)?;
+                (session, Some(key_exchange.derive_key(&peer_public_key)?))
+            }
+            Err(zbus::Error::MethodError(name, _, _))
+                if name.as_str() == "org.freedesktop.DBus.Error.NotSupported" =>
+            {
+                let (_, session) = service
+                    .open_session(ALGORITHM_PLAIN, &Value::from(""))
+                    .await
+                    .map_err(convert_error)?;
+                (session, None)
+            }
+            Err(e) => return Err(convert_error(e)),
+        };
+
+        Ok(SecretService {
+            connection,
+            service,
+            session,
+            key,
+        })
+    }
+
+    /// The items with all of the attributes. When `unlock` is set, the locked items are unlocked,
+    /// prompting the user if needed, otherwise they are returned as they are.
+    pub async fn search_items(
+        &self,
+        attributes: HashMap<&str, &str>,
+        unlock: bool,
+    ) -> Result<Vec<OwnedObjectPath>> {
+        let (mut items, locked) = self
+            .service
+            .search_items(attributes)
+            .await
+            .map_err(convert_error)?;
+        if !locked.is_empty() {
+            if unlock {
+                items.extend(self.unlock(&locked).await?);
+            } else {
+                items.extend(locked);
+            }
+        }
+
+        Ok(items)
+    }
+
+    pub async fn get_secret(&self, item: &OwnedObjectPath) -> Result<SecretBytes> {
+        let secret = self
+            .item(item)
+            .await?
+            .get_secret(&self.session)
+            .await
+            .map_err(convert_error)?;
+
+        match &self.key {
+            Some(key) => key.decrypt(&secret.parameters, &secret.value),
+            None => Ok(SecretBytes::from(secret.value.clone())),
+        }
+    }
+
+    pub async fn label(&self, item: &OwnedObjectPath) -> Result<String> {
+        self.item(item).await?.label().await.map_err(convert_error)
+    }
+
+    pub async fn attributes(&self, item: &OwnedObjectPath) -> Result<HashMap<String, String>> {
+        self.item(item)
+            .await?
+            .attributes()
+            .await
+            .map_err(convert_error)
+    }
+
+    /// Store a secret in the default collection, replacing the secret and label of the item with
+    /// the same attributes. Returns the path of the stored item.
+    pub async fn create_item(
+        &self,
+        label: &str,
+        attributes: HashMap<&str, &str>,
+        secret: &[u8],
+        content_type: &str,
+    ) -> Result<OwnedObjectPath> {
+        let collection = self.default_collection().await?;
+        // Items can only be added to unlocked collections, unlocking an unlocked collection
+        // doesn't prompt
+        self.unlock(std::slice::from_ref(&collection)).await?;
+
+        let properties = HashMap::from([
+            (ITEM_LABEL, Value::from(label)),
+            (ITEM_ATTRIBUTES, Value::from(attributes)),
+        ]);
+        let (item, prompt) = CollectionProxy::builder(&self.connection)
+            .path(collection)
+            .map_err(convert_error)?
+            .cache_properties(CacheProperties::No)
+            .build()
+            .await
+            .map_err(convert_error)?
+            .create_item(properties, &self.secret(secret, content_type), true)
+            .await
+            .map_err(convert_error)?;
+        if item.as_str() != "/" {
+            return Ok(item);
+        }
+        match self.prompt(prompt).await? {
+            Some(result) => OwnedObjectPath::try_from(Value::from(result)).map_err(invalid_reply),
+            None => Err(Error::Platform(
+                "The Secret Service did not create an item".to_string(),
+            )),
+        }
+    }
+
+    pub async fn delete_item(&self, item: &OwnedObjectPath) -> Result<()> {
+        let prompt = self
+            .item(item)
+            .await?
+            .delete()
+            .await
+            .map_err(convert_error)?;
+        self.prompt(prompt).await?;
+
+        Ok(())
+    }
+
+    /// Unlock the items or collections, returning the ones which were unlocked.
+    async fn unlock(&self, objects: &[OwnedObjectPath]) -> Result<Vec<OwnedObjectPath>> {
+        let (mut unlocked, prompt) = self.service.unlock(objects).await.map_err(convert_error)?;
+        if let Some(result) = self.prompt(prompt).await? {
+            unlocked.extend(
+                Vec::<OwnedObjectPath>::try_from(Value::from(result)).map_err(invalid_reply)?,
+            );
+        }
+
+        Ok(unlocked)
+    }
+
+    // The collection new items are stored in, created when there is none yet, e.g. on a fresh
+    // install
+    async fn default_collection(&self) -> Result<OwnedObjectPath> {
+        let collection = self
+            .service
+            .read_alias("default")
+            .await
+            .map_err(convert_error)?;
+        if collection.as_str() != "/" {
+            return Ok(collection);
+        }
+
+        let properties = HashMap::from([(COLLECTION_LABEL, Value::from("Login"))]);
+        let (collection, prompt) = self
+            .service
+            .create_collection(properties, "default")
+            .await
+            .map_err(convert_error)?;
+        if collection.as_str() != "/" {
+            return Ok(collection);
+        }
+
+        match self.prompt(prompt).await? {
+            Some(result) => OwnedObjectPath::try_from(Value::from(result)).map_err(invalid_reply),
+            None => Err(Error::Platform(
+                "The Secret Service did not create a collection".to_string(),
+            )),
+        }
+    }
+
+    /// Show a prompt and wait for the user to complete it, returning its result. Returns `None`
+    /// without prompting when the path is `/`, which the service returns when no prompt is needed.
+    ///
+    /// The prompt is dismissed when the returned future is dropped before it completes, e.g. when
+    /// the call times out.
+    async fn prompt(&self, path: OwnedObjectPath) -> Result<Option<OwnedValue>> {
+        if path.as_str() == "/" {
+            return Ok(None);
+        }
+
+        let prompt = PromptProxy::builder(&self.connection)
+            .path(path)
+            .map_err(convert_error)?
+            .cache_properties(CacheProperties::No)
+            .build()
+            .await
+            .map_err(convert_error)?;
+        let mut completed = prompt.receive_completed().await.map_err(convert_error)?;
+
+        let executor = self.connection.executor().clone();
+        let dismiss = scopeguard::guard(prompt.clone(), move |prompt| {
+            executor
+                .spawn(
+                    async move {
+                        let _ = prompt.dismiss().await;
+                    },
+                    "dismiss prompt",
+                )
+                .detach();
+        });
+        prompt.prompt("").await.map_err(convert_error)?;
+        let completed = completed.next().await.ok_or(Error::ServiceUnavailable(
+            "The Secret Service closed the prompt".to_string(),
+        ))?;
+        scopeguard::ScopeGuard::into_inner(dismiss);
+
+        let args = completed.args().map_err(invalid_reply)?;
+        if args.dismissed {
+            return Err(Error::UserCancelled);
+        }
+        Ok(Some(args.result.try_to_owned().map_err(invalid_reply)?))
+    }
+
+    async fn item(&self, path: &OwnedObjectPath) -> Result<ItemProxy<'static>> {
+        ItemProxy::builder(&self.connection)
+            .path(path.clone())
+            .map_err(convert_error)?
+            .cache_properties(CacheProperties::No)
+            .build()
+            .await
+            .map_err(convert_error)
+    }
+
+    // The secret to transfer, encrypted with the session key
+    fn secret(&self, value: &[u8], content_type: &str) -> Secret {
+        let (parameters, value) = match &self.key {
+            Some(key) => {
+                let (iv, value) = key.encrypt(value);
+                (iv.to_vec(), value)
+            }
+            None => (Vec::new(), value.to_vec()),
+        };
+
+        Secret {
+            session: self.session.clone(),
+            parameters,
+            value,
+            content_type: content_type.to_string(),
+        }
+    }
+}
+
+fn invalid_reply(e: impl std::fmt::Display) -> Error {
+    Error::Platform(format!("Invalid reply from the Secret Service: {}", e))
+}
+
+// Convert the Secret Service and D-Bus errors to their matching error kinds
+fn convert_error(e: zbus::Error) -> Error {
+    let zbus::Error::MethodError(name, message, _) = &e else {
+        return Error::from(e);
+    };
+
+    let message = message.clone().unwrap_or_else(|| name.to_string());
+    match name.as_str() {
+        "org.freedesktop.Secret.Error.IsLocked" | "org.freedesktop.DBus.Error.AccessDenied" => {
+            Error::AccessDenied(message)
+        }
+        "org.freedesktop.Secret.Error.NoSuchObject"
+        | "org.freedesktop.DBus.Error.UnknownObject" => Error::NotFound(message),
+        "org.freedesktop.DBus.Error.ServiceUnknown"
+        | "org.freedesktop.DBus.Error.NameHasNoOwner"
+        | "org.freedesktop.DBus.Error.NoReply"
+        | "org.freedesktop.Secret.Error.NoSession" => Error::ServiceUnavailable(message),
+        _ => Error::Platform(message),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::mock::MockSecretService;
+    use super::*;
+
+    use futures::executor::block_on;
+
+    fn attributes(account: &str) -> HashMap<&str, &str> {
+        HashMap::from([("service", "BitwardenTest"), ("account", account)])
+    }
+
+    #[test]
+    fn create_get_delete() {
+        block_on(async {
+            let (_mock, connection) = MockSecretService::start(true).await;
+            let client = SecretService::new(connection).await.unwrap();
+            assert!(client.key.is_some());
+
+            client
+                .create_item("Label", attributes("Account"), b"Random", "text/plain")
+                .await
+                .unwrap();
+            let items = client
+                .search_items(attributes("Account"), true)
+                .await
+                .unwrap();
+            assert_eq!(1, items.len());
+            assert_eq!(b"Random", &*client.get_secret(&items[0]).await.unwrap());
+            assert_eq!("Label", client.label(&items[0]).await.unwrap());
+            assert_eq!(
+                Some("Account"),
+                client
+                    .attributes(&items[0])
+                    .await
+                    .unwrap()
+                    .get("account")
+                    .map(String::as_str)
+            );
+
+            client.delete_item(&items[0]).await.unwrap();
+            assert!(client
+                .search_items(attributes("Account"), true)
+                .await
+                .unwrap()
+                .is_empty());
+        })
+    }
+
+    #[test]
+    fn plain_session() {
+        block_on(async {
+            let (_mock, connection) = MockSecretService::start(false).await;
+            let client = SecretService::new(connection).await.unwrap();
+            assert!(client.key.is_none());
+
+            client
+                .create_item("Label", attributes("Account"), &[0, 159], "text/plain")
+                .await
+                .unwrap();
+            let items = client
+                .search_items(attributes("Account"), true)
+                .await
+                .unwrap();
+            assert_eq!(&[0, 159], &*client.get_secret(&items[0]).await.unwrap());
+        })
+    }
+
+    #[test]
+    fn replace_item() {
+        block_on(async {
+            let (_mock, connection) = MockSecretService::start(true).await;
+            let client = SecretService::new(connection).await.unwrap();
+
+            for secret in [b"First", b"Other"] {
+                client
+                    .create_item("Label", attributes("Account"), secret, "text/plain")
+                    .await
+                    .unwrap();
+            }
+            client
+                .create_item("Label", attributes("Account2"), b"Second", "text/plain")
+                .await
+                .unwrap();
+
+            let items = client
+                .search_items(attributes("Account"), true)
+                .await
+                .unwrap();
+            assert_eq!(1, items.len());
+            assert_eq!(b"Other", &*client.get_secret(&items[0]).await.unwrap());
+        })
+    }
+
+    #[test]
+    fn unlock_with_prompt() {
+        block_on(async {
+            let (mock, connection) = MockSecretService::start(true).await;
+            let client = SecretService::new(connection).await.unwrap();
+            client
+                .create_item("Label", attributes("Account"), b"Random", "text/plain")
+                .await
+                .unwrap();
+            mock.lock();
+
+            // Locked items are returned as they are unless they are unlocked
+            let items = client
+                .search_items(attributes("Account"), false)
+                .await
+                .unwrap();
+            assert_eq!(1, items.len());
+            assert!(mock.is_locked());
+
+            mock.dismiss_prompts(true);
+            assert!(matches!(
+                client.search_items(attributes("Account"), true).await,
+                Err(Error::UserCancelled)
+            ));
+            assert!(mock.is_locked());
+
+            mock.dismiss_prompts(false);
+            let items = client
+                .search_items(attributes("Account"), true)
+                .await
+                .unwrap();
+            assert_eq!(1, items.len());
+            assert!(!mock.is_locked());
+            assert_eq!(b"Random", &*client.get_secret(&items[0]).await.unwrap());
+        })
+    }
+
+    #[test]
+    fn missing_item() {
+        block_on(async {
+            let (_mock, connection) = MockSecretService::start(true).await;
+            let client = SecretService::new(connection).await.unwrap();
+
+            let item =
+                OwnedObjectPath::try_from("/org/freedesktop/secrets/collection/login/404").unwrap();
+            assert!(matches!(
+                client.get_secret(&item).await,
+                Err(Error::NotFound(_))
+            ));
+        })
+    }
+}
@@ -0,0 +1,99 @@
+//! The D-Bus interfaces of the Secret Service used by the client.
+//!
+//! https://specifications.freedesktop.org/secret-service-spec/latest/
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use zbus::zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Type, Value};
+use zeroize::Zeroize;
+
+/// A secret as transferred over D-Bus, encrypted with the session it was transferred in.
+#[derive(Debug, Serialize, Deserialize, Type)]
+pub struct Secret {
+    pub session: OwnedObjectPath,
+    /// The IV of an encrypted secret.
+    pub parameters: Vec<u8>,
+    pub value: Vec<u8>,
+    pub content_type: String,
+}
+
+impl Drop for Secret {
+    fn drop(&mut self) {
+        self.value.zeroize();
+    }
+}
+
+#[zbus::proxy(
+    interface = "org.freedesktop.Secret.Service",
+    default_service = "org.freedesktop.secrets",
+    default_path = "/org/freedesktop/secrets"
+)]
+pub trait Service {
+    fn open_session(
+        &self,
+        algorithm: &str,
+        input: &Value<'_>,
+    ) -> zbus::Result<(OwnedValue, OwnedObjectPath)>;
+
+    fn create_collection(
+        &self,
+        properties: HashMap<&str, Value<'_>>,
+        alias: &str,
+    ) -> zbus::Result<(OwnedObjectPath, OwnedObjectPath)>;
+
+    /// The unlocked and the locked items matching the attributes.
+    fn search_items(
+        &self,
+        attributes: HashMap<&str, &str>,
+    ) -> zbus::Result<(Vec<OwnedObjectPath>, Vec<OwnedObjectPath>)>;
+
+    fn unlock(
+        &self,
+        objects: &[OwnedObjectPath],
+    ) -> zbus::Result<(Vec<OwnedObjectPath>, OwnedObjectPath)>;
+
+    fn read_alias(&self, name: &str) -> zbus::Result<OwnedObjectPath>;
+}
+
+#[zbus::proxy(
+    interface = "org.freedesktop.Secret.Collection",
+    default_service = "org.freedesktop.secrets"
+)]
+pub trait Collection {
+    fn create_item(
+        &self,
+        properties: HashMap<&str, Value<'_>>,
+        secret: &Secret,
+        replace: bool,
+    ) -> zbus::Result<(OwnedObjectPath, OwnedObjectPath)>;
+}
+
+#[zbus::proxy(
+    interface = "org.freedesktop.Secret.Item",
+    default_service = "org.freedesktop.secrets"
+)]
+pub trait Item {
+    fn delete(&self) -> zbus::Result<OwnedObjectPath>;
+
+    fn get_secret(&self, session: &ObjectPath<'_>) -> zbus::Result<Secret>;
+
+    #[zbus(property)]
+    fn label(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn attributes(&self) -> zbus::Result<HashMap<String, String>>;
+}
+
+#[zbus::proxy(
+    interface = "org.freedesktop.Secret.Prompt",
+    default_service = "org.freedesktop.secrets"
+)]
+pub trait Prompt {
+    fn prompt(&self, window_id: &str) -> zbus::Result<()>;
+
+    fn dismiss(&self) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    fn completed(&self, dismissed: bool, result: Value<'_>) -> zbus::Result<()>;
+}
@@ -0,0 +1,153 @@
+//! The encryption of the secrets transferred over D-Bus, negotiated when opening a session.
+//!
+//! https://specifications.freedesktop.org/secret-service-spec/latest/transfer-secrets.html
+
+use aes::cipher::{
+    block_padding::Pkcs7, typenum::U16, BlockDecryptMut, BlockEncryptMut, KeyIvInit,
+};
+use rand::RngCore;
+use rsa::BigUint;
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::{
+    crypto::{kdf, SecretBytes, SymmetricKey},
+    error::{CryptoError, Error, Result},
+};
+
+/// Diffie-Hellman key exchange in the 1024 bit MODP group, with the AES-128 key derived using
+/// HKDF-SHA256 without salt and info.
+pub const ALGORITHM_DH: &str = "dh-ietf1024-sha256-aes128-cbc-pkcs7";
+/// Secrets transferred unencrypted, for services that don't support [`ALGORITHM_DH`].
+pub const ALGORITHM_PLAIN: &str = "plain";
+
+// The Second Oakley Group from RFC 2409, section 6.2
+const PRIME: &str = "FFFFFFFFFFFFFFFFC90FDAA22168C234C4C6628B80DC1CD129024E088A67CC74020BBEA63B139B22514A08798E3404DDEF9519B3CD3A431B302B0A6DF25F14374FE1356D6D51C245E485B576625E7EC6F44C42E9A637ED6B0BFF5CB6F406B7EDEE386BFB5A899FA5AE9F24117C4B1FE649286651ECE65381FFFFFFFFFFFFFFFF";
+const GENERATOR: u32 = 2;
+const KEY_SIZE: usize = 128;
+
+/// Our half of a Diffie-Hellman key exchange.
+pub struct KeyExchange {
+    private_key: BigUint,
+    public_key: BigUint,
+}
+
+impl KeyExchange {
+    pub fn new() -> Self {
+        let mut bytes = [0u8; KEY_SIZE];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        let private_key = BigUint::from_bytes_be(&bytes);
+        bytes.zeroize();
+
+        let public_key = BigUint::from(GENERATOR).modpow(&private_key, &prime());
+        KeyExchange {
+            private_key,
+            public_key,
+        }
+    }
+
+    /// The public key sent to the peer, as a big-endian integer.
+    pub fn public_key(&self) -> Vec<u8> {
+        self.public_key.to_bytes_be()
+    }
+
+    /// Derive the session key from the public key of the peer.
+    pub fn derive_key(&self, peer_public_key: &[u8]) -> Result<SessionKey> {
+        let prime = prime();
+        let peer_public_key = BigUint::from_bytes_be(peer_public_key);
+        // Reject the keys which would make the shared secret predictable
+        if peer_public_key <= BigUint::from(1u32) || peer_public_key >= &prime - 1u32 {
+            return Err(CryptoError::InvalidKey.into());
+        }
+
+        let mut shared_secret = peer_public_key.modpow(&self.private_key, &prime);
+        // The shared secret is padded to the size of the prime
+        let mut ikm = SecretBytes::from(vec![0u8; KEY_SIZE]);
+        let bytes = Zeroizing::new(shared_secret.to_bytes_be());
+        ikm.as_mut_vec()[KEY_SIZE - bytes.len()..].copy_from_slice(&bytes);
+        shared_secret.zeroize();
+
+        Ok(SessionKey(kdf::hkdf::<U16>(&ikm, &[], &[])?))
+    }
+}
+
+impl Default for KeyExchange {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for KeyExchange {
+    fn drop(&mut self) {
+        self.private_key.zeroize();
+    }
+}
+
+/// The AES-128 key secrets are encrypted with in a [`ALGORITHM_DH`] session.
+pub struct SessionKey(SymmetricKey<U16>);
+
+impl SessionKey {
+    /// Encrypt the data with a random IV, returning the IV and the ciphertext.
+    pub fn encrypt(&self, data: &[u8]) -> ([u8; 16], Vec<u8>) {
+        let mut iv = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut iv);
+        let data = cbc::Encryptor::<aes::Aes128>::new(self.0.as_array(), &iv.into())
+            .encrypt_padded_vec_mut::<Pkcs7>(data);
+
+        (iv, data)
+    }
+
+    pub fn decrypt(&self, iv: &[u8], data: &[u8]) -> Result<SecretBytes> {
+        let iv: [u8; 16] = iv
+            .try_into()
+            .map_err(|_| Error::InvalidInput("Invalid IV length".to_string()))?;
+        let data = cbc::Decryptor::<aes::Aes128>::new(self.0.as_array(), &iv.into())
+            .decrypt_padded_vec_mut::<Pkcs7>(data)
+            .map_err(|_| CryptoError::KeyDecrypt)?;
+
+        Ok(SecretBytes::from(data))
+    }
+}
+
+fn prime() -> BigUint {
+    BigUint::parse_bytes(PRIME.as_bytes(), 16).expect("The prime is valid hex")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn derive_same_key() {
+        let ours = KeyExchange::new();
+        let theirs = KeyExchange::new();
+
+        let our_key = ours.derive_key(&theirs.public_key()).unwrap();
+        let their_key = theirs.derive_key(&ours.public_key()).unwrap();
+        assert!(our_key.0 == their_key.0);
+
+        let (iv, data) = our_key.encrypt(b"secret");
+        assert_ne!(b"secret", &data[..]);
+        assert_eq!(b"secret", &*their_key.decrypt(&iv, &data).unwrap());
+    }
+
+    #[test]
+    fn reject_invalid_keys() {
+        let ours = KeyExchange::new();
+        let p_minus_one = (prime() - 1u32).to_bytes_be();
+        for key in [&[0][..], &[1], &p_minus_one, &prime().to_bytes_be()] {
+            assert!(ours.derive_key(key).is_err());
+        }
+    }
+
+    #[test]
+    fn reject_invalid_iv() {
+        let key = KeyExchange::new()
+            .derive_key(&KeyExchange::new().public_key())
+            .unwrap();
+        let (_, data) = key.encrypt(b"secret");
+        assert!(matches!(
+            key.decrypt(&[0; 8], &data),
+            Err(Error::InvalidInput(_))
+        ));
+    }
+}
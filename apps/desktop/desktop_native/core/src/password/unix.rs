@@ -1,96 +1,292 @@
-use anyhow::{anyhow, Result};
-use libsecret::{password_clear_sync, password_lookup_sync, password_store_sync, Schema};
-use std::collections::HashMap;
+use futures::future::{self, Either};
+use std::{collections::HashMap, future::Future, pin::pin, sync::Arc, time::Duration};
 
-pub fn get_password(service: &str, account: &str) -> Result<String> {
-    let res = password_lookup_sync(
-        Some(&get_schema()),
-        build_attributes(service, account),
-        gio::Cancellable::NONE,
-    )?;
+use crate::{
+    crypto::SecretString,
+    error::{Error, Result},
+};
 
-    match res {
-        Some(s) => Ok(String::from(s)),
-        None => Err(anyhow!("No password found")),
-    }
+use super::{secret_service::SecretService, SecretMetadata, StoredSecret, RESERVED_ATTRIBUTES};
+
+const TEXT_CONTENT_TYPE: &str = "text/plain";
+const BINARY_CONTENT_TYPE: &str = "application/octet-stream";
+
+// The schema libsecret stored the entries with, kept to find the existing entries
+const SCHEMA: &str = "org.freedesktop.Secret.Generic";
+
+/// The time after which a call to the Secret Service is cancelled, long enough for the user to
+/// answer an unlock prompt.
+const TIMEOUT: Duration = Duration::from_secs(120);
+/// The time after which the Secret Service is reported as unavailable, checking it doesn't prompt.
+const AVAILABILITY_TIMEOUT: Duration = Duration::from_secs(3);
+
+pub async fn get_password(service: &str, account: &str) -> Result<SecretString> {
+    run(TIMEOUT, |client| async move {
+        let item = find_item(&client, service, account).await?;
+        SecretString::try_from(client.get_secret(&item).await?)
+            .map_err(|e| Error::InvalidInput(e.to_string()))
+    })
+    .await
 }
 
-pub fn get_password_keytar(service: &str, account: &str) -> Result<String> {
-    get_password(service, account)
+pub async fn get_password_keytar(service: &str, account: &str) -> Result<SecretString> {
+    get_password(service, account).await
 }
 
-pub fn set_password(service: &str, account: &str, password: &str) -> Result<()> {
-    let result = password_store_sync(
-        Some(&get_schema()),
-        build_attributes(service, account),
-        Some(&libsecret::COLLECTION_DEFAULT),
-        &format!("{}/{}", service, account),
-        password,
-        gio::Cancellable::NONE,
-    )?;
-    Ok(result)
+pub async fn set_password(service: &str, account: &str, password: &str) -> Result<()> {
+    run(TIMEOUT, |client| async move {
+        replace_item(
+            &client,
+            service,
+            account,
+            &format!("{}/{}", service, account),
+            build_attributes(service, account),
+            password.as_bytes(),
+            TEXT_CONTENT_TYPE,
+        )
+        .await
+    })
+    .await
 }
 
-pub fn delete_password(service: &str, account: &str) -> Result<()> {
-    let result = password_clear_sync(
-        Some(&get_schema()),
-        build_attributes(service, account),
-        gio::Cancellable::NONE,
-    )?;
-    Ok(result)
+pub async fn delete_password(service: &str, account: &str) -> Result<()> {
+    run(TIMEOUT, |client| async move {
+        for item in client
+            .search_items(build_attributes(service, account), true)
+            .await?
+        {
+            client.delete_item(&item).await?;
+        }
+
+        Ok(())
+    })
+    .await
 }
 
-pub fn is_available() -> Result<bool> {
-    let result = password_clear_sync(
-        Some(&get_schema()),
-        build_attributes("bitwardenSecretsAvailabilityTest", "test"),
-        gio::Cancellable::NONE,
-    );
-    match result {
-        Ok(_) => Ok(true),
-        Err(_) => {
-            println!("secret-service unavailable: {:?}", result);
-            Ok(false)
+pub async fn get_secret(service: &str, account: &str) -> Result<StoredSecret> {
+    run(TIMEOUT, |client| async move {
+        let item = find_item(&client, service, account).await?;
+
+        let mut attributes = client.attributes(&item).await?;
+        for name in RESERVED_ATTRIBUTES {
+            attributes.remove(name);
         }
+        let metadata = SecretMetadata {
+            label: Some(client.label(&item).await?),
+            attributes,
+        };
+
+        Ok(StoredSecret {
+            secret: client.get_secret(&item).await?,
+            metadata,
+        })
+    })
+    .await
+}
+
+pub async fn set_secret(
+    service: &str,
+    account: &str,
+    secret: &[u8],
+    metadata: &SecretMetadata,
+) -> Result<()> {
+    let mut attributes = build_attributes(service, account);
+    for (name, value) in &metadata.attributes {
+        attributes.insert(name, value);
     }
+    let label = match &metadata.label {
+        Some(label) => label.clone(),
+        None => format!("{}/{}", service, account),
+    };
+
+    run(TIMEOUT, |client| async move {
+        replace_item(
+            &client,
+            service,
+            account,
+            &label,
+            attributes,
+            secret,
+            BINARY_CONTENT_TYPE,
+        )
+        .await
+    })
+    .await
+}
+
+pub async fn list_accounts(service: &str) -> Result<Vec<String>> {
+    let attributes = HashMap::from([("service", service), ("xdg:schema", SCHEMA)]);
+    run(TIMEOUT, |client| async move {
+        // The attributes of locked items can be read, so they are not unlocked
+        let mut accounts = Vec::new();
+        for item in client.search_items(attributes, false).await? {
+            if let Some(account) = client.attributes(&item).await?.remove("account") {
+                accounts.push(account);
+            }
+        }
+
+        Ok(accounts)
+    })
+    .await
 }
 
-fn get_schema() -> Schema {
-    let mut attributes = std::collections::HashMap::new();
-    attributes.insert("service", libsecret::SchemaAttributeType::String);
-    attributes.insert("account", libsecret::SchemaAttributeType::String);
+pub async fn delete_all(service: &str) -> Result<()> {
+    let attributes = HashMap::from([("service", service), ("xdg:schema", SCHEMA)]);
+    run(TIMEOUT, |client| async move {
+        for item in client.search_items(attributes, true).await? {
+            client.delete_item(&item).await?;
+        }
+
+        Ok(())
+    })
+    .await
+}
 
-    libsecret::Schema::new(
-        "org.freedesktop.Secret.Generic",
-        libsecret::SchemaFlags::NONE,
-        attributes,
-    )
+/// Whether a session can be opened with the Secret Service, without prompting the user.
+pub async fn is_available() -> Result<bool> {
+    Ok(run(AVAILABILITY_TIMEOUT, |_| async { Ok(()) })
+        .await
+        .is_ok())
+}
+
+/// Run a call with the shared Secret Service session, cancelling it when it doesn't complete
+/// within `timeout`. Cancelling the call, by the timeout or by dropping the returned future,
+/// dismisses the prompt it is showing.
+///
+/// The session is reopened by the next call when the service can't be reached, e.g. after it was
+/// restarted.
+async fn run<T, F>(timeout: Duration, call: impl FnOnce(Arc<SecretService>) -> F) -> Result<T>
+where
+    F: Future<Output = Result<T>>,
+{
+    let call = async { call(client().await?).await };
+    let result = match future::select(pin!(call), async_io::Timer::after(timeout)).await {
+        Either::Left((result, _)) => result,
+        Either::Right(_) => Err(Error::ServiceUnavailable(
+            "The Secret Service did not respond in time".to_string(),
+        )),
+    };
+    if let Err(Error::ServiceUnavailable(_)) = result {
+        SecretService::reset_shared();
+    }
+    result
+}
+
+// The shared session, or in tests the session with the mock service of the test
+async fn client() -> Result<Arc<SecretService>> {
+    #[cfg(test)]
+    if let Some(client) = tests::MOCK_CLIENT.with(|client| client.borrow().clone()) {
+        return Ok(client);
+    }
+    SecretService::shared().await
+}
+
+async fn find_item(
+    client: &SecretService,
+    service: &str,
+    account: &str,
+) -> Result<zbus::zvariant::OwnedObjectPath> {
+    client
+        .search_items(build_attributes(service, account), true)
+        .await?
+        .into_iter()
+        .next()
+        .ok_or(Error::NotFound("No password found".to_string()))
+}
+
+// Store the item, then delete the other items of the account. Their attributes can differ from the
+// stored ones, e.g. when the metadata changed, so they are not replaced by it. The existing items
+// are kept when storing fails.
+async fn replace_item(
+    client: &SecretService,
+    service: &str,
+    account: &str,
+    label: &str,
+    attributes: HashMap<&str, &str>,
+    secret: &[u8],
+    content_type: &str,
+) -> Result<()> {
+    let item = client
+        .create_item(label, attributes, secret, content_type)
+        .await?;
+    for stale in client
+        .search_items(build_attributes(service, account), true)
+        .await?
+    {
+        if stale != item {
+            client.delete_item(&stale).await?;
+        }
+    }
+
+    Ok(())
 }
 
 fn build_attributes<'a>(service: &'a str, account: &'a str) -> HashMap<&'a str, &'a str> {
     let mut attributes = HashMap::new();
     attributes.insert("service", service);
     attributes.insert("account", account);
+    attributes.insert("xdg:schema", SCHEMA);
 
     attributes
 }
 
 #[cfg(test)]
 mod tests {
+    use std::{cell::RefCell, ops::Deref};
+
+    use futures::executor::block_on;
+
     use super::*;
+    use crate::password::secret_service::mock::MockSecretService;
+
+    thread_local! {
+        // The session with the mock service of the test running on this thread
+        pub(super) static MOCK_CLIENT: RefCell<Option<Arc<SecretService>>> =
+            const { RefCell::new(None) };
+    }
+
+    /// Make the calls of this thread to a mock service until the returned guard is dropped, so
+    /// that the tests don't depend on a session bus and don't touch the user's keyring.
+    fn use_mock() -> MockGuard {
+        let mock = block_on(async {
+            let (mock, connection) = MockSecretService::start(true).await;
+            let client = SecretService::new(connection).await.unwrap();
+            MOCK_CLIENT.with(|mock_client| mock_client.replace(Some(Arc::new(client))));
+            mock
+        });
+        MockGuard { mock }
+    }
+
+    struct MockGuard {
+        mock: MockSecretService,
+    }
+
+    impl Deref for MockGuard {
+        type Target = MockSecretService;
+
+        fn deref(&self) -> &MockSecretService {
+            &self.mock
+        }
+    }
+
+    impl Drop for MockGuard {
+        fn drop(&mut self) {
+            MOCK_CLIENT.with(|client| client.take());
+        }
+    }
 
     #[test]
     fn test() {
-        scopeguard::defer!(delete_password("BitwardenTest", "BitwardenTest").unwrap_or({}););
-        set_password("BitwardenTest", "BitwardenTest", "Random").unwrap();
+        let _mock = use_mock();
+        block_on(set_password("BitwardenTest", "BitwardenTest", "Random")).unwrap();
         assert_eq!(
             "Random",
-            get_password("BitwardenTest", "BitwardenTest").unwrap()
+            &*block_on(get_password("BitwardenTest", "BitwardenTest")).unwrap()
         );
-        delete_password("BitwardenTest", "BitwardenTest").unwrap();
+        block_on(delete_password("BitwardenTest", "BitwardenTest")).unwrap();
 
         // Ensure password is deleted
-        match get_password("BitwardenTest", "BitwardenTest") {
+        match block_on(get_password("BitwardenTest", "BitwardenTest")) {
             Ok(_) => panic!("Got a result"),
             Err(e) => assert_eq!("No password found", e.to_string()),
         }
@@ -98,9 +294,137 @@ mod tests {
 
     #[test]
     fn test_error_no_password() {
-        match get_password("BitwardenTest", "BitwardenTest") {
+        let _mock = use_mock();
+        match block_on(get_password("BitwardenTest", "BitwardenTest")) {
             Ok(_) => panic!("Got a result"),
-            Err(e) => assert_eq!("No password found", e.to_string()),
+            Err(e) => {
+                assert!(matches!(e, Error::NotFound(_)));
+                assert_eq!("No password found", e.to_string());
+            }
+        }
+    }
+
+    #[test]
+    fn test_list_accounts_delete_all() {
+        let _mock = use_mock();
+        block_on(set_password("BitwardenTestList", "Account1", "Random")).unwrap();
+        block_on(set_password("BitwardenTestList", "Account2", "Random")).unwrap();
+        block_on(set_password("BitwardenTestOther", "Account3", "Random")).unwrap();
+
+        let mut accounts = block_on(list_accounts("BitwardenTestList")).unwrap();
+        accounts.sort();
+        assert_eq!(vec!["Account1", "Account2"], accounts);
+
+        block_on(delete_all("BitwardenTestList")).unwrap();
+        assert!(block_on(list_accounts("BitwardenTestList"))
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            vec!["Account3"],
+            block_on(list_accounts("BitwardenTestOther")).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_secret_with_metadata() {
+        let _mock = use_mock();
+        let metadata = SecretMetadata {
+            label: Some("Bitwarden test secret".to_string()),
+            attributes: HashMap::from([("version".to_string(), "2".to_string())]),
+        };
+        block_on(set_secret(
+            "BitwardenTestSecret",
+            "BitwardenTest",
+            &[0, 159, 146, 150],
+            &metadata,
+        ))
+        .unwrap();
+
+        let stored = block_on(get_secret("BitwardenTestSecret", "BitwardenTest")).unwrap();
+        assert_eq!(&[0, 159, 146, 150], &*stored.secret);
+        assert_eq!(metadata, stored.metadata);
+    }
+
+    #[test]
+    fn test_secret_metadata_update() {
+        let _mock = use_mock();
+        for version in ["1", "2"] {
+            let metadata = SecretMetadata {
+                label: None,
+                attributes: HashMap::from([("version".to_string(), version.to_string())]),
+            };
+            block_on(set_secret(
+                "BitwardenTestSecret",
+                "BitwardenTest",
+                &[0, 159, 146, 150],
+                &metadata,
+            ))
+            .unwrap();
         }
+
+        let items = stored_items("BitwardenTestSecret", "BitwardenTest");
+        assert_eq!(1, items.len());
+        assert_eq!(Some("2"), items[0].get("version").map(String::as_str));
+    }
+
+    #[test]
+    fn test_password_replaces_secret() {
+        let _mock = use_mock();
+        let metadata = SecretMetadata {
+            label: None,
+            attributes: HashMap::from([("version".to_string(), "2".to_string())]),
+        };
+        block_on(set_secret(
+            "BitwardenTest",
+            "BitwardenTest",
+            b"Secret",
+            &metadata,
+        ))
+        .unwrap();
+        block_on(set_password("BitwardenTest", "BitwardenTest", "Random")).unwrap();
+
+        assert_eq!(1, stored_items("BitwardenTest", "BitwardenTest").len());
+        assert_eq!(
+            "Random",
+            &*block_on(get_password("BitwardenTest", "BitwardenTest")).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_failed_store_keeps_secret() {
+        let mock = use_mock();
+        block_on(set_password("BitwardenTest", "BitwardenTest", "Random")).unwrap();
+
+        mock.reject_items(true);
+        assert!(block_on(set_password("BitwardenTest", "BitwardenTest", "Other")).is_err());
+        assert!(block_on(set_secret(
+            "BitwardenTest",
+            "BitwardenTest",
+            b"Other",
+            &SecretMetadata::default()
+        ))
+        .is_err());
+
+        mock.reject_items(false);
+        assert_eq!(
+            "Random",
+            &*block_on(get_password("BitwardenTest", "BitwardenTest")).unwrap()
+        );
+    }
+
+    // The attributes of the items stored for the account in the mock service
+    fn stored_items(service: &str, account: &str) -> Vec<HashMap<String, String>> {
+        let client = MOCK_CLIENT.with(|client| client.borrow().clone()).unwrap();
+        block_on(async {
+            let mut items = Vec::new();
+            for item in client
+                .search_items(build_attributes(service, account), true)
+                .await
+                .unwrap()
+            {
+                items.push(client.attributes(&item).await.unwrap());
+            }
+            items
+        })
     }
 }
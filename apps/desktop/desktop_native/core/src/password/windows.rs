@@ -1,20 +1,31 @@
-use anyhow::{anyhow, Result};
 use widestring::{U16CString, U16String};
 use windows::{
     core::{PCWSTR, PWSTR},
     Win32::{
-        Foundation::{ERROR_NOT_FOUND, FILETIME},
+        Foundation::{ERROR_ACCESS_DENIED, ERROR_CANCELLED, ERROR_NOT_FOUND, FILETIME},
         Security::Credentials::{
-            CredDeleteW, CredFree, CredReadW, CredWriteW, CREDENTIALW, CRED_FLAGS,
-            CRED_PERSIST_ENTERPRISE, CRED_TYPE_GENERIC,
+            CredDeleteW, CredEnumerateW, CredFree, CredReadW, CredWriteW, CREDENTIALW,
+            CREDENTIAL_ATTRIBUTEW, CRED_ENUMERATE_FLAGS, CRED_FLAGS, CRED_PERSIST_ENTERPRISE,
+            CRED_TYPE_GENERIC,
         },
     },
 };
 
+use crate::{
+    crypto::{SecretBytes, SecretString},
+    error::{Error, Result},
+};
+
+use super::{SecretMetadata, StoredSecret};
+
 const CRED_FLAGS_NONE: u32 = 0;
 
-pub fn get_password<'a>(service: &str, account: &str) -> Result<String> {
-    let target_name = U16CString::from_str(target_name(service, account))?;
+// https://learn.microsoft.com/en-us/windows/win32/api/wincred/ns-wincred-credentialw
+const CRED_MAX_ATTRIBUTES: usize = 64;
+const CRED_MAX_VALUE_SIZE: usize = 256;
+
+pub async fn get_password<'a>(service: &str, account: &str) -> Result<SecretString> {
+    let target_name = U16CString::from_str(target_name(service, account)).map_err(invalid_input)?;
 
     let mut credential: *mut CREDENTIALW = std::ptr::null_mut();
     let credential_ptr = &mut credential;
@@ -32,7 +43,7 @@ pub fn get_password<'a>(service: &str, account: &str) -> Result<String> {
         unsafe { CredFree(credential as *mut _) };
     });
 
-    result.map_err(|e| anyhow!(convert_error(e)))?;
+    result.map_err(convert_error)?;
 
     let password = unsafe {
         U16String::from_ptr(
@@ -42,12 +53,12 @@ pub fn get_password<'a>(service: &str, account: &str) -> Result<String> {
         .to_string_lossy()
     };
 
-    Ok(String::from(password))
+    Ok(SecretString::from(password))
 }
 
 // Remove this after sufficient releases
-pub fn get_password_keytar<'a>(service: &str, account: &str) -> Result<String> {
-    let target_name = U16CString::from_str(target_name(service, account))?;
+pub async fn get_password_keytar<'a>(service: &str, account: &str) -> Result<SecretString> {
+    let target_name = U16CString::from_str(target_name(service, account)).map_err(invalid_input)?;
 
     let mut credential: *mut CREDENTIALW = std::ptr::null_mut();
     let credential_ptr = &mut credential;
@@ -65,7 +76,7 @@ pub fn get_password_keytar<'a>(service: &str, account: &str) -> Result<String> {
         unsafe { CredFree(credential as *mut _) };
     });
 
-    result?;
+    result.map_err(convert_error)?;
 
     let password = unsafe {
         std::str::from_utf8_unchecked(std::slice::from_raw_parts(
@@ -74,18 +85,19 @@ pub fn get_password_keytar<'a>(service: &str, account: &str) -> Result<String> {
         ))
     };
 
-    Ok(String::from(password))
+    Ok(SecretString::from(String::from(password)))
 }
 
-pub fn set_password(service: &str, account: &str, password: &str) -> Result<()> {
-    let mut target_name = U16CString::from_str(target_name(service, account))?;
-    let mut user_name = U16CString::from_str(account)?;
+pub async fn set_password(service: &str, account: &str, password: &str) -> Result<()> {
+    let mut target_name =
+        U16CString::from_str(target_name(service, account)).map_err(invalid_input)?;
+    let mut user_name = U16CString::from_str(account).map_err(invalid_input)?;
     let last_written = FILETIME {
         dwLowDateTime: 0,
         dwHighDateTime: 0,
     };
 
-    let credential = U16CString::from_str(password)?;
+    let credential = U16CString::from_str(password).map_err(invalid_input)?;
     let credential_len = password.len() as u32 * 2;
 
     let credential = CREDENTIALW {
@@ -103,26 +115,196 @@ pub fn set_password(service: &str, account: &str, password: &str) -> Result<()>
         UserName: PWSTR(user_name.as_mut_ptr()),
     };
 
-    unsafe { CredWriteW(&credential, 0) }?;
+    unsafe { CredWriteW(&credential, 0) }.map_err(convert_error)?;
 
     Ok(())
 }
 
-pub fn delete_password(service: &str, account: &str) -> Result<()> {
-    let target_name = U16CString::from_str(target_name(service, account))?;
+pub async fn delete_password(service: &str, account: &str) -> Result<()> {
+    let target_name = U16CString::from_str(target_name(service, account)).map_err(invalid_input)?;
 
     unsafe {
         CredDeleteW(
             PCWSTR(target_name.as_ptr()),
             CRED_TYPE_GENERIC,
             CRED_FLAGS_NONE,
-        )?
+        )
+    }
+    .map_err(convert_error)?;
+
+    Ok(())
+}
+
+pub async fn get_secret(service: &str, account: &str) -> Result<StoredSecret> {
+    let target_name = U16CString::from_str(target_name(service, account)).map_err(invalid_input)?;
+
+    let mut credential: *mut CREDENTIALW = std::ptr::null_mut();
+    let credential_ptr = &mut credential;
+
+    let result = unsafe {
+        CredReadW(
+            PCWSTR(target_name.as_ptr()),
+            CRED_TYPE_GENERIC,
+            CRED_FLAGS_NONE,
+            credential_ptr,
+        )
+    };
+
+    scopeguard::defer!({
+        unsafe { CredFree(credential as *mut _) };
+    });
+
+    result.map_err(convert_error)?;
+
+    let stored = unsafe {
+        let credential = &*credential;
+        let secret = std::slice::from_raw_parts(
+            credential.CredentialBlob,
+            credential.CredentialBlobSize as usize,
+        );
+        let label = (!credential.Comment.is_null())
+            .then(|| U16CString::from_ptr_str(credential.Comment.0).to_string_lossy());
+        let attributes = (0..credential.AttributeCount as usize)
+            .map(|i| {
+                let attribute = &*credential.Attributes.add(i);
+                let value =
+                    std::slice::from_raw_parts(attribute.Value, attribute.ValueSize as usize);
+                (
+                    U16CString::from_ptr_str(attribute.Keyword.0).to_string_lossy(),
+                    String::from_utf8_lossy(value).into_owned(),
+                )
+            })
+            .collect();
+
+        StoredSecret {
+            secret: SecretBytes::from(secret.to_vec()),
+            metadata: SecretMetadata { label, attributes },
+        }
+    };
+
+    Ok(stored)
+}
+
+pub async fn set_secret(
+    service: &str,
+    account: &str,
+    secret: &[u8],
+    metadata: &SecretMetadata,
+) -> Result<()> {
+    if metadata.attributes.len() > CRED_MAX_ATTRIBUTES {
+        return Err(Error::InvalidInput(format!(
+            "At most {} attributes can be stored",
+            CRED_MAX_ATTRIBUTES
+        )));
+    }
+    if let Some(name) = metadata
+        .attributes
+        .iter()
+        .find_map(|(name, value)| (value.len() > CRED_MAX_VALUE_SIZE).then_some(name))
+    {
+        return Err(Error::InvalidInput(format!(
+            "The value of the attribute {} is longer than {} bytes",
+            name, CRED_MAX_VALUE_SIZE
+        )));
+    }
+
+    let mut target_name =
+        U16CString::from_str(target_name(service, account)).map_err(invalid_input)?;
+    let mut user_name = U16CString::from_str(account).map_err(invalid_input)?;
+    let mut comment = match &metadata.label {
+        Some(label) => Some(U16CString::from_str(label).map_err(invalid_input)?),
+        None => None,
+    };
+    let last_written = FILETIME {
+        dwLowDateTime: 0,
+        dwHighDateTime: 0,
+    };
+
+    let mut keywords = metadata
+        .attributes
+        .keys()
+        .map(U16CString::from_str)
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(invalid_input)?;
+    let mut attributes: Vec<CREDENTIAL_ATTRIBUTEW> = keywords
+        .iter_mut()
+        .zip(metadata.attributes.values())
+        .map(|(keyword, value)| CREDENTIAL_ATTRIBUTEW {
+            Keyword: PWSTR(keyword.as_mut_ptr()),
+            Flags: 0,
+            ValueSize: value.len() as u32,
+            Value: value.as_ptr() as *mut u8,
+        })
+        .collect();
+
+    let credential = CREDENTIALW {
+        Flags: CRED_FLAGS(CRED_FLAGS_NONE),
+        Type: CRED_TYPE_GENERIC,
+        TargetName: PWSTR(target_name.as_mut_ptr()),
+        Comment: comment
+            .as_mut()
+            .map_or(PWSTR::null(), |comment| PWSTR(comment.as_mut_ptr())),
+        LastWritten: last_written,
+        CredentialBlobSize: secret.len() as u32,
+        CredentialBlob: secret.as_ptr() as *mut u8,
+        Persist: CRED_PERSIST_ENTERPRISE,
+        AttributeCount: attributes.len() as u32,
+        Attributes: attributes.as_mut_ptr(),
+        TargetAlias: PWSTR::null(),
+        UserName: PWSTR(user_name.as_mut_ptr()),
     };
 
+    unsafe { CredWriteW(&credential, 0) }.map_err(convert_error)?;
+
     Ok(())
 }
 
-pub fn is_available() -> Result<bool> {
+pub async fn list_accounts(service: &str) -> Result<Vec<String>> {
+    let prefix = target_name(service, "");
+    let filter = U16CString::from_str(format!("{}*", prefix)).map_err(invalid_input)?;
+
+    let mut count: u32 = 0;
+    let mut credentials: *mut *mut CREDENTIALW = std::ptr::null_mut();
+
+    let result = unsafe {
+        CredEnumerateW(
+            PCWSTR(filter.as_ptr()),
+            CRED_ENUMERATE_FLAGS(CRED_FLAGS_NONE),
+            &mut count,
+            &mut credentials,
+        )
+    };
+
+    scopeguard::defer!({
+        unsafe { CredFree(credentials as *mut _) };
+    });
+
+    match result.map_err(convert_error) {
+        Ok(()) => {}
+        Err(Error::NotFound(_)) => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    }
+
+    let accounts = (0..count as usize)
+        .map(|i| unsafe {
+            let credential = *credentials.add(i);
+            U16CString::from_ptr_str((*credential).TargetName.0).to_string_lossy()
+        })
+        .filter_map(|target_name| target_name.strip_prefix(&prefix).map(String::from))
+        .collect();
+
+    Ok(accounts)
+}
+
+pub async fn delete_all(service: &str) -> Result<()> {
+    for account in list_accounts(service).await? {
+        delete_password(service, &account).await?;
+    }
+
+    Ok(())
+}
+
+pub async fn is_available() -> Result<bool> {
     Ok(true)
 }
 
@@ -130,30 +312,44 @@ fn target_name(service: &str, account: &str) -> String {
     format!("{}/{}", service, account)
 }
 
-// Convert the internal WIN32 errors to descriptive messages
-fn convert_error(e: windows::core::Error) -> String {
+// Convert the internal WIN32 errors to their matching error kinds with descriptive messages
+fn convert_error(e: windows::core::Error) -> Error {
     if e == ERROR_NOT_FOUND.into() {
-        return "Password not found.".to_string();
+        return Error::NotFound("Password not found.".to_string());
     }
-    e.to_string()
+    if e == ERROR_ACCESS_DENIED.into() {
+        return Error::AccessDenied(e.message());
+    }
+    if e == ERROR_CANCELLED.into() {
+        return Error::UserCancelled;
+    }
+    Error::Platform(e.to_string())
+}
+
+fn invalid_input(e: widestring::error::ContainsNul<u16>) -> Error {
+    Error::InvalidInput(e.to_string())
 }
 
 #[cfg(test)]
 mod tests {
+    use futures::executor::block_on;
+
     use super::*;
 
     #[test]
     fn test() {
-        scopeguard::defer!(delete_password("BitwardenTest", "BitwardenTest").unwrap_or({}););
-        set_password("BitwardenTest", "BitwardenTest", "Random").unwrap();
+        scopeguard::defer!({
+            let _ = block_on(delete_password("BitwardenTest", "BitwardenTest"));
+        });
+        block_on(set_password("BitwardenTest", "BitwardenTest", "Random")).unwrap();
         assert_eq!(
             "Random",
-            get_password("BitwardenTest", "BitwardenTest").unwrap()
+            &*block_on(get_password("BitwardenTest", "BitwardenTest")).unwrap()
         );
-        delete_password("BitwardenTest", "BitwardenTest").unwrap();
+        block_on(delete_password("BitwardenTest", "BitwardenTest")).unwrap();
 
         // Ensure password is deleted
-        match get_password("BitwardenTest", "BitwardenTest") {
+        match block_on(get_password("BitwardenTest", "BitwardenTest")) {
             Ok(_) => panic!("Got a result"),
             Err(e) => assert_eq!("Password not found.", e.to_string()),
         }
@@ -161,19 +357,64 @@ mod tests {
 
     #[test]
     fn test_get_password_keytar() {
-        scopeguard::defer!(delete_password("BitwardenTest", "BitwardenTest").unwrap_or({}););
+        scopeguard::defer!({
+            let _ = block_on(delete_password("BitwardenTest", "BitwardenTest"));
+        });
         keytar::set_password("BitwardenTest", "BitwardenTest", "HelloFromKeytar").unwrap();
         assert_eq!(
             "HelloFromKeytar",
-            get_password_keytar("BitwardenTest", "BitwardenTest").unwrap()
+            &*block_on(get_password_keytar("BitwardenTest", "BitwardenTest")).unwrap()
         );
     }
 
     #[test]
     fn test_error_no_password() {
-        match get_password("BitwardenTest", "BitwardenTest") {
+        match block_on(get_password("BitwardenTest", "BitwardenTest")) {
             Ok(_) => panic!("Got a result"),
-            Err(e) => assert_eq!("Password not found.", e.to_string()),
+            Err(e) => {
+                assert!(matches!(e, Error::NotFound(_)));
+                assert_eq!("Password not found.", e.to_string());
+            }
         }
     }
+
+    #[test]
+    fn test_list_accounts_delete_all() {
+        scopeguard::defer!({
+            let _ = block_on(delete_all("BitwardenTestList"));
+        });
+        block_on(set_password("BitwardenTestList", "Account1", "Random")).unwrap();
+        block_on(set_password("BitwardenTestList", "Account2", "Random")).unwrap();
+
+        let mut accounts = block_on(list_accounts("BitwardenTestList")).unwrap();
+        accounts.sort();
+        assert_eq!(vec!["Account1", "Account2"], accounts);
+
+        block_on(delete_all("BitwardenTestList")).unwrap();
+        assert!(block_on(list_accounts("BitwardenTestList"))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_secret_with_metadata() {
+        scopeguard::defer!({
+            let _ = block_on(delete_password("BitwardenTestSecret", "BitwardenTest"));
+        });
+        let metadata = SecretMetadata {
+            label: Some("Bitwarden test secret".to_string()),
+            attributes: std::collections::HashMap::from([("version".to_string(), "2".to_string())]),
+        };
+        block_on(set_secret(
+            "BitwardenTestSecret",
+            "BitwardenTest",
+            &[0, 159, 146, 150],
+            &metadata,
+        ))
+        .unwrap();
+
+        let stored = block_on(get_secret("BitwardenTestSecret", "BitwardenTest")).unwrap();
+        assert_eq!(&[0, 159, 146, 150], &*stored.secret);
+        assert_eq!(metadata, stored.metadata);
+    }
 }
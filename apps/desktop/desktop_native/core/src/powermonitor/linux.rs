@@ -1,6 +1,9 @@
 use std::borrow::Cow;
 
 use zbus::{export::futures_util::TryStreamExt, Connection, MatchRule};
+
+use crate::error::Result;
+
 struct ScreenLock {
     interface: Cow<'static, str>,
     path: Cow<'static, str>,
@@ -17,7 +20,7 @@ const SCREEN_LOCK_MONITORS: [ScreenLock; 2] = [
     },
 ];
 
-pub async fn on_lock(tx: tokio::sync::mpsc::Sender<()>) -> Result<(), Box<dyn std::error::Error>> {
+pub async fn on_lock(tx: tokio::sync::mpsc::Sender<()>) -> Result<()> {
     let connection = Connection::session().await?;
 
     let proxy = zbus::fdo::DBusProxy::new(&connection).await?;
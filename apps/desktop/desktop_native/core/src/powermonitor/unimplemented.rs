@@ -1,4 +1,6 @@
-pub async fn on_lock(_: tokio::sync::mpsc::Sender<()>) -> Result<(), Box<dyn std::error::Error>> {
+use crate::error::Result;
+
+pub async fn on_lock(_: tokio::sync::mpsc::Sender<()>) -> Result<()> {
     unimplemented!();
 }
 
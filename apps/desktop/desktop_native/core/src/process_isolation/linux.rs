@@ -1,8 +1,9 @@
-use anyhow::Result;
 #[cfg(target_env = "gnu")]
 use libc::c_uint;
 use libc::{self, c_int};
 
+use crate::error::{Error, Result};
+
 // RLIMIT_CORE is the maximum size of a core dump file. Setting both to 0 disables core dumps, on crashes
 // https://github.com/torvalds/linux/blob/1613e604df0cd359cf2a7fbd9be7a0bcfacfabd0/include/uapi/asm-generic/resource.h#L20
 #[cfg(target_env = "musl")]
@@ -22,10 +23,10 @@ pub fn disable_coredumps() -> Result<()> {
     };
     if unsafe { libc::setrlimit(RLIMIT_CORE, &rlimit) } != 0 {
         let e = std::io::Error::last_os_error();
-        return Err(anyhow::anyhow!(
+        return Err(Error::Platform(format!(
             "failed to disable core dumping, memory might be persisted to disk on crashes {}",
             e
-        ));
+        )));
     }
 
     Ok(())
@@ -38,7 +39,10 @@ pub fn is_core_dumping_disabled() -> Result<bool> {
     };
     if unsafe { libc::getrlimit(RLIMIT_CORE, &mut rlimit) } != 0 {
         let e = std::io::Error::last_os_error();
-        return Err(anyhow::anyhow!("failed to get core dump limit {}", e));
+        return Err(Error::Platform(format!(
+            "failed to get core dump limit {}",
+            e
+        )));
     }
 
     Ok(rlimit.rlim_cur == 0 && rlimit.rlim_max == 0)
@@ -47,10 +51,10 @@ pub fn is_core_dumping_disabled() -> Result<bool> {
 pub fn disable_memory_access() -> Result<()> {
     if unsafe { libc::prctl(PR_SET_DUMPABLE, 0) } != 0 {
         let e = std::io::Error::last_os_error();
-        return Err(anyhow::anyhow!(
+        return Err(Error::Platform(format!(
             "failed to disable memory dumping, memory is dumpable by other processes {}",
             e
-        ));
+        )));
     }
 
     Ok(())
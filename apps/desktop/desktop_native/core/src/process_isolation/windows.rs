@@ -1,13 +1,13 @@
-use anyhow::{bail, Result};
+use crate::error::{Error, Result};
 
 pub fn disable_coredumps() -> Result<()> {
-    bail!("Not implemented on Windows")
+    Err(Error::Unsupported("Not implemented on Windows".to_string()))
 }
 
 pub fn is_core_dumping_disabled() -> Result<bool> {
-    bail!("Not implemented on Windows")
+    Err(Error::Unsupported("Not implemented on Windows".to_string()))
 }
 
 pub fn disable_memory_access() -> Result<()> {
-    bail!("Not implemented on Windows")
+    Err(Error::Unsupported("Not implemented on Windows".to_string()))
 }
@@ -1,45 +1,102 @@
 use rand::SeedableRng;
 use rand_chacha::ChaCha8Rng;
-use ssh_key::{Algorithm, HashAlg, LineEnding};
+use ssh_key::{Algorithm, EcdsaCurve};
+
+use crate::error::{Error, Result};
 
 use super::importer::SshKey;
 
-pub async fn generate_keypair(key_algorithm: String) -> Result<SshKey, anyhow::Error> {
+pub async fn generate_keypair(key_algorithm: String, comment: String) -> Result<SshKey> {
     // sourced from cryptographically secure entropy source, with sources for all targets: https://docs.rs/getrandom
     // if it cannot be securely sourced, this will panic instead of leading to a weak key
     let mut rng: ChaCha8Rng = ChaCha8Rng::from_entropy();
 
-    let key = match key_algorithm.as_str() {
+    let mut key = match key_algorithm.as_str() {
         "ed25519" => ssh_key::PrivateKey::random(&mut rng, Algorithm::Ed25519),
+        "ecdsa256" | "ecdsa384" | "ecdsa521" => {
+            let curve = match key_algorithm.as_str() {
+                "ecdsa256" => EcdsaCurve::NistP256,
+                "ecdsa384" => EcdsaCurve::NistP384,
+                "ecdsa521" => EcdsaCurve::NistP521,
+                _ => return Err(Error::Unsupported("Unsupported ECDSA curve".to_string())),
+            };
+            ssh_key::PrivateKey::random(&mut rng, Algorithm::Ecdsa { curve })
+        }
         "rsa2048" | "rsa3072" | "rsa4096" => {
             let bits = match key_algorithm.as_str() {
                 "rsa2048" => 2048,
                 "rsa3072" => 3072,
                 "rsa4096" => 4096,
-                _ => return Err(anyhow::anyhow!("Unsupported RSA key size")),
+                _ => return Err(Error::Unsupported("Unsupported RSA key size".to_string())),
             };
             let rsa_keypair = ssh_key::private::RsaKeypair::random(&mut rng, bits)
-                .or_else(|e| Err(anyhow::anyhow!(e.to_string())))?;
+                .map_err(|e| Error::Platform(e.to_string()))?;
 
             let private_key = ssh_key::PrivateKey::new(
                 ssh_key::private::KeypairData::from(rsa_keypair),
                 "".to_string(),
             )
-            .or_else(|e| Err(anyhow::anyhow!(e.to_string())))?;
+            .map_err(|e| Error::Platform(e.to_string()))?;
             Ok(private_key)
         }
         _ => {
-            return Err(anyhow::anyhow!("Unsupported key algorithm"));
+            return Err(Error::Unsupported("Unsupported key algorithm".to_string()));
+        }
+    }
+    .map_err(|e| Error::Platform(e.to_string()))?;
+    key.set_comment(comment);
+
+    SshKey::try_from(&key).map_err(|e| Error::Platform(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ssh_agent::importer::{import_key, SshKeyImportStatus};
+
+    #[test]
+    fn generate_ecdsa_keypair() {
+        for (key_algorithm, prefix) in [
+            ("ecdsa256", "ecdsa-sha2-nistp256 "),
+            ("ecdsa384", "ecdsa-sha2-nistp384 "),
+            ("ecdsa521", "ecdsa-sha2-nistp521 "),
+        ] {
+            let key = futures::executor::block_on(generate_keypair(
+                key_algorithm.to_string(),
+                "".to_string(),
+            ))
+            .unwrap();
+            assert!(key.public_key.starts_with(prefix));
+
+            let result = import_key(key.private_key, "".to_string()).unwrap();
+            assert_eq!(result.status, SshKeyImportStatus::Success);
+            assert_eq!(result.ssh_key.unwrap().public_key, key.public_key);
         }
     }
-    .or_else(|e| Err(anyhow::anyhow!(e.to_string())))?;
-
-    let private_key_openssh = key
-        .to_openssh(LineEnding::LF)
-        .or_else(|e| Err(anyhow::anyhow!(e.to_string())))?;
-    Ok(SshKey {
-        private_key: private_key_openssh.to_string(),
-        public_key: key.public_key().to_string(),
-        key_fingerprint: key.fingerprint(HashAlg::Sha256).to_string(),
-    })
+
+    #[test]
+    fn generate_keypair_metadata() {
+        let key = futures::executor::block_on(generate_keypair(
+            "ed25519".to_string(),
+            "user@host".to_string(),
+        ))
+        .unwrap();
+        assert_eq!(key.algorithm, "ssh-ed25519");
+        assert_eq!(key.key_size, 256);
+        assert_eq!(key.comment, "user@host");
+        assert!(key.public_key.ends_with(" user@host"));
+        assert!(key.md5_fingerprint.starts_with("MD5:"));
+        assert!(key.randomart.starts_with("+--[ED25519 256]--+\n"));
+
+        let result = import_key(key.private_key, "".to_string()).unwrap();
+        assert_eq!(result.ssh_key.unwrap().comment, "user@host");
+    }
+
+    #[test]
+    fn generate_unsupported_keypair() {
+        assert!(matches!(
+            futures::executor::block_on(generate_keypair("dsa".to_string(), "".to_string())),
+            Err(Error::Unsupported(_))
+        ));
+    }
 }
@@ -5,13 +5,18 @@ use tokio_util::sync::CancellationToken;
 
 use bitwarden_russh::ssh_agent::{self, Key};
 
+use crate::error::{Error, Result};
+
 #[cfg_attr(target_os = "windows", path = "windows.rs")]
 #[cfg_attr(target_os = "macos", path = "unix.rs")]
 #[cfg_attr(target_os = "linux", path = "unix.rs")]
 mod platform_ssh_agent;
 
+pub mod exporter;
 pub mod generator;
 pub mod importer;
+mod ppk;
+mod randomart;
 
 #[derive(Clone)]
 pub struct BitwardenDesktopAgent {
@@ -62,13 +67,10 @@ impl BitwardenDesktopAgent {
             .clear();
     }
 
-    pub fn set_keys(
-        &mut self,
-        new_keys: Vec<(String, String, String)>,
-    ) -> Result<(), anyhow::Error> {
+    pub fn set_keys(&mut self, new_keys: Vec<(String, String, String)>) -> Result<()> {
         if !*self.is_running.blocking_lock() {
-            return Err(anyhow::anyhow!(
-                "[BitwardenDesktopAgent] Tried to set keys while agent is not running"
+            return Err(Error::InvalidState(
+                "[BitwardenDesktopAgent] Tried to set keys while agent is not running".to_string(),
             ));
         }
 
@@ -100,10 +102,10 @@ impl BitwardenDesktopAgent {
         Ok(())
     }
 
-    pub fn lock(&mut self) -> Result<(), anyhow::Error> {
+    pub fn lock(&mut self) -> Result<()> {
         if !*self.is_running.blocking_lock() {
-            return Err(anyhow::anyhow!(
-                "[BitwardenDesktopAgent] Tried to lock agent, but it is not running"
+            return Err(Error::InvalidState(
+                "[BitwardenDesktopAgent] Tried to lock agent, but it is not running".to_string(),
             ));
         }
 
@@ -135,15 +137,136 @@ impl BitwardenDesktopAgent {
     }
 }
 
-fn parse_key_safe(pem: &str) -> Result<ssh_key::private::PrivateKey, anyhow::Error> {
+fn parse_key_safe(pem: &str) -> Result<ssh_key::private::PrivateKey> {
     match ssh_key::private::PrivateKey::from_openssh(pem) {
         Ok(key) => match key.public_key().to_bytes() {
             Ok(_) => Ok(key),
-            Err(e) => Err(anyhow::Error::msg(format!(
+            Err(e) => Err(Error::InvalidInput(format!(
                 "Failed to parse public key: {}",
                 e
             ))),
         },
-        Err(e) => Err(anyhow::Error::msg(format!("Failed to parse key: {}", e))),
+        Err(e) => Err(Error::InvalidInput(format!("Failed to parse key: {}", e))),
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::{collections::HashMap, sync::RwLock};
+
+    use signature::Verifier;
+    use ssh_encoding::{Decode, Encode};
+    use ssh_key::{PrivateKey, Signature};
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{UnixListener, UnixStream},
+        sync::{broadcast, mpsc},
+    };
+    use tokio_stream::wrappers::UnixListenerStream;
+
+    use super::*;
+
+    const SSH_AGENTC_SIGN_REQUEST: u8 = 13;
+    const SSH_AGENT_SIGN_RESPONSE: u8 = 14;
+
+    fn running_agent() -> (
+        BitwardenDesktopAgent,
+        mpsc::Receiver<(u32, String)>,
+        broadcast::Sender<(u32, bool)>,
+    ) {
+        let (ui_request_tx, ui_request_rx) = mpsc::channel(32);
+        let (ui_response_tx, ui_response_rx) = broadcast::channel(32);
+        let agent = BitwardenDesktopAgent {
+            keystore: ssh_agent::KeyStore(Arc::new(RwLock::new(HashMap::new()))),
+            cancellation_token: CancellationToken::new(),
+            show_ui_request_tx: ui_request_tx,
+            get_ui_response_rx: Arc::new(Mutex::new(ui_response_rx)),
+            request_id: Arc::new(Mutex::new(0)),
+            is_running: Arc::new(Mutex::new(true)),
+        };
+        (agent, ui_request_rx, ui_response_tx)
+    }
+
+    /// Send a sign request for `public_key` to the agent, approving it in the UI, and return the
+    /// response.
+    async fn sign(
+        agent: &BitwardenDesktopAgent,
+        mut ui_requests: mpsc::Receiver<(u32, String)>,
+        ui_responses: broadcast::Sender<(u32, bool)>,
+        public_key: &[u8],
+        data: &[u8],
+    ) -> Vec<u8> {
+        tokio::spawn(async move {
+            while let Some((request_id, cipher_id)) = ui_requests.recv().await {
+                assert_eq!("cipher-id", cipher_id);
+                ui_responses.send((request_id, true)).unwrap();
+            }
+        });
+
+        let path = std::env::temp_dir().join(format!(
+            "bitwarden-ssh-agent-test-{}-{}.sock",
+            std::process::id(),
+            rand::random::<u32>()
+        ));
+        let listener = UnixListener::bind(&path).unwrap();
+        tokio::spawn(ssh_agent::serve(
+            UnixListenerStream::new(listener),
+            agent.clone(),
+            agent.keystore.clone(),
+            agent.cancellation_token.clone(),
+        ));
+        let mut stream = UnixStream::connect(&path).await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let mut request = vec![SSH_AGENTC_SIGN_REQUEST];
+        public_key.encode(&mut request).unwrap();
+        data.encode(&mut request).unwrap();
+        0u32.encode(&mut request).unwrap();
+        stream.write_u32(request.len() as u32).await.unwrap();
+        stream.write_all(&request).await.unwrap();
+
+        let mut response = vec![0; stream.read_u32().await.unwrap() as usize];
+        stream.read_exact(&mut response).await.unwrap();
+        agent.cancellation_token.cancel();
+        response
+    }
+
+    #[test]
+    fn sign_with_ecdsa_key() {
+        for key_algorithm in ["ecdsa256", "ecdsa384", "ecdsa521"] {
+            let key = futures::executor::block_on(generator::generate_keypair(
+                key_algorithm.to_string(),
+                "".to_string(),
+            ))
+            .unwrap();
+            let private_key = PrivateKey::from_openssh(&key.private_key).unwrap();
+            let public_key = private_key.public_key().to_bytes().unwrap();
+
+            let (mut agent, ui_requests, ui_responses) = running_agent();
+            agent
+                .set_keys(vec![(
+                    key.private_key,
+                    "Test key".to_string(),
+                    "cipher-id".to_string(),
+                )])
+                .unwrap();
+
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_io()
+                .build()
+                .unwrap();
+            let response = runtime.block_on(sign(
+                &agent,
+                ui_requests,
+                ui_responses,
+                &public_key,
+                b"data to sign",
+            ));
+
+            assert_eq!(SSH_AGENT_SIGN_RESPONSE, response[0]);
+            let signature = Vec::<u8>::decode(&mut &response[1..]).unwrap();
+            let signature = Signature::try_from(signature.as_slice()).unwrap();
+            Verifier::verify(private_key.public_key(), b"data to sign", &signature).unwrap();
+        }
     }
 }
@@ -0,0 +1,485 @@
+//! PuTTY private key files, versions 2 and 3.
+//!
+//! The format is described in the PuTTY documentation, appendix C.
+
+use aes::cipher::{block_padding::NoPadding, BlockDecryptMut, BlockEncryptMut, KeyInit, KeyIvInit};
+use argon2::{Argon2, Params, Version};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha1::{Digest, Sha1};
+use sha2::Sha256;
+use ssh_encoding::{Decode, Encode};
+use ssh_key::{
+    private::{
+        EcdsaKeypair, Ed25519Keypair, Ed25519PrivateKey, KeypairData, RsaKeypair, RsaPrivateKey,
+    },
+    public::RsaPublicKey,
+    Mpint, PrivateKey,
+};
+use zeroize::Zeroizing;
+
+use crate::error::{Error, Result};
+
+use super::importer::{decode_hex, SshKeyImportError};
+
+pub const PPK_HEADER_PREFIX: &str = "PuTTY-User-Key-File-";
+
+const MAC_KEY_PREFIX: &[u8] = b"putty-private-key-file-mac-key";
+
+// The defaults of PuTTYgen, apart from the passes which it calibrates to the speed of the machine
+const ARGON2_MEMORY: u32 = 8192;
+const ARGON2_PARALLELISM: u32 = 1;
+pub const DEFAULT_ARGON2_PASSES: u32 = 13;
+
+// The most a file may ask of the key derivation, far above what PuTTYgen writes, so that a crafted
+// file can't make the import take unbounded memory or time
+const MAX_ARGON2_MEMORY: u32 = 1024 * 1024;
+pub const MAX_ARGON2_PASSES: u32 = 100;
+const MAX_ARGON2_PARALLELISM: u32 = 16;
+
+const ED25519_PRIVATE_KEY_SIZE: usize = 32;
+
+/// Decode a PPK file, decrypting it with the password if it is encrypted.
+pub fn import_ppk_key(
+    encoded_key: &str,
+    password: &str,
+) -> std::result::Result<PrivateKey, SshKeyImportError> {
+    PpkFile::parse(encoded_key)?.decode(password)
+}
+
+/// Encode a key as a version 3 PPK file. Unless the passphrase is empty, the file is encrypted
+/// with a key derived from it with Argon2id, using the given number of passes.
+pub fn export_ppk_key(private_key: &PrivateKey, passphrase: &str, passes: u32) -> Result<String> {
+    let encoding_error = |e: ssh_key::Error| Error::Platform(e.to_string());
+
+    let mut file = PpkFile {
+        version: 3,
+        algorithm: private_key.algorithm().as_str().to_string(),
+        encryption: "none".to_string(),
+        comment: private_key.comment().to_string(),
+        public_blob: private_key
+            .public_key()
+            .to_bytes()
+            .map_err(encoding_error)?,
+        key_derivation: None,
+        private_blob: private_blob(private_key.key_data()).map_err(|e| match e {
+            ssh_key::Error::AlgorithmUnknown => Error::Unsupported(format!(
+                "PPK files can't hold {} keys",
+                private_key.algorithm()
+            )),
+            e => encoding_error(e),
+        })?,
+        mac: Vec::new(),
+    };
+
+    if passphrase.is_empty() {
+        // The MAC of unencrypted files is keyed with an empty password
+        file.mac = compute_mac(&[], &file.mac_data()?);
+        return Ok(file.encode());
+    }
+
+    let mut salt = vec![0u8; 16];
+    rand::thread_rng().fill_bytes(&mut salt);
+    let key_derivation = KeyDerivation {
+        algorithm: argon2::Algorithm::Argon2id,
+        params: Params::new(ARGON2_MEMORY, passes, ARGON2_PARALLELISM, None)
+            .map_err(|e| Error::InvalidInput(e.to_string()))?,
+        salt,
+    };
+    // The cipher key, the IV and the MAC key
+    let mut keys = Zeroizing::new([0u8; 80]);
+    key_derivation
+        .derive(passphrase.as_bytes(), &mut *keys)
+        .map_err(|e| Error::InvalidInput(e.to_string()))?;
+
+    // The private blob is padded to the cipher block size with arbitrary data
+    let padding = (16 - file.private_blob.len() % 16) % 16;
+    let mut padding_bytes = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut padding_bytes[..padding]);
+    file.private_blob
+        .extend_from_slice(&padding_bytes[..padding]);
+
+    file.encryption = "aes256-cbc".to_string();
+    file.key_derivation = Some(key_derivation);
+    file.mac = compute_mac(&keys[48..], &file.mac_data()?);
+    encrypt(&keys[..32], &keys[32..48], &mut file.private_blob);
+    Ok(file.encode())
+}
+
+struct PpkFile {
+    version: u8,
+    algorithm: String,
+    encryption: String,
+    comment: String,
+    public_blob: Vec<u8>,
+    /// Only present in encrypted version 3 files.
+    key_derivation: Option<KeyDerivation>,
+    private_blob: Zeroizing<Vec<u8>>,
+    mac: Vec<u8>,
+}
+
+struct KeyDerivation {
+    algorithm: argon2::Algorithm,
+    params: Params,
+    salt: Vec<u8>,
+}
+
+impl PpkFile {
+    fn parse(encoded_key: &str) -> std::result::Result<Self, SshKeyImportError> {
+        let mut lines = encoded_key.lines().map(str::trim_end);
+
+        let (header, algorithm) = lines
+            .next()
+            .and_then(|line| line.split_once(": "))
+            .ok_or(SshKeyImportError::ParsingError)?;
+        let version = match header.strip_prefix(PPK_HEADER_PREFIX) {
+            Some("2") => 2,
+            Some("3") => 3,
+            _ => return Err(SshKeyImportError::ParsingError),
+        };
+        let encryption = field(&mut lines, "Encryption")?.to_string();
+        let comment = field(&mut lines, "Comment")?.to_string();
+        let public_blob = blob(&mut lines, "Public-Lines")?;
+        let key_derivation = match (version, encryption.as_str()) {
+            (3, "aes256-cbc") => Some(KeyDerivation::parse(&mut lines)?),
+            _ => None,
+        };
+        let private_blob = Zeroizing::new(blob(&mut lines, "Private-Lines")?);
+        let mac =
+            decode_hex(field(&mut lines, "Private-MAC")?).ok_or(SshKeyImportError::ParsingError)?;
+
+        Ok(PpkFile {
+            version,
+            algorithm: algorithm.to_string(),
+            encryption,
+            comment,
+            public_blob,
+            key_derivation,
+            private_blob,
+            mac,
+        })
+    }
+
+    fn decode(mut self, password: &str) -> std::result::Result<PrivateKey, SshKeyImportError> {
+        let encrypted = match self.encryption.as_str() {
+            "none" => false,
+            "aes256-cbc" => true,
+            _ => return Err(SshKeyImportError::ParsingError),
+        };
+        if encrypted && password.is_empty() {
+            return Err(SshKeyImportError::PasswordRequired);
+        }
+        // The MAC of unencrypted files is keyed with an empty password
+        let password = match encrypted {
+            true => password.as_bytes(),
+            false => &[],
+        };
+
+        let mac_key = match (self.version, &self.key_derivation) {
+            (2, _) => {
+                if encrypted {
+                    let mut key = Zeroizing::new(Vec::with_capacity(40));
+                    for sequence in [0u32, 1] {
+                        key.extend_from_slice(
+                            &Sha1::new()
+                                .chain_update(sequence.to_be_bytes())
+                                .chain_update(password)
+                                .finalize(),
+                        );
+                    }
+                    decrypt(&key[..32], &[0; 16], &mut self.private_blob)?;
+                }
+                Zeroizing::new(
+                    Sha1::new()
+                        .chain_update(MAC_KEY_PREFIX)
+                        .chain_update(password)
+                        .finalize()
+                        .to_vec(),
+                )
+            }
+            (_, Some(key_derivation)) => {
+                // The cipher key, the IV and the MAC key
+                let mut keys = Zeroizing::new([0u8; 80]);
+                key_derivation
+                    .derive(password, &mut *keys)
+                    .map_err(|_| SshKeyImportError::ParsingError)?;
+                decrypt(&keys[..32], &keys[32..48], &mut self.private_blob)?;
+                Zeroizing::new(keys[48..].to_vec())
+            }
+            (_, None) if !encrypted => Zeroizing::new(Vec::new()),
+            (_, None) => return Err(SshKeyImportError::ParsingError),
+        };
+
+        let mac_data = self
+            .mac_data()
+            .map_err(|_| SshKeyImportError::ParsingError)?;
+        let valid = match self.version {
+            2 => verify_mac::<Hmac<Sha1>>(&mac_key, &mac_data, &self.mac),
+            _ => verify_mac::<Hmac<Sha256>>(&mac_key, &mac_data, &self.mac),
+        };
+        if !valid {
+            return Err(match encrypted {
+                true => SshKeyImportError::WrongPassword,
+                false => SshKeyImportError::ParsingError,
+            });
+        }
+
+        let keypair = keypair(&self.algorithm, &self.public_blob, &self.private_blob).map_err(
+            |e| match e {
+                ssh_key::Error::AlgorithmUnknown => SshKeyImportError::UnsupportedKeyType,
+                _ => SshKeyImportError::ParsingError,
+            },
+        )?;
+        PrivateKey::new(keypair, self.comment).map_err(|_| SshKeyImportError::ParsingError)
+    }
+
+    // The MAC covers the header fields and both blobs, the private one decrypted with its padding
+    fn mac_data(&self) -> Result<Zeroizing<Vec<u8>>> {
+        let mut data = Zeroizing::new(Vec::new());
+        for field in [
+            self.algorithm.as_bytes(),
+            self.encryption.as_bytes(),
+            self.comment.as_bytes(),
+            &self.public_blob,
+            &self.private_blob,
+        ] {
+            field
+                .encode(&mut *data)
+                .map_err(|e| Error::Platform(e.to_string()))?;
+        }
+        Ok(data)
+    }
+
+    fn encode(&self) -> String {
+        let mut lines = vec![
+            format!("{}{}: {}", PPK_HEADER_PREFIX, self.version, self.algorithm),
+            format!("Encryption: {}", self.encryption),
+            format!("Comment: {}", self.comment),
+        ];
+        encode_blob(&mut lines, "Public-Lines", &self.public_blob);
+        if let Some(key_derivation) = &self.key_derivation {
+            key_derivation.encode(&mut lines);
+        }
+        encode_blob(&mut lines, "Private-Lines", &self.private_blob);
+        lines.push(format!("Private-MAC: {}", encode_hex(&self.mac)));
+
+        let mut encoded = lines.join("\n");
+        encoded.push('\n');
+        encoded
+    }
+}
+
+impl KeyDerivation {
+    fn parse<'a>(
+        lines: &mut impl Iterator<Item = &'a str>,
+    ) -> std::result::Result<Self, SshKeyImportError> {
+        let algorithm = match field(lines, "Key-Derivation")? {
+            "Argon2d" => argon2::Algorithm::Argon2d,
+            "Argon2i" => argon2::Algorithm::Argon2i,
+            "Argon2id" => argon2::Algorithm::Argon2id,
+            _ => return Err(SshKeyImportError::ParsingError),
+        };
+        let mut number = |name| {
+            field(lines, name)?
+                .parse::<u32>()
+                .map_err(|_| SshKeyImportError::ParsingError)
+        };
+        let memory = number("Argon2-Memory")?;
+        let passes = number("Argon2-Passes")?;
+        let parallelism = number("Argon2-Parallelism")?;
+        if memory > MAX_ARGON2_MEMORY
+            || passes > MAX_ARGON2_PASSES
+            || parallelism > MAX_ARGON2_PARALLELISM
+        {
+            return Err(SshKeyImportError::ParsingError);
+        }
+        let salt =
+            decode_hex(field(lines, "Argon2-Salt")?).ok_or(SshKeyImportError::ParsingError)?;
+
+        Ok(KeyDerivation {
+            algorithm,
+            params: Params::new(memory, passes, parallelism, None)
+                .map_err(|_| SshKeyImportError::ParsingError)?,
+            salt,
+        })
+    }
+
+    fn derive(&self, password: &[u8], output: &mut [u8]) -> argon2::Result<()> {
+        Argon2::new(self.algorithm, Version::V0x13, self.params.clone())
+            .hash_password_into(password, &self.salt, output)
+    }
+
+    fn encode(&self, lines: &mut Vec<String>) {
+        let algorithm = match self.algorithm {
+            argon2::Algorithm::Argon2d => "Argon2d",
+            argon2::Algorithm::Argon2i => "Argon2i",
+            argon2::Algorithm::Argon2id => "Argon2id",
+        };
+        lines.push(format!("Key-Derivation: {}", algorithm));
+        lines.push(format!("Argon2-Memory: {}", self.params.m_cost()));
+        lines.push(format!("Argon2-Passes: {}", self.params.t_cost()));
+        lines.push(format!("Argon2-Parallelism: {}", self.params.p_cost()));
+        lines.push(format!("Argon2-Salt: {}", encode_hex(&self.salt)));
+    }
+}
+
+fn field<'a>(
+    lines: &mut impl Iterator<Item = &'a str>,
+    name: &str,
+) -> std::result::Result<&'a str, SshKeyImportError> {
+    // The lines are trimmed, an empty value also loses the space after the colon
+    lines
+        .next()
+        .and_then(|line| line.strip_prefix(name)?.strip_prefix(':'))
+        .map(|value| value.strip_prefix(' ').unwrap_or(value))
+        .ok_or(SshKeyImportError::ParsingError)
+}
+
+// A base64 blob, preceded by its number of lines
+fn blob<'a>(
+    lines: &mut impl Iterator<Item = &'a str>,
+    name: &str,
+) -> std::result::Result<Vec<u8>, SshKeyImportError> {
+    let count: usize = field(lines, name)?
+        .parse()
+        .map_err(|_| SshKeyImportError::ParsingError)?;
+    let encoded = Zeroizing::new(lines.take(count).collect::<String>());
+    STANDARD
+        .decode(&*encoded)
+        .map_err(|_| SshKeyImportError::ParsingError)
+}
+
+// Base64 in lines of 64 characters, preceded by their number
+fn encode_blob(lines: &mut Vec<String>, name: &str, blob: &[u8]) {
+    let encoded = Zeroizing::new(STANDARD.encode(blob));
+    let chunks = encoded.as_bytes().chunks(64);
+    lines.push(format!("{}: {}", name, chunks.len()));
+    lines.extend(chunks.map(|chunk| String::from_utf8_lossy(chunk).into_owned()));
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn decrypt(key: &[u8], iv: &[u8], data: &mut [u8]) -> std::result::Result<(), SshKeyImportError> {
+    cbc::Decryptor::<aes::Aes256>::new_from_slices(key, iv)
+        .map_err(|_| SshKeyImportError::ParsingError)?
+        .decrypt_padded_mut::<NoPadding>(data)
+        .map_err(|_| SshKeyImportError::ParsingError)?;
+    Ok(())
+}
+
+// The data is padded to the block size already
+fn encrypt(key: &[u8], iv: &[u8], data: &mut [u8]) {
+    let len = data.len();
+    cbc::Encryptor::<aes::Aes256>::new_from_slices(key, iv)
+        .expect("The key and IV have the sizes of AES-256")
+        .encrypt_padded_mut::<NoPadding>(data, len)
+        .expect("The data is a multiple of the block size");
+}
+
+fn compute_mac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    <Hmac<Sha256> as Mac>::new_from_slice(key)
+        .expect("HMAC accepts keys of any length")
+        .chain_update(data)
+        .finalize()
+        .into_bytes()
+        .to_vec()
+}
+
+fn verify_mac<M: Mac + KeyInit>(key: &[u8], data: &[u8], mac: &[u8]) -> bool {
+    <M as Mac>::new_from_slice(key)
+        .map(|m| m.chain_update(data).verify_slice(mac).is_ok())
+        .unwrap_or(false)
+}
+
+// Combine the public and private blobs, which hold the fields of the key after the algorithm
+fn keypair(
+    algorithm: &str,
+    mut public_blob: &[u8],
+    mut private_blob: &[u8],
+) -> ssh_key::Result<KeypairData> {
+    if String::decode(&mut public_blob)? != algorithm {
+        return Err(ssh_key::Error::FormatEncoding);
+    }
+
+    match algorithm {
+        "ssh-rsa" => {
+            let e = Mpint::decode(&mut public_blob)?;
+            let n = Mpint::decode(&mut public_blob)?;
+            let d = Mpint::decode(&mut private_blob)?;
+            let p = Mpint::decode(&mut private_blob)?;
+            let q = Mpint::decode(&mut private_blob)?;
+            let iqmp = Mpint::decode(&mut private_blob)?;
+            Ok(RsaKeypair {
+                public: RsaPublicKey { e, n },
+                private: RsaPrivateKey { d, iqmp, p, q },
+            }
+            .into())
+        }
+        "ssh-ed25519" => {
+            let public_key = Vec::<u8>::decode(&mut public_blob)?;
+            // PuTTY writes the scalar little-endian without its trailing zero bytes
+            let scalar = Zeroizing::new(Vec::<u8>::decode(&mut private_blob)?);
+            if scalar.len() > ED25519_PRIVATE_KEY_SIZE {
+                return Err(ssh_key::Error::FormatEncoding);
+            }
+            let mut private_key = Zeroizing::new([0u8; ED25519_PRIVATE_KEY_SIZE]);
+            private_key[..scalar.len()].copy_from_slice(&scalar);
+            let keypair = Ed25519Keypair::from(Ed25519PrivateKey::from_bytes(&private_key));
+            if keypair.public.0[..] != public_key[..] {
+                return Err(ssh_key::Error::Crypto);
+            }
+            Ok(keypair.into())
+        }
+        "ecdsa-sha2-nistp256" | "ecdsa-sha2-nistp384" | "ecdsa-sha2-nistp521" => {
+            let field_size = match algorithm {
+                "ecdsa-sha2-nistp256" => 32,
+                "ecdsa-sha2-nistp384" => 48,
+                _ => 66,
+            };
+            // The scalar is an mpint without its leading zero bytes, OpenSSH encodes it with the
+            // size of the field
+            let scalar = Mpint::decode(&mut private_blob)?;
+            let scalar = scalar
+                .as_positive_bytes()
+                .ok_or(ssh_key::Error::FormatEncoding)?;
+            if scalar.len() > field_size {
+                return Err(ssh_key::Error::FormatEncoding);
+            }
+            let mut private_key = Zeroizing::new(vec![0u8; field_size]);
+            private_key[field_size - scalar.len()..].copy_from_slice(scalar);
+
+            // The curve and the public point followed by the private scalar is the OpenSSH
+            // encoding of the keypair
+            let mut encoded = Zeroizing::new(public_blob.to_vec());
+            private_key.as_slice().encode(&mut *encoded)?;
+            Ok(EcdsaKeypair::decode(&mut &encoded[..])?.into())
+        }
+        _ => Err(ssh_key::Error::AlgorithmUnknown),
+    }
+}
+
+// The fields of the private key, the inverse of `keypair`
+fn private_blob(key_data: &KeypairData) -> ssh_key::Result<Zeroizing<Vec<u8>>> {
+    let mut blob = Zeroizing::new(Vec::new());
+    match key_data {
+        KeypairData::Rsa(keypair) => {
+            for mpint in [
+                &keypair.private.d,
+                &keypair.private.p,
+                &keypair.private.q,
+                &keypair.private.iqmp,
+            ] {
+                mpint.encode(&mut *blob)?;
+            }
+        }
+        KeypairData::Ed25519(keypair) => keypair.private.as_ref().as_slice().encode(&mut *blob)?,
+        KeypairData::Ecdsa(keypair) => {
+            Mpint::from_positive_bytes(keypair.private_key_bytes())?.encode(&mut *blob)?
+        }
+        _ => return Err(ssh_key::Error::AlgorithmUnknown),
+    }
+    Ok(blob)
+}
@@ -0,0 +1,103 @@
+//! The randomart of a fingerprint, as printed by `ssh-keygen -lv`.
+//!
+//! The digest moves a bishop around a 17 by 9 field, each byte four diagonal steps, and every
+//! square is drawn with a character as dense as the number of times the bishop visited it.
+
+const WIDTH: usize = 17;
+const HEIGHT: usize = 9;
+// The characters for 0 to 14 visits, then the start and the end of the walk
+const SYMBOLS: &[u8] = b" .o+=*BOX@%&#/^SE";
+const MAX_VISITS: u8 = SYMBOLS.len() as u8 - 3;
+const START: u8 = SYMBOLS.len() as u8 - 2;
+const END: u8 = SYMBOLS.len() as u8 - 1;
+
+/// Draw the digest, with the key type and size in the top border and the name of the hash in the
+/// bottom one.
+pub fn randomart(key_type: &str, key_size: u32, hash_name: &str, digest: &[u8]) -> String {
+    let mut field = [[0u8; WIDTH]; HEIGHT];
+    let (mut x, mut y) = (WIDTH / 2, HEIGHT / 2);
+
+    for byte in digest {
+        let mut input = *byte;
+        for _ in 0..4 {
+            x = match input & 1 {
+                0 => x.saturating_sub(1),
+                _ => (x + 1).min(WIDTH - 1),
+            };
+            y = match input & 2 {
+                0 => y.saturating_sub(1),
+                _ => (y + 1).min(HEIGHT - 1),
+            };
+            if field[y][x] < MAX_VISITS {
+                field[y][x] += 1;
+            }
+            input >>= 2;
+        }
+    }
+    field[HEIGHT / 2][WIDTH / 2] = START;
+    field[y][x] = END;
+
+    let mut title = format!("{} {}", key_type, key_size);
+    if title.len() + 2 > WIDTH {
+        title = key_type.to_string();
+    }
+
+    let mut lines = vec![border(&title)];
+    lines.extend(field.iter().map(|row| {
+        let row: String = row
+            .iter()
+            .map(|&visits| SYMBOLS[visits as usize] as char)
+            .collect();
+        format!("|{}|", row)
+    }));
+    lines.push(border(hash_name));
+    lines.join("\n")
+}
+
+// The label in brackets, centered in a line of dashes
+fn border(label: &str) -> String {
+    let label: String = format!("[{}]", label).chars().take(WIDTH).collect();
+    let before = (WIDTH - label.len()) / 2;
+    let after = WIDTH - before - label.len();
+    format!("+{}{}{}+", "-".repeat(before), label, "-".repeat(after))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use base64::{engine::general_purpose::STANDARD_NO_PAD, Engine};
+
+    #[test]
+    fn randomart_matches_ssh_keygen() {
+        let digest = STANDARD_NO_PAD
+            .decode("oaEiIEZe8SyB9Dh+eHD/SRkUj8enzP39H/sctgzbDb8")
+            .unwrap();
+        let expected = [
+            "+--[ED25519 256]--+",
+            "|.o.+.  o.        |",
+            "|o.+ + . +        |",
+            "|o* + o.o.+ .     |",
+            "|= = o. o*.+      |",
+            "|.o.o...oS+ .     |",
+            "| .o.  o .   . .  |",
+            "|       o     o.= |",
+            "|              *+B|",
+            "|             . E@|",
+            "+----[SHA256]-----+",
+        ];
+        assert_eq!(
+            randomart("ED25519", 256, "SHA256", &digest),
+            expected.join("\n")
+        );
+    }
+
+    #[test]
+    fn randomart_long_title() {
+        let art = randomart("ED25519-SK", 256, "SHA256", &[0; 32]);
+        assert!(art.starts_with("+[ED25519-SK 256]-+\n"));
+        let art = randomart("ECDSA-SHA2-NISTP256-SK", 256, "SHA256", &[0; 32]);
+        assert!(art.starts_with("+[ECDSA-SHA2-NISTP+\n"));
+        assert!(art.lines().all(|line| line.len() == WIDTH + 2));
+    }
+}
@@ -8,13 +8,15 @@ use std::{
 use tokio::sync::Mutex;
 use tokio_util::sync::CancellationToken;
 
+use crate::error::Result;
+
 use super::BitwardenDesktopAgent;
 
 impl BitwardenDesktopAgent {
     pub async fn start_server(
         auth_request_tx: tokio::sync::mpsc::Sender<(u32, String)>,
         auth_response_rx: Arc<Mutex<tokio::sync::broadcast::Receiver<(u32, bool)>>>,
-    ) -> Result<Self, anyhow::Error> {
+    ) -> Result<Self> {
         let agent_state = BitwardenDesktopAgent {
             keystore: ssh_agent::KeyStore(Arc::new(RwLock::new(HashMap::new()))),
             show_ui_request_tx: auth_request_tx,
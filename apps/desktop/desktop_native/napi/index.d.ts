@@ -12,13 +12,66 @@ export declare namespace passwords {
   export function setPassword(service: string, account: string, password: string): Promise<void>
   /** Delete the stored password from the keychain. */
   export function deletePassword(service: string, account: string): Promise<void>
+  /** Fetch the stored secret and its metadata from the keychain. */
+  export function getSecret(service: string, account: string): Promise<StoredSecret>
+  /**
+   * Save the binary secret with its metadata to the keychain. Adds an entry if none exists
+   * otherwise replaces the existing entry.
+   */
+  export function setSecret(service: string, account: string, secret: Buffer, metadata?: SecretMetadata | undefined | null): Promise<void>
+  export interface SecretMetadata {
+    /** The label shown by keyring managers, `service/account` if unset. */
+    label?: string
+    attributes?: Record<string, string>
+  }
+  export interface StoredSecret {
+    secret: Buffer
+    label?: string
+    attributes: Record<string, string>
+  }
+  /** List the accounts with a password stored for the service. */
+  export function listAccounts(service: string): Promise<Array<string>>
+  /** Delete the stored passwords of all accounts of the service. */
+  export function deleteAll(service: string): Promise<void>
   export function isAvailable(): Promise<boolean>
+  export const enum PasswordBackend {
+    /** The secure storage of the OS. */
+    Os = 0,
+    /** An encrypted file in the user's config directory. */
+    EncryptedFile = 1
+  }
+  /**
+   * Select where passwords are stored. The encrypted file is encrypted with a key derived from
+   * `secret`, or on Linux with a key stored in the kernel keyring if no secret is given.
+   */
+  export function setBackend(backend: PasswordBackend, secret?: string | undefined | null): Promise<void>
+  /** Fetch a password stored with `setSessionPassword`. */
+  export function getSessionPassword(service: string, account: string): Promise<string>
+  /**
+   * Save a short-lived password, e.g. the biometric unlock key, to the session keyring of the
+   * Linux kernel. It works without a D-Bus session and is cleared on logout. Fails on the
+   * other platforms.
+   */
+  export function setSessionPassword(service: string, account: string, password: string): Promise<void>
+  /** Delete a password stored with `setSessionPassword`. */
+  export function deleteSessionPassword(service: string, account: string): Promise<void>
+  /** Checks if the session keyring can be used, false on the platforms other than Linux. */
+  export function isSessionKeyringAvailable(): Promise<boolean>
 }
 export declare namespace biometrics {
   export function prompt(hwnd: Buffer, message: string): Promise<boolean>
   export function available(): Promise<boolean>
+  /**
+   * Encrypts and stores the secret, returning the stored value. `iv_b64` must be the `ivB64` of
+   * the `OsDerivedKey` the os key part of the key material was derived from.
+   */
   export function setBiometricSecret(service: string, account: string, secret: string, keyMaterial: KeyMaterial | undefined | null, ivB64: string): Promise<string>
   export function getBiometricSecret(service: string, account: string, keyMaterial?: KeyMaterial | undefined | null): Promise<string>
+  /**
+   * Reads the iv of a value returned by `setBiometricSecret`, to pass to `deriveKeyMaterial`
+   * before getting the secret. Returns null if the value is not an encrypted secret.
+   */
+  export function getStoredIv(encryptedSecret: string): string | null
   /**
    * Derives key material from biometric data. Returns a string encoded with a
    * base64 encoded key and the base64 encoded challenge used to create it
@@ -41,6 +94,58 @@ export declare namespace biometrics {
 export declare namespace clipboards {
   export function read(): Promise<string>
   export function write(text: string, password: boolean): Promise<void>
+  export const enum ClipboardTarget {
+    /** The regular clipboard, used by copy and paste. */
+    Clipboard = 0,
+    /** The PRIMARY selection, pasted with the middle mouse button. Only available on Linux. */
+    Primary = 1
+  }
+  export const enum ClipboardFormat {
+    Text = 0,
+    Html = 1,
+    Image = 2
+  }
+  /** The content of a clipboard, exactly one of `text`, `html` and `image` is set. */
+  export interface ClipboardContent {
+    text?: string
+    html?: string
+    /** The plain text offered with `html` to the applications which don't paste HTML. */
+    altText?: string
+    image?: ClipboardImage
+  }
+  /** An image with its pixels in RGBA order, 4 bytes per pixel. */
+  export interface ClipboardImage {
+    width: number
+    height: number
+    bytes: Buffer
+  }
+  /** Read the content of the target in the format. */
+  export function readContent(target: ClipboardTarget, format: ClipboardFormat): Promise<ClipboardContent>
+  /**
+   * Replace the content of the target. Passwords are excluded from the clipboard history
+   * where the platform supports it.
+   */
+  export function writeContent(target: ClipboardTarget, content: ClipboardContent, password: boolean): Promise<void>
+  /** Clear the target, the regular clipboard if unset. */
+  export function clear(target?: ClipboardTarget | undefined | null): Promise<void>
+  /**
+   * Whether the clipboard still holds the text written last, i.e. no other application
+   * replaced it. Only supported on Linux, fails with `CLIPBOARD_UNSUPPORTED` on Windows and
+   * macOS.
+   */
+  export function isOwner(): Promise<boolean>
+  /**
+   * Write the text and clear the clipboard after `timeout_ms` if it still holds the text.
+   * Replaces the clear scheduled by the previous call.
+   */
+  export function writeWithTimeout(text: string, password: boolean, timeoutMs: number): Promise<void>
+  /** Cancel the clear scheduled by `write_with_timeout`, if any. */
+  export function cancelClear(): void
+  /**
+   * Run the clear scheduled by `write_with_timeout` now, if any. The clipboard is only cleared
+   * if it still holds the text.
+   */
+  export function clearPending(): void
 }
 export declare namespace sshagent {
   export interface PrivateKey {
@@ -51,7 +156,17 @@ export declare namespace sshagent {
   export interface SshKey {
     privateKey: string
     publicKey: string
+    /** The SSH name of the algorithm, e.g. `ssh-ed25519` */
+    algorithm: string
+    /** The size of the key in bits */
+    keySize: number
+    comment: string
+    /** `SHA256:<base64>` */
     keyFingerprint: string
+    /** `MD5:<hex>` */
+    md5Fingerprint: string
+    /** The randomart of the SHA-256 fingerprint, as shown by `ssh-keygen -lv` */
+    randomart: string
   }
   export const enum SshKeyImportStatus {
     /** ssh key was parsed correctly and will be returned in the result */
@@ -75,7 +190,32 @@ export declare namespace sshagent {
   export function setKeys(agentState: SshAgentState, newKeys: Array<PrivateKey>): void
   export function lock(agentState: SshAgentState): void
   export function importKey(encodedKey: string, password: string): SshKeyImportResult
-  export function generateKeypair(keyAlgorithm: string): Promise<SshKey>
+  export function generateKeypair(keyAlgorithm: string, comment?: string | undefined | null): Promise<SshKey>
+  export const enum SshPrivateKeyFormat {
+    /** OpenSSH, encrypted with bcrypt-pbkdf and AES-256-CTR */
+    OpenSsh = 0,
+    /** PKCS#8, encrypted with PBKDF2-HMAC-SHA256 and AES-256-CBC */
+    Pkcs8 = 1,
+    /** PKCS#1, only for RSA keys and never encrypted */
+    Pkcs1 = 2,
+    /** PuTTY version 3, encrypted with Argon2id and AES-256-CBC */
+    Ppk = 3
+  }
+  export const enum SshPublicKeyFormat {
+    /** `<algorithm> <base64> <comment>`, as in `authorized_keys` */
+    OpenSsh = 0,
+    /** `---- BEGIN SSH2 PUBLIC KEY ----` */
+    Rfc4716 = 1
+  }
+  /**
+   * Export the private key of an `SshKey`, encrypted with the passphrase unless it is empty.
+   * The rounds set the work factor of the key derivation: the bcrypt-pbkdf rounds for OpenSSH,
+   * the PBKDF2 iterations for PKCS#8 and the Argon2 passes for PPK, at most 1000, 10000000 and
+   * 100 respectively.
+   */
+  export function exportPrivateKey(privateKey: string, format: SshPrivateKeyFormat, passphrase: string, rounds?: number | undefined | null): Promise<string>
+  /** Export the public key of an `SshKey`. */
+  export function exportPublicKey(publicKey: string, format: SshPublicKeyFormat): Promise<string>
   export class SshAgentState {   }
 }
 export declare namespace processisolations {
@@ -0,0 +1,255 @@
+use std::{fmt::Display, future::Future};
+
+use desktop_core::error::Error;
+use napi::{bindgen_prelude::ToNapiValue, Env, JsObject};
+
+// The error codes with their names in JavaScript, and the list of all of them
+macro_rules! error_codes {
+    ($($code:ident => $name:literal,)*) => {
+        /// Stable, machine readable codes for the errors thrown to JavaScript.
+        ///
+        /// These are part of the public API of the module, existing codes must not be renamed.
+        /// Keep in sync with `NATIVE_ERROR_CODES` in `src/platform/utils/native-error.ts`, which
+        /// is checked by the tests.
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub enum ErrorCode {
+            $($code,)*
+        }
+
+        impl ErrorCode {
+            #[cfg(test)]
+            pub const ALL: &'static [ErrorCode] = &[$(ErrorCode::$code,)*];
+
+            pub fn as_str(&self) -> &'static str {
+                match self {
+                    $(ErrorCode::$code => $name,)*
+                }
+            }
+        }
+    };
+}
+
+error_codes! {
+    InvalidInput => "INVALID_INPUT",
+    UserCancelled => "USER_CANCELLED",
+
+    PasswordNotFound => "PASSWORD_NOT_FOUND",
+    PasswordAccessDenied => "PASSWORD_ACCESS_DENIED",
+    PasswordStoreUnavailable => "PASSWORD_STORE_UNAVAILABLE",
+    PasswordStoreFailed => "PASSWORD_STORE_FAILED",
+
+    BiometricCancelled => "BIOMETRIC_CANCELLED",
+    BiometricAccessDenied => "BIOMETRIC_ACCESS_DENIED",
+    BiometricUnavailable => "BIOMETRIC_UNAVAILABLE",
+    BiometricUnsupported => "BIOMETRIC_UNSUPPORTED",
+    BiometricKeyInvalid => "BIOMETRIC_KEY_INVALID",
+    BiometricFailed => "BIOMETRIC_FAILED",
+
+    ClipboardEmpty => "CLIPBOARD_EMPTY",
+    ClipboardUnavailable => "CLIPBOARD_UNAVAILABLE",
+    ClipboardUnsupported => "CLIPBOARD_UNSUPPORTED",
+    ClipboardFailed => "CLIPBOARD_FAILED",
+
+    SshAgentNotRunning => "SSH_AGENT_NOT_RUNNING",
+    SshAgentStartFailed => "SSH_AGENT_START_FAILED",
+    SshAgentFailed => "SSH_AGENT_FAILED",
+    SshKeyUnsupported => "SSH_KEY_UNSUPPORTED",
+    SshKeyInvalid => "SSH_KEY_INVALID",
+    SshKeyGenerationFailed => "SSH_KEY_GENERATION_FAILED",
+    SshKeyExportFailed => "SSH_KEY_EXPORT_FAILED",
+
+    ProcessIsolationUnsupported => "PROCESS_ISOLATION_UNSUPPORTED",
+    ProcessIsolationFailed => "PROCESS_ISOLATION_FAILED",
+
+    PowerMonitorUnavailable => "POWER_MONITOR_UNAVAILABLE",
+
+    IpcBindFailed => "IPC_BIND_FAILED",
+    IpcSendFailed => "IPC_SEND_FAILED",
+
+    RegistryFailed => "REGISTRY_FAILED",
+}
+
+/// An error thrown to JavaScript, as an `Error` with the message, the [`ErrorCode`] as its `code`
+/// and, if any, the details of what the error occurred for as its `details` object.
+///
+/// napi only allows a fixed set of statuses as the `code` of the errors it creates, so the
+/// `Error` is created on the JavaScript thread instead, see [`NativeError::into_napi`] and
+/// [`spawn`].
+#[derive(Debug)]
+pub struct NativeError {
+    code: ErrorCode,
+    message: String,
+    details: Vec<(&'static str, String)>,
+}
+
+impl NativeError {
+    pub fn new(code: ErrorCode, message: impl Display) -> Self {
+        NativeError {
+            code,
+            message: message.to_string(),
+            details: Vec::new(),
+        }
+    }
+
+    pub fn with_detail(mut self, name: &'static str, value: impl Into<String>) -> Self {
+        self.details.push((name, value.into()));
+        self
+    }
+
+    /// The error to return from a synchronous function, which takes the [`Env`] for it.
+    pub fn into_napi(self, env: &Env) -> napi::Error {
+        match self.to_js(env) {
+            Ok(error) => napi::Error::from(error.into_unknown()),
+            // Without its code and details
+            Err(_) => napi::Error::from_reason(self.message),
+        }
+    }
+
+    fn to_js(&self, env: &Env) -> napi::Result<JsObject> {
+        let mut error = env.create_error(napi::Error::from_reason(self.message.as_str()))?;
+        error.set_named_property("code", self.code.as_str())?;
+        if !self.details.is_empty() {
+            let mut details = env.create_object()?;
+            for (name, value) in &self.details {
+                details.set_named_property(name, value.as_str())?;
+            }
+            error.set_named_property("details", details)?;
+        }
+        Ok(error)
+    }
+}
+
+/// Run the future on the async runtime, returning a promise which is rejected with the
+/// [`NativeError`] the future failed with.
+pub fn spawn<T, F>(env: Env, future: F) -> napi::Result<JsObject>
+where
+    T: ToNapiValue + Send + 'static,
+    F: Future<Output = Result<T, NativeError>> + Send + 'static,
+{
+    env.execute_tokio_future(async move { Ok(future.await) }, |env, result| {
+        result.map_err(|e| e.into_napi(env))
+    })
+}
+
+fn password_code(e: &Error) -> ErrorCode {
+    match e {
+        Error::NotFound(_) => ErrorCode::PasswordNotFound,
+        Error::AccessDenied(_) => ErrorCode::PasswordAccessDenied,
+        Error::ServiceUnavailable(_) => ErrorCode::PasswordStoreUnavailable,
+        Error::UserCancelled => ErrorCode::UserCancelled,
+        Error::InvalidInput(_) => ErrorCode::InvalidInput,
+        _ => ErrorCode::PasswordStoreFailed,
+    }
+}
+
+pub fn password_error(e: Error) -> NativeError {
+    NativeError::new(password_code(&e), e)
+}
+
+/// Like [`password_error`], with the entry the error occurred for as the details.
+pub fn password_entry_error(e: Error, service: &str, account: &str) -> NativeError {
+    password_error(e)
+        .with_detail("service", service)
+        .with_detail("account", account)
+}
+
+/// Like [`password_error`], with the service the error occurred for as the details.
+pub fn password_service_error(e: Error, service: &str) -> NativeError {
+    password_error(e).with_detail("service", service)
+}
+
+pub fn biometric_error(e: Error) -> NativeError {
+    let code = match e {
+        Error::UserCancelled => ErrorCode::BiometricCancelled,
+        Error::AccessDenied(_) => ErrorCode::BiometricAccessDenied,
+        Error::ServiceUnavailable(_) => ErrorCode::BiometricUnavailable,
+        Error::Unsupported(_) => ErrorCode::BiometricUnsupported,
+        Error::Crypto(_) | Error::InvalidCipherString(_) => ErrorCode::BiometricKeyInvalid,
+        // The secrets are read from the password store
+        Error::NotFound(_) => ErrorCode::PasswordNotFound,
+        Error::InvalidInput(_) => ErrorCode::InvalidInput,
+        _ => ErrorCode::BiometricFailed,
+    };
+    NativeError::new(code, e)
+}
+
+pub fn clipboard_error(e: Error) -> NativeError {
+    let code = match e {
+        Error::NotFound(_) => ErrorCode::ClipboardEmpty,
+        Error::ServiceUnavailable(_) => ErrorCode::ClipboardUnavailable,
+        Error::Unsupported(_) => ErrorCode::ClipboardUnsupported,
+        Error::InvalidInput(_) => ErrorCode::InvalidInput,
+        _ => ErrorCode::ClipboardFailed,
+    };
+    NativeError::new(code, e)
+}
+
+/// The errors of a running agent, starting it fails with [`ErrorCode::SshAgentStartFailed`].
+pub fn ssh_agent_error(e: Error) -> NativeError {
+    let code = match e {
+        Error::InvalidState(_) => ErrorCode::SshAgentNotRunning,
+        Error::Unsupported(_) => ErrorCode::SshKeyUnsupported,
+        Error::InvalidInput(_) | Error::InvalidCipherString(_) | Error::Crypto(_) => {
+            ErrorCode::SshKeyInvalid
+        }
+        Error::UserCancelled => ErrorCode::UserCancelled,
+        Error::NotFound(_)
+        | Error::AccessDenied(_)
+        | Error::ServiceUnavailable(_)
+        | Error::Platform(_)
+        | Error::Io(_) => ErrorCode::SshAgentFailed,
+    };
+    NativeError::new(code, e)
+}
+
+pub fn ssh_key_generation_error(e: Error, key_algorithm: &str) -> NativeError {
+    let code = match e {
+        Error::Unsupported(_) => ErrorCode::SshKeyUnsupported,
+        _ => ErrorCode::SshKeyGenerationFailed,
+    };
+    NativeError::new(code, e).with_detail("keyAlgorithm", key_algorithm)
+}
+
+pub fn ssh_key_export_error(e: Error) -> NativeError {
+    let code = match e {
+        Error::Unsupported(_) => ErrorCode::SshKeyUnsupported,
+        Error::InvalidInput(_) => ErrorCode::SshKeyInvalid,
+        _ => ErrorCode::SshKeyExportFailed,
+    };
+    NativeError::new(code, e)
+}
+
+pub fn process_isolation_error(e: Error) -> NativeError {
+    let code = match e {
+        Error::Unsupported(_) => ErrorCode::ProcessIsolationUnsupported,
+        _ => ErrorCode::ProcessIsolationFailed,
+    };
+    NativeError::new(code, e)
+}
+
+pub fn power_monitor_error(e: Error) -> NativeError {
+    NativeError::new(ErrorCode::PowerMonitorUnavailable, e)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes_match_typescript() {
+        let typescript = include_str!("../../../src/platform/utils/native-error.ts");
+        let list = typescript
+            .split_once("NATIVE_ERROR_CODES = [")
+            .and_then(|(_, rest)| rest.split_once(']'))
+            .map(|(list, _)| list)
+            .expect("native-error.ts lists the codes");
+        let typescript_codes: Vec<&str> = list
+            .split(',')
+            .map(|code| code.trim().trim_matches('"'))
+            .filter(|code| !code.is_empty())
+            .collect();
+
+        let codes: Vec<&str> = ErrorCode::ALL.iter().map(ErrorCode::as_str).collect();
+        assert_eq!(codes, typescript_codes);
+    }
+}
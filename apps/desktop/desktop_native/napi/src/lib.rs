@@ -1,99 +1,372 @@
 #[macro_use]
 extern crate napi_derive;
 
+mod error;
 mod registry;
 
 #[napi]
 pub mod passwords {
+    use std::collections::HashMap;
+
+    use napi::{Env, JsObject};
+
+    use crate::error::{
+        password_entry_error, password_error, password_service_error, spawn, NativeError,
+    };
+
     /// Fetch the stored password from the keychain.
-    #[napi]
-    pub async fn get_password(service: String, account: String) -> napi::Result<String> {
-        desktop_core::password::get_password(&service, &account)
-            .map_err(|e| napi::Error::from_reason(e.to_string()))
+    #[napi(ts_return_type = "Promise<string>")]
+    pub fn get_password(env: Env, service: String, account: String) -> napi::Result<JsObject> {
+        spawn(env, async move {
+            desktop_core::password::get_password(&service, &account)
+                .await
+                .map(|password| password.to_string())
+                .map_err(|e| password_entry_error(e, &service, &account))
+        })
     }
 
     /// Fetch the stored password from the keychain that was stored with Keytar.
-    #[napi]
-    pub async fn get_password_keytar(service: String, account: String) -> napi::Result<String> {
-        desktop_core::password::get_password_keytar(&service, &account)
-            .map_err(|e| napi::Error::from_reason(e.to_string()))
+    #[napi(ts_return_type = "Promise<string>")]
+    pub fn get_password_keytar(
+        env: Env,
+        service: String,
+        account: String,
+    ) -> napi::Result<JsObject> {
+        spawn(env, async move {
+            desktop_core::password::get_password_keytar(&service, &account)
+                .await
+                .map(|password| password.to_string())
+                .map_err(|e| password_entry_error(e, &service, &account))
+        })
     }
 
     /// Save the password to the keychain. Adds an entry if none exists otherwise updates the existing entry.
-    #[napi]
-    pub async fn set_password(
+    #[napi(ts_return_type = "Promise<void>")]
+    pub fn set_password(
+        env: Env,
         service: String,
         account: String,
         password: String,
-    ) -> napi::Result<()> {
-        desktop_core::password::set_password(&service, &account, &password)
-            .map_err(|e| napi::Error::from_reason(e.to_string()))
+    ) -> napi::Result<JsObject> {
+        spawn(env, async move {
+            desktop_core::password::set_password(&service, &account, &password)
+                .await
+                .map_err(|e| password_entry_error(e, &service, &account))
+        })
     }
 
     /// Delete the stored password from the keychain.
-    #[napi]
-    pub async fn delete_password(service: String, account: String) -> napi::Result<()> {
-        desktop_core::password::delete_password(&service, &account)
-            .map_err(|e| napi::Error::from_reason(e.to_string()))
+    #[napi(ts_return_type = "Promise<void>")]
+    pub fn delete_password(env: Env, service: String, account: String) -> napi::Result<JsObject> {
+        spawn(env, async move {
+            desktop_core::password::delete_password(&service, &account)
+                .await
+                .map_err(|e| password_entry_error(e, &service, &account))
+        })
+    }
+
+    /// Fetch the stored secret and its metadata from the keychain.
+    #[napi(ts_return_type = "Promise<StoredSecret>")]
+    pub fn get_secret(env: Env, service: String, account: String) -> napi::Result<JsObject> {
+        spawn(env, async move {
+            desktop_core::password::get_secret(&service, &account)
+                .await
+                .map(StoredSecret::from)
+                .map_err(|e| password_entry_error(e, &service, &account))
+        })
+    }
+
+    /// Save the binary secret with its metadata to the keychain. Adds an entry if none exists
+    /// otherwise replaces the existing entry.
+    #[napi(ts_return_type = "Promise<void>")]
+    pub fn set_secret(
+        env: Env,
+        service: String,
+        account: String,
+        secret: napi::bindgen_prelude::Buffer,
+        metadata: Option<SecretMetadata>,
+    ) -> napi::Result<JsObject> {
+        let secret = desktop_core::crypto::SecretBytes::from(secret.to_vec());
+        let metadata = metadata.map(|m| m.into()).unwrap_or_default();
+        spawn(env, async move {
+            desktop_core::password::set_secret(&service, &account, &secret, &metadata)
+                .await
+                .map_err(|e| password_entry_error(e, &service, &account))
+        })
+    }
+
+    #[napi(object)]
+    pub struct SecretMetadata {
+        /// The label shown by keyring managers, `service/account` if unset.
+        pub label: Option<String>,
+        pub attributes: Option<HashMap<String, String>>,
+    }
+
+    impl From<SecretMetadata> for desktop_core::password::SecretMetadata {
+        fn from(metadata: SecretMetadata) -> Self {
+            desktop_core::password::SecretMetadata {
+                label: metadata.label,
+                attributes: metadata.attributes.unwrap_or_default(),
+            }
+        }
+    }
+
+    #[napi(object)]
+    pub struct StoredSecret {
+        pub secret: napi::bindgen_prelude::Buffer,
+        pub label: Option<String>,
+        pub attributes: HashMap<String, String>,
+    }
+
+    impl From<desktop_core::password::StoredSecret> for StoredSecret {
+        fn from(stored: desktop_core::password::StoredSecret) -> Self {
+            StoredSecret {
+                secret: stored.secret.to_vec().into(),
+                label: stored.metadata.label,
+                attributes: stored.metadata.attributes,
+            }
+        }
+    }
+
+    /// List the accounts with a password stored for the service.
+    #[napi(ts_return_type = "Promise<Array<string>>")]
+    pub fn list_accounts(env: Env, service: String) -> napi::Result<JsObject> {
+        spawn(env, async move {
+            desktop_core::password::list_accounts(&service)
+                .await
+                .map_err(|e| password_service_error(e, &service))
+        })
+    }
+
+    /// Delete the stored passwords of all accounts of the service.
+    #[napi(ts_return_type = "Promise<void>")]
+    pub fn delete_all(env: Env, service: String) -> napi::Result<JsObject> {
+        spawn(env, async move {
+            desktop_core::password::delete_all(&service)
+                .await
+                .map_err(|e| password_service_error(e, &service))
+        })
     }
 
     // Checks if the os secure storage is available
+    #[napi(ts_return_type = "Promise<boolean>")]
+    pub fn is_available(env: Env) -> napi::Result<JsObject> {
+        spawn(env, async move {
+            desktop_core::password::is_available()
+                .await
+                .map_err(password_error)
+        })
+    }
+
     #[napi]
-    pub async fn is_available() -> napi::Result<bool> {
-        desktop_core::password::is_available().map_err(|e| napi::Error::from_reason(e.to_string()))
+    pub enum PasswordBackend {
+        /// The secure storage of the OS.
+        Os,
+        /// An encrypted file in the user's config directory.
+        EncryptedFile,
+    }
+
+    /// Select where passwords are stored. The encrypted file is encrypted with a key derived from
+    /// `secret`, or on Linux with a key stored in the kernel keyring if no secret is given.
+    #[napi(ts_return_type = "Promise<void>")]
+    pub fn set_backend(
+        env: Env,
+        backend: PasswordBackend,
+        secret: Option<String>,
+    ) -> napi::Result<JsObject> {
+        spawn(env, select_backend(backend, secret))
+    }
+
+    async fn select_backend(
+        backend: PasswordBackend,
+        secret: Option<String>,
+    ) -> Result<(), NativeError> {
+        use std::sync::Arc;
+
+        use desktop_core::password::{EncryptedFileBackend, FileKey};
+
+        match backend {
+            PasswordBackend::Os => desktop_core::password::reset_backend(),
+            PasswordBackend::EncryptedFile => {
+                let key = match secret {
+                    Some(secret) => FileKey::Secret(secret.into()),
+                    #[cfg(target_os = "linux")]
+                    None => FileKey::KernelKeyring,
+                    #[cfg(not(target_os = "linux"))]
+                    None => {
+                        return Err(NativeError::new(
+                            crate::error::ErrorCode::InvalidInput,
+                            "A secret is required for the encrypted file backend",
+                        ))
+                    }
+                };
+                let backend = tokio::task::spawn_blocking(move || {
+                    EncryptedFileBackend::default_path()
+                        .and_then(|path| EncryptedFileBackend::new(path, key))
+                        .map_err(password_error)
+                })
+                .await
+                .unwrap_or_else(|e| Err(join_error(e)))?;
+                desktop_core::password::set_backend(Arc::new(backend));
+            }
+        }
+        Ok(())
+    }
+
+    // The kernel keyring is only used by the callers which ask for it, the long-lived passwords
+    // stay in the selected backend
+
+    /// Fetch a password stored with `setSessionPassword`.
+    #[napi(ts_return_type = "Promise<string>")]
+    pub fn get_session_password(
+        env: Env,
+        service: String,
+        account: String,
+    ) -> napi::Result<JsObject> {
+        spawn(env, async move {
+            session_keyring()
+                .and_then(|keyring| keyring.get_password(&service, &account))
+                .map(|password| password.to_string())
+                .map_err(|e| password_entry_error(e, &service, &account))
+        })
+    }
+
+    /// Save a short-lived password, e.g. the biometric unlock key, to the session keyring of the
+    /// Linux kernel. It works without a D-Bus session and is cleared on logout. Fails on the
+    /// other platforms.
+    #[napi(ts_return_type = "Promise<void>")]
+    pub fn set_session_password(
+        env: Env,
+        service: String,
+        account: String,
+        password: String,
+    ) -> napi::Result<JsObject> {
+        spawn(env, async move {
+            session_keyring()
+                .and_then(|keyring| keyring.set_password(&service, &account, &password))
+                .map_err(|e| password_entry_error(e, &service, &account))
+        })
+    }
+
+    /// Delete a password stored with `setSessionPassword`.
+    #[napi(ts_return_type = "Promise<void>")]
+    pub fn delete_session_password(
+        env: Env,
+        service: String,
+        account: String,
+    ) -> napi::Result<JsObject> {
+        spawn(env, async move {
+            session_keyring()
+                .and_then(|keyring| keyring.delete_password(&service, &account))
+                .map_err(|e| password_entry_error(e, &service, &account))
+        })
+    }
+
+    /// Checks if the session keyring can be used, false on the platforms other than Linux.
+    #[napi(ts_return_type = "Promise<boolean>")]
+    pub fn is_session_keyring_available(env: Env) -> napi::Result<JsObject> {
+        spawn(env, async move {
+            match session_keyring() {
+                Ok(keyring) => keyring.is_available().map_err(password_error),
+                Err(_) => Ok(false),
+            }
+        })
+    }
+
+    type SessionKeyring = Box<dyn desktop_core::password::PasswordBackend>;
+
+    #[cfg(target_os = "linux")]
+    fn session_keyring() -> desktop_core::error::Result<SessionKeyring> {
+        use desktop_core::password::{KernelKeyringBackend, Keyring};
+
+        Ok(Box::new(KernelKeyringBackend::new(Keyring::Session)))
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn session_keyring() -> desktop_core::error::Result<SessionKeyring> {
+        Err(desktop_core::error::Error::Unsupported(
+            "The kernel keyring is only available on Linux".to_string(),
+        ))
+    }
+
+    fn join_error(e: tokio::task::JoinError) -> NativeError {
+        NativeError::new(crate::error::ErrorCode::PasswordStoreFailed, e)
     }
 }
 
 #[napi]
 pub mod biometrics {
     use desktop_core::biometric::{Biometric, BiometricTrait};
+    use napi::{Env, JsObject};
+
+    use crate::error::{biometric_error, spawn};
 
     // Prompt for biometric confirmation
-    #[napi]
-    pub async fn prompt(
+    #[napi(ts_return_type = "Promise<boolean>")]
+    pub fn prompt(
+        env: Env,
         hwnd: napi::bindgen_prelude::Buffer,
         message: String,
-    ) -> napi::Result<bool> {
-        Biometric::prompt(hwnd.into(), message)
-            .await
-            .map_err(|e| napi::Error::from_reason(e.to_string()))
+    ) -> napi::Result<JsObject> {
+        let hwnd: Vec<u8> = hwnd.into();
+        spawn(env, async move {
+            Biometric::prompt(hwnd, message)
+                .await
+                .map_err(biometric_error)
+        })
     }
 
-    #[napi]
-    pub async fn available() -> napi::Result<bool> {
-        Biometric::available()
-            .await
-            .map_err(|e| napi::Error::from_reason(e.to_string()))
+    #[napi(ts_return_type = "Promise<boolean>")]
+    pub fn available(env: Env) -> napi::Result<JsObject> {
+        spawn(env, async move {
+            Biometric::available().await.map_err(biometric_error)
+        })
     }
 
-    #[napi]
-    pub async fn set_biometric_secret(
+    /// Encrypts and stores the secret, returning the stored value. `iv_b64` must be the `ivB64` of
+    /// the `OsDerivedKey` the os key part of the key material was derived from.
+    #[napi(ts_return_type = "Promise<string>")]
+    pub fn set_biometric_secret(
+        env: Env,
         service: String,
         account: String,
         secret: String,
         key_material: Option<KeyMaterial>,
         iv_b64: String,
-    ) -> napi::Result<String> {
-        Biometric::set_biometric_secret(
-            &service,
-            &account,
-            &secret,
-            key_material.map(|m| m.into()),
-            &iv_b64,
-        )
-        .map_err(|e| napi::Error::from_reason(e.to_string()))
+    ) -> napi::Result<JsObject> {
+        spawn(env, async move {
+            Biometric::set_biometric_secret(
+                &service,
+                &account,
+                &secret,
+                key_material.map(|m| m.into()),
+                &iv_b64,
+            )
+            .await
+            .map_err(biometric_error)
+        })
     }
 
-    #[napi]
-    pub async fn get_biometric_secret(
+    #[napi(ts_return_type = "Promise<string>")]
+    pub fn get_biometric_secret(
+        env: Env,
         service: String,
         account: String,
         key_material: Option<KeyMaterial>,
-    ) -> napi::Result<String> {
-        let result =
+    ) -> napi::Result<JsObject> {
+        spawn(env, async move {
             Biometric::get_biometric_secret(&service, &account, key_material.map(|m| m.into()))
-                .map_err(|e| napi::Error::from_reason(e.to_string()));
-        result
+                .await
+                .map(|secret| secret.to_string())
+                .map_err(biometric_error)
+        })
+    }
+
+    /// Reads the iv of a value returned by `setBiometricSecret`, to pass to `deriveKeyMaterial`
+    /// before getting the secret. Returns null if the value is not an encrypted secret.
+    #[napi]
+    pub fn get_stored_iv(encrypted_secret: String) -> Option<String> {
+        desktop_core::biometric::stored_iv(&encrypted_secret).ok()
     }
 
     /// Derives key material from biometric data. Returns a string encoded with a
@@ -103,11 +376,13 @@ pub mod biometrics {
     /// If the iv is provided, it will be used as the challenge. Otherwise a random challenge will be generated.
     ///
     /// `format!("<key_base64>|<iv_base64>")`
-    #[napi]
-    pub async fn derive_key_material(iv: Option<String>) -> napi::Result<OsDerivedKey> {
-        Biometric::derive_key_material(iv.as_deref())
-            .map(|k| k.into())
-            .map_err(|e| napi::Error::from_reason(e.to_string()))
+    #[napi(ts_return_type = "Promise<OsDerivedKey>")]
+    pub fn derive_key_material(env: Env, iv: Option<String>) -> napi::Result<JsObject> {
+        spawn(env, async move {
+            Biometric::derive_key_material(iv.as_deref())
+                .map(OsDerivedKey::from)
+                .map_err(biometric_error)
+        })
     }
 
     #[napi(object)]
@@ -143,15 +418,216 @@ pub mod biometrics {
 
 #[napi]
 pub mod clipboards {
+    use napi::{Env, JsObject};
+
+    use crate::error::{clipboard_error, spawn, ErrorCode, NativeError};
+
+    #[napi(ts_return_type = "Promise<string>")]
+    pub fn read(env: Env) -> napi::Result<JsObject> {
+        spawn(env, async move {
+            desktop_core::clipboard::read()
+                .await
+                .map_err(clipboard_error)
+        })
+    }
+
+    #[napi(ts_return_type = "Promise<void>")]
+    pub fn write(env: Env, text: String, password: bool) -> napi::Result<JsObject> {
+        spawn(env, async move {
+            desktop_core::clipboard::write(&text, password)
+                .await
+                .map_err(clipboard_error)
+        })
+    }
+
+    #[napi]
+    pub enum ClipboardTarget {
+        /// The regular clipboard, used by copy and paste.
+        Clipboard,
+        /// The PRIMARY selection, pasted with the middle mouse button. Only available on Linux.
+        Primary,
+    }
+
+    impl From<ClipboardTarget> for desktop_core::clipboard::Target {
+        fn from(target: ClipboardTarget) -> Self {
+            match target {
+                ClipboardTarget::Clipboard => desktop_core::clipboard::Target::Clipboard,
+                ClipboardTarget::Primary => desktop_core::clipboard::Target::Primary,
+            }
+        }
+    }
+
     #[napi]
-    pub async fn read() -> napi::Result<String> {
-        desktop_core::clipboard::read().map_err(|e| napi::Error::from_reason(e.to_string()))
+    pub enum ClipboardFormat {
+        Text,
+        Html,
+        Image,
+    }
+
+    impl From<ClipboardFormat> for desktop_core::clipboard::Format {
+        fn from(format: ClipboardFormat) -> Self {
+            match format {
+                ClipboardFormat::Text => desktop_core::clipboard::Format::Text,
+                ClipboardFormat::Html => desktop_core::clipboard::Format::Html,
+                ClipboardFormat::Image => desktop_core::clipboard::Format::Image,
+            }
+        }
+    }
+
+    /// The content of a clipboard, exactly one of `text`, `html` and `image` is set.
+    #[napi(object)]
+    pub struct ClipboardContent {
+        pub text: Option<String>,
+        pub html: Option<String>,
+        /// The plain text offered with `html` to the applications which don't paste HTML.
+        pub alt_text: Option<String>,
+        pub image: Option<ClipboardImage>,
+    }
+
+    /// An image with its pixels in RGBA order, 4 bytes per pixel.
+    #[napi(object)]
+    pub struct ClipboardImage {
+        pub width: u32,
+        pub height: u32,
+        pub bytes: napi::bindgen_prelude::Buffer,
     }
 
+    impl From<desktop_core::clipboard::Content> for ClipboardContent {
+        fn from(content: desktop_core::clipboard::Content) -> Self {
+            use desktop_core::clipboard::Content;
+
+            let mut result = ClipboardContent {
+                text: None,
+                html: None,
+                alt_text: None,
+                image: None,
+            };
+            match content {
+                Content::Text(text) => result.text = Some(text),
+                Content::Html { html, alt_text } => {
+                    result.html = Some(html);
+                    result.alt_text = alt_text;
+                }
+                Content::Image(image) => {
+                    result.image = Some(ClipboardImage {
+                        width: image.width as u32,
+                        height: image.height as u32,
+                        bytes: image.bytes.into(),
+                    })
+                }
+            }
+            result
+        }
+    }
+
+    impl TryFrom<ClipboardContent> for desktop_core::clipboard::Content {
+        type Error = NativeError;
+
+        fn try_from(content: ClipboardContent) -> Result<Self, NativeError> {
+            use desktop_core::clipboard::{Content, Image};
+
+            match (content.text, content.html, content.image) {
+                (Some(text), None, None) => Ok(Content::Text(text)),
+                (None, Some(html), None) => Ok(Content::Html {
+                    html,
+                    alt_text: content.alt_text,
+                }),
+                (None, None, Some(image)) => Ok(Content::Image(Image {
+                    width: image.width as usize,
+                    height: image.height as usize,
+                    bytes: image.bytes.to_vec(),
+                })),
+                _ => Err(NativeError::new(
+                    ErrorCode::InvalidInput,
+                    "Exactly one of text, html and image must be set",
+                )),
+            }
+        }
+    }
+
+    /// Read the content of the target in the format.
+    #[napi(ts_return_type = "Promise<ClipboardContent>")]
+    pub fn read_content(
+        env: Env,
+        target: ClipboardTarget,
+        format: ClipboardFormat,
+    ) -> napi::Result<JsObject> {
+        spawn(env, async move {
+            desktop_core::clipboard::read_content(target.into(), format.into())
+                .await
+                .map(ClipboardContent::from)
+                .map_err(clipboard_error)
+        })
+    }
+
+    /// Replace the content of the target. Passwords are excluded from the clipboard history
+    /// where the platform supports it.
+    #[napi(ts_return_type = "Promise<void>")]
+    pub fn write_content(
+        env: Env,
+        target: ClipboardTarget,
+        content: ClipboardContent,
+        password: bool,
+    ) -> napi::Result<JsObject> {
+        let content = desktop_core::clipboard::Content::try_from(content);
+        spawn(env, async move {
+            desktop_core::clipboard::write_content(target.into(), &content?, password)
+                .await
+                .map_err(clipboard_error)
+        })
+    }
+
+    /// Clear the target, the regular clipboard if unset.
+    #[napi(ts_return_type = "Promise<void>")]
+    pub fn clear(env: Env, target: Option<ClipboardTarget>) -> napi::Result<JsObject> {
+        let target = target.unwrap_or(ClipboardTarget::Clipboard).into();
+        spawn(env, async move {
+            desktop_core::clipboard::clear(target)
+                .await
+                .map_err(clipboard_error)
+        })
+    }
+
+    /// Whether the clipboard still holds the text written last, i.e. no other application
+    /// replaced it. Only supported on Linux, fails with `CLIPBOARD_UNSUPPORTED` on Windows and
+    /// macOS.
+    #[napi(ts_return_type = "Promise<boolean>")]
+    pub fn is_owner(env: Env) -> napi::Result<JsObject> {
+        spawn(env, async move {
+            desktop_core::clipboard::is_owner()
+                .await
+                .map_err(clipboard_error)
+        })
+    }
+
+    /// Write the text and clear the clipboard after `timeout_ms` if it still holds the text.
+    /// Replaces the clear scheduled by the previous call.
+    #[napi(ts_return_type = "Promise<void>")]
+    pub fn write_with_timeout(
+        env: Env,
+        text: String,
+        password: bool,
+        timeout_ms: u32,
+    ) -> napi::Result<JsObject> {
+        let timeout = std::time::Duration::from_millis(timeout_ms.into());
+        spawn(env, async move {
+            desktop_core::clipboard::write_with_timeout(&text, password, timeout)
+                .await
+                .map_err(clipboard_error)
+        })
+    }
+
+    /// Cancel the clear scheduled by `write_with_timeout`, if any.
+    #[napi]
+    pub fn cancel_clear() {
+        desktop_core::clipboard::cancel_clear();
+    }
+
+    /// Run the clear scheduled by `write_with_timeout` now, if any. The clipboard is only cleared
+    /// if it still holds the text.
     #[napi]
-    pub async fn write(text: String, password: bool) -> napi::Result<()> {
-        desktop_core::clipboard::write(&text, password)
-            .map_err(|e| napi::Error::from_reason(e.to_string()))
+    pub fn clear_pending() {
+        desktop_core::clipboard::clear_pending();
     }
 }
 
@@ -162,9 +638,12 @@ pub mod sshagent {
     use napi::{
         bindgen_prelude::Promise,
         threadsafe_function::{ErrorStrategy::CalleeHandled, ThreadsafeFunction},
+        Env, JsObject,
     };
     use tokio::{self, sync::Mutex};
 
+    use crate::error::{spawn, ssh_agent_error, ErrorCode, NativeError};
+
     #[napi]
     pub struct SshAgentState {
         state: desktop_core::ssh_agent::BitwardenDesktopAgent,
@@ -181,7 +660,17 @@ pub mod sshagent {
     pub struct SshKey {
         pub private_key: String,
         pub public_key: String,
+        /// The SSH name of the algorithm, e.g. `ssh-ed25519`
+        pub algorithm: String,
+        /// The size of the key in bits
+        pub key_size: u32,
+        pub comment: String,
+        /// `SHA256:<base64>`
         pub key_fingerprint: String,
+        /// `MD5:<hex>`
+        pub md5_fingerprint: String,
+        /// The randomart of the SHA-256 fingerprint, as shown by `ssh-keygen -lv`
+        pub randomart: String,
     }
 
     impl From<desktop_core::ssh_agent::importer::SshKey> for SshKey {
@@ -189,7 +678,12 @@ pub mod sshagent {
             SshKey {
                 private_key: key.private_key,
                 public_key: key.public_key,
+                algorithm: key.algorithm,
+                key_size: key.key_size,
+                comment: key.comment,
                 key_fingerprint: key.key_fingerprint,
+                md5_fingerprint: key.md5_fingerprint,
+                randomart: key.randomart,
             }
         }
     }
@@ -245,10 +739,17 @@ pub mod sshagent {
         }
     }
 
-    #[napi]
-    pub async fn serve(
+    #[napi(ts_return_type = "Promise<SshAgentState>")]
+    pub fn serve(
+        env: Env,
+        callback: ThreadsafeFunction<String, CalleeHandled>,
+    ) -> napi::Result<JsObject> {
+        spawn(env, start_server(callback))
+    }
+
+    async fn start_server(
         callback: ThreadsafeFunction<String, CalleeHandled>,
-    ) -> napi::Result<SshAgentState> {
+    ) -> Result<SshAgentState, NativeError> {
         let (auth_request_tx, mut auth_request_rx) = tokio::sync::mpsc::channel::<(u32, String)>(32);
         let (auth_response_tx, auth_response_rx) = tokio::sync::broadcast::channel::<(u32, bool)>(32);
         let auth_response_tx_arc = Arc::new(Mutex::new(auth_response_tx));
@@ -296,7 +797,7 @@ pub mod sshagent {
         .await
         {
             Ok(state) => Ok(SshAgentState { state }),
-            Err(e) => Err(napi::Error::from_reason(e.to_string())),
+            Err(e) => Err(NativeError::new(ErrorCode::SshAgentStartFailed, e)),
         }
     }
 
@@ -315,6 +816,7 @@ pub mod sshagent {
 
     #[napi]
     pub fn set_keys(
+        env: Env,
         agent_state: &mut SshAgentState,
         new_keys: Vec<PrivateKey>,
     ) -> napi::Result<()> {
@@ -326,50 +828,152 @@ pub mod sshagent {
                     .map(|k| (k.private_key.clone(), k.name.clone(), k.cipher_id.clone()))
                     .collect(),
             )
-            .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+            .map_err(|e| ssh_agent_error(e).into_napi(&env))?;
         Ok(())
     }
 
     #[napi]
-    pub fn lock(agent_state: &mut SshAgentState) -> napi::Result<()> {
+    pub fn lock(env: Env, agent_state: &mut SshAgentState) -> napi::Result<()> {
         let bitwarden_agent_state = &mut agent_state.state;
         bitwarden_agent_state
             .lock()
-            .map_err(|e| napi::Error::from_reason(e.to_string()))
+            .map_err(|e| ssh_agent_error(e).into_napi(&env))
     }
 
     #[napi]
-    pub fn import_key(encoded_key: String, password: String) -> napi::Result<SshKeyImportResult> {
+    pub fn import_key(
+        env: Env,
+        encoded_key: String,
+        password: String,
+    ) -> napi::Result<SshKeyImportResult> {
         let result = desktop_core::ssh_agent::importer::import_key(encoded_key, password)
-            .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+            .map_err(|e| NativeError::new(ErrorCode::SshKeyInvalid, e).into_napi(&env))?;
         Ok(result.into())
     }
 
-    #[napi]
-    pub async fn generate_keypair(key_algorithm: String) -> napi::Result<SshKey> {
-        desktop_core::ssh_agent::generator::generate_keypair(key_algorithm)
+    #[napi(ts_return_type = "Promise<SshKey>")]
+    pub fn generate_keypair(
+        env: Env,
+        key_algorithm: String,
+        comment: Option<String>,
+    ) -> napi::Result<JsObject> {
+        spawn(env, async move {
+            desktop_core::ssh_agent::generator::generate_keypair(
+                key_algorithm.clone(),
+                comment.unwrap_or_default(),
+            )
             .await
-            .map_err(|e| napi::Error::from_reason(e.to_string()))
-            .map(|k| k.into())
+            .map_err(|e| crate::error::ssh_key_generation_error(e, &key_algorithm))
+            .map(SshKey::from)
+        })
+    }
+
+    #[napi]
+    pub enum SshPrivateKeyFormat {
+        /// OpenSSH, encrypted with bcrypt-pbkdf and AES-256-CTR
+        OpenSsh,
+        /// PKCS#8, encrypted with PBKDF2-HMAC-SHA256 and AES-256-CBC
+        Pkcs8,
+        /// PKCS#1, only for RSA keys and never encrypted
+        Pkcs1,
+        /// PuTTY version 3, encrypted with Argon2id and AES-256-CBC
+        Ppk,
+    }
+
+    impl From<SshPrivateKeyFormat> for desktop_core::ssh_agent::exporter::PrivateKeyFormat {
+        fn from(format: SshPrivateKeyFormat) -> Self {
+            use desktop_core::ssh_agent::exporter::PrivateKeyFormat;
+
+            match format {
+                SshPrivateKeyFormat::OpenSsh => PrivateKeyFormat::OpenSsh,
+                SshPrivateKeyFormat::Pkcs8 => PrivateKeyFormat::Pkcs8,
+                SshPrivateKeyFormat::Pkcs1 => PrivateKeyFormat::Pkcs1,
+                SshPrivateKeyFormat::Ppk => PrivateKeyFormat::Ppk,
+            }
+        }
+    }
+
+    #[napi]
+    pub enum SshPublicKeyFormat {
+        /// `<algorithm> <base64> <comment>`, as in `authorized_keys`
+        OpenSsh,
+        /// `---- BEGIN SSH2 PUBLIC KEY ----`
+        Rfc4716,
+    }
+
+    impl From<SshPublicKeyFormat> for desktop_core::ssh_agent::exporter::PublicKeyFormat {
+        fn from(format: SshPublicKeyFormat) -> Self {
+            use desktop_core::ssh_agent::exporter::PublicKeyFormat;
+
+            match format {
+                SshPublicKeyFormat::OpenSsh => PublicKeyFormat::OpenSsh,
+                SshPublicKeyFormat::Rfc4716 => PublicKeyFormat::Rfc4716,
+            }
+        }
+    }
+
+    /// Export the private key of an `SshKey`, encrypted with the passphrase unless it is empty.
+    /// The rounds set the work factor of the key derivation: the bcrypt-pbkdf rounds for OpenSSH,
+    /// the PBKDF2 iterations for PKCS#8 and the Argon2 passes for PPK, at most 1000, 10000000 and
+    /// 100 respectively.
+    #[napi(ts_return_type = "Promise<string>")]
+    pub fn export_private_key(
+        env: Env,
+        private_key: String,
+        format: SshPrivateKeyFormat,
+        passphrase: String,
+        rounds: Option<u32>,
+    ) -> napi::Result<JsObject> {
+        spawn(env, async move {
+            desktop_core::ssh_agent::exporter::export_private_key(
+                private_key,
+                format.into(),
+                passphrase,
+                rounds,
+            )
+            .map_err(crate::error::ssh_key_export_error)
+        })
+    }
+
+    /// Export the public key of an `SshKey`.
+    #[napi(ts_return_type = "Promise<string>")]
+    pub fn export_public_key(
+        env: Env,
+        public_key: String,
+        format: SshPublicKeyFormat,
+    ) -> napi::Result<JsObject> {
+        spawn(env, async move {
+            desktop_core::ssh_agent::exporter::export_public_key(public_key, format.into())
+                .map_err(crate::error::ssh_key_export_error)
+        })
     }
 }
 
 #[napi]
 pub mod processisolations {
-    #[napi]
-    pub async fn disable_coredumps() -> napi::Result<()> {
-        desktop_core::process_isolation::disable_coredumps()
-            .map_err(|e| napi::Error::from_reason(e.to_string()))
+    use napi::{Env, JsObject};
+
+    use crate::error::{process_isolation_error, spawn};
+
+    #[napi(ts_return_type = "Promise<void>")]
+    pub fn disable_coredumps(env: Env) -> napi::Result<JsObject> {
+        spawn(env, async move {
+            desktop_core::process_isolation::disable_coredumps().map_err(process_isolation_error)
+        })
     }
-    #[napi]
-    pub async fn is_core_dumping_disabled() -> napi::Result<bool> {
-        desktop_core::process_isolation::is_core_dumping_disabled()
-            .map_err(|e| napi::Error::from_reason(e.to_string()))
+    #[napi(ts_return_type = "Promise<boolean>")]
+    pub fn is_core_dumping_disabled(env: Env) -> napi::Result<JsObject> {
+        spawn(env, async move {
+            desktop_core::process_isolation::is_core_dumping_disabled()
+                .map_err(process_isolation_error)
+        })
     }
-    #[napi]
-    pub async fn disable_memory_access() -> napi::Result<()> {
-        desktop_core::process_isolation::disable_memory_access()
-            .map_err(|e| napi::Error::from_reason(e.to_string()))
+    #[napi(ts_return_type = "Promise<void>")]
+    pub fn disable_memory_access(env: Env) -> napi::Result<JsObject> {
+        spawn(env, async move {
+            desktop_core::process_isolation::disable_memory_access()
+                .map_err(process_isolation_error)
+        })
     }
 }
 
@@ -379,21 +983,26 @@ pub mod powermonitors {
         threadsafe_function::{
             ErrorStrategy::CalleeHandled, ThreadsafeFunction, ThreadsafeFunctionCallMode,
         },
-        tokio,
+        tokio, Env, JsObject,
     };
 
-    #[napi]
-    pub async fn on_lock(callback: ThreadsafeFunction<(), CalleeHandled>) -> napi::Result<()> {
-        let (tx, mut rx) = tokio::sync::mpsc::channel::<()>(32);
-        desktop_core::powermonitor::on_lock(tx)
-            .await
-            .map_err(|e| napi::Error::from_reason(e.to_string()))?;
-        tokio::spawn(async move {
-            while let Some(message) = rx.recv().await {
-                callback.call(Ok(message.into()), ThreadsafeFunctionCallMode::NonBlocking);
-            }
-        });
-        Ok(())
+    #[napi(ts_return_type = "Promise<void>")]
+    pub fn on_lock(
+        env: Env,
+        callback: ThreadsafeFunction<(), CalleeHandled>,
+    ) -> napi::Result<JsObject> {
+        crate::error::spawn(env, async move {
+            let (tx, mut rx) = tokio::sync::mpsc::channel::<()>(32);
+            desktop_core::powermonitor::on_lock(tx)
+                .await
+                .map_err(crate::error::power_monitor_error)?;
+            tokio::spawn(async move {
+                while let Some(message) = rx.recv().await {
+                    callback.call(Ok(message.into()), ThreadsafeFunctionCallMode::NonBlocking);
+                }
+            });
+            Ok(())
+        })
     }
 
     #[napi]
@@ -404,26 +1013,42 @@ pub mod powermonitors {
 
 #[napi]
 pub mod windows_registry {
-    #[napi]
-    pub async fn create_key(key: String, subkey: String, value: String) -> napi::Result<()> {
-        crate::registry::create_key(&key, &subkey, &value)
-            .map_err(|e| napi::Error::from_reason(e.to_string()))
+    use napi::{Env, JsObject};
+
+    use crate::error::{spawn, ErrorCode, NativeError};
+
+    #[napi(ts_return_type = "Promise<void>")]
+    pub fn create_key(
+        env: Env,
+        key: String,
+        subkey: String,
+        value: String,
+    ) -> napi::Result<JsObject> {
+        spawn(env, async move {
+            crate::registry::create_key(&key, &subkey, &value)
+                .map_err(|e| NativeError::new(ErrorCode::RegistryFailed, e))
+        })
     }
 
-    #[napi]
-    pub async fn delete_key(key: String, subkey: String) -> napi::Result<()> {
-        crate::registry::delete_key(&key, &subkey)
-            .map_err(|e| napi::Error::from_reason(e.to_string()))
+    #[napi(ts_return_type = "Promise<void>")]
+    pub fn delete_key(env: Env, key: String, subkey: String) -> napi::Result<JsObject> {
+        spawn(env, async move {
+            crate::registry::delete_key(&key, &subkey)
+                .map_err(|e| NativeError::new(ErrorCode::RegistryFailed, e))
+        })
     }
 }
 
 #[napi]
 pub mod ipc {
     use desktop_core::ipc::server::{Message, MessageType};
-    use napi::threadsafe_function::{
-        ErrorStrategy, ThreadsafeFunction, ThreadsafeFunctionCallMode,
+    use napi::{
+        threadsafe_function::{ErrorStrategy, ThreadsafeFunction, ThreadsafeFunctionCallMode},
+        Env, JsObject,
     };
 
+    use crate::error::{ErrorCode, NativeError};
+
     #[napi(object)]
     pub struct IpcMessage {
         pub client_id: u32,
@@ -469,28 +1094,34 @@ pub mod ipc {
         ///
         /// @param name The endpoint name to listen on. This name uniquely identifies the IPC connection and must be the same for both the server and client.
         /// @param callback This function will be called whenever a message is received from a client.
-        #[napi(factory)]
-        pub async fn listen(
+        #[napi(ts_return_type = "Promise<IpcServer>")]
+        pub fn listen(
+            env: Env,
             name: String,
             #[napi(ts_arg_type = "(error: null | Error, message: IpcMessage) => void")]
             callback: ThreadsafeFunction<IpcMessage, ErrorStrategy::CalleeHandled>,
-        ) -> napi::Result<Self> {
-            let (send, mut recv) = tokio::sync::mpsc::channel::<Message>(32);
-            tokio::spawn(async move {
-                while let Some(message) = recv.recv().await {
-                    callback.call(Ok(message.into()), ThreadsafeFunctionCallMode::NonBlocking);
-                }
-            });
+        ) -> napi::Result<JsObject> {
+            crate::error::spawn(env, async move {
+                let (send, mut recv) = tokio::sync::mpsc::channel::<Message>(32);
+                tokio::spawn(async move {
+                    while let Some(message) = recv.recv().await {
+                        callback.call(Ok(message.into()), ThreadsafeFunctionCallMode::NonBlocking);
+                    }
+                });
 
-            let path = desktop_core::ipc::path(&name);
+                let path = desktop_core::ipc::path(&name);
 
-            let server = desktop_core::ipc::server::Server::start(&path, send).map_err(|e| {
-                napi::Error::from_reason(format!(
-                    "Error listening to server - Path: {path:?} - Error: {e} - {e:?}"
-                ))
-            })?;
+                let server =
+                    desktop_core::ipc::server::Server::start(&path, send).map_err(|e| {
+                        NativeError::new(
+                            ErrorCode::IpcBindFailed,
+                            format!("Error listening to server - Path: {path:?} - Error: {e}"),
+                        )
+                        .with_detail("path", path.to_string_lossy())
+                    })?;
 
-            Ok(IpcServer { server })
+                Ok(IpcServer { server })
+            })
         }
 
         /// Return the path to the IPC server.
@@ -511,11 +1142,15 @@ pub mod ipc {
         /// @return The number of clients that the message was sent to. Note that the number of messages
         /// actually received may be less, as some clients could disconnect before receiving the message.
         #[napi]
-        pub fn send(&self, message: String) -> napi::Result<u32> {
+        pub fn send(&self, env: Env, message: String) -> napi::Result<u32> {
             self.server
                 .send(message)
                 .map_err(|e| {
-                    napi::Error::from_reason(format!("Error sending message - Error: {e} - {e:?}"))
+                    NativeError::new(
+                        ErrorCode::IpcSendFailed,
+                        format!("Error sending message - Error: {e}"),
+                    )
+                    .into_napi(&env)
                 })
                 // NAPI doesn't support u64 or usize, so we need to convert to u32
                 .map(|u| u32::try_from(u).unwrap_or_default())
@@ -52,7 +52,6 @@ import { MessagingService } from "@bitwarden/common/platform/abstractions/messag
 import { PlatformUtilsService } from "@bitwarden/common/platform/abstractions/platform-utils.service";
 import { SdkService } from "@bitwarden/common/platform/abstractions/sdk/sdk.service";
 import { StateService } from "@bitwarden/common/platform/abstractions/state.service";
-import { SystemService } from "@bitwarden/common/platform/abstractions/system.service";
 import { clearCaches } from "@bitwarden/common/platform/misc/sequentialize";
 import { StateEventRunnerService } from "@bitwarden/common/platform/state";
 import { SyncService } from "@bitwarden/common/platform/sync";
@@ -152,7 +151,6 @@ export class AppComponent implements OnInit, OnDestroy {
     private searchService: SearchService,
     private notificationsService: NotificationsService,
     private platformUtilsService: PlatformUtilsService,
-    private systemService: SystemService,
     private processReloadService: ProcessReloadServiceAbstraction,
     private stateService: StateService,
     private eventUploadService: EventUploadService,
@@ -236,7 +234,7 @@ export class AppComponent implements OnInit, OnDestroy {
             // FIXME: Verify that this floating promise is intentional. If it is, add an explanatory comment and ensure there is proper error handling.
             // eslint-disable-next-line @typescript-eslint/no-floating-promises
             this.updateAppMenu();
-            await this.systemService.clearPendingClipboard();
+            await ipc.platform.clipboard.clearPending();
             await this.processReloadService.startProcessReload(this.authService);
             break;
           case "authBlocked":
@@ -280,7 +278,7 @@ export class AppComponent implements OnInit, OnDestroy {
             // eslint-disable-next-line @typescript-eslint/no-floating-promises
             this.notificationsService.updateConnection();
             await this.updateAppMenu();
-            await this.systemService.clearPendingClipboard();
+            await ipc.platform.clipboard.clearPending();
             await this.processReloadService.startProcessReload(this.authService);
             break;
           case "startProcessReload":
@@ -329,13 +327,6 @@ export class AppComponent implements OnInit, OnDestroy {
           case "showToast":
             this.toastService._showToast(message);
             break;
-          case "copiedToClipboard":
-            if (!message.clearing) {
-              // FIXME: Verify that this floating promise is intentional. If it is, add an explanatory comment and ensure there is proper error handling.
-              // eslint-disable-next-line @typescript-eslint/no-floating-promises
-              this.systemService.clearClipboard(message.clipboardValue, message.clearMs);
-            }
-            break;
           case "ssoCallback": {
             const queryParams = {
               code: message.code,
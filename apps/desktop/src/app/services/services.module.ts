@@ -156,7 +156,7 @@ const safeProviders: SafeProvider[] = [
   safeProvider({
     provide: PlatformUtilsServiceAbstraction,
     useClass: ElectronPlatformUtilsService,
-    deps: [I18nServiceAbstraction, MessagingServiceAbstraction],
+    deps: [I18nServiceAbstraction, MessagingServiceAbstraction, AutofillSettingsServiceAbstraction],
   }),
   safeProvider({
     // We manually override the value of SUPPORTS_SECURE_STORAGE here to avoid
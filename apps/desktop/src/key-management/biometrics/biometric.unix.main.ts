@@ -1,7 +1,6 @@
 import { spawn } from "child_process";
 
 import { I18nService } from "@bitwarden/common/platform/abstractions/i18n.service";
-import { EncString } from "@bitwarden/common/platform/models/domain/enc-string";
 import { biometrics, passwords } from "@bitwarden/desktop-napi";
 
 import { WindowMain } from "../../main/window.main";
@@ -72,8 +71,7 @@ export default class BiometricUnixMain implements OsBiometricService {
     if (value == null || value == "") {
       return null;
     } else {
-      const encValue = new EncString(value);
-      this.setIv(encValue.iv);
+      this.setIv(biometrics.getStoredIv(value));
       const storageDetails = await this.getStorageDetails({ clientKeyHalfB64: clientKeyPartB64 });
       const storedValue = await biometrics.getBiometricSecret(
         service,
@@ -132,7 +130,7 @@ export default class BiometricUnixMain implements OsBiometricService {
 
   // Nulls out key material in order to force a re-derive. This should only be used in getBiometricKey
   // when we want to force a re-derive of the key material.
-  private setIv(iv: string) {
+  private setIv(iv: string | null) {
     this._iv = iv;
     this._osKeyHalf = null;
   }
@@ -1,6 +1,5 @@
 import { I18nService } from "@bitwarden/common/platform/abstractions/i18n.service";
 import { LogService } from "@bitwarden/common/platform/abstractions/log.service";
-import { EncString } from "@bitwarden/common/platform/models/domain/enc-string";
 import { SymmetricCryptoKey } from "@bitwarden/common/platform/models/domain/symmetric-crypto-key";
 import { biometrics, passwords } from "@bitwarden/desktop-napi";
 
@@ -36,7 +35,10 @@ export default class BiometricWindowsMain implements OsBiometricService {
 
     if (value == null || value == "") {
       return null;
-    } else if (!EncString.isSerializedEncString(value)) {
+    }
+
+    const iv = biometrics.getStoredIv(value);
+    if (iv == null) {
       // Update to format encrypted with client key half
       const storageDetails = await this.getStorageDetails({
         clientKeyHalfB64,
@@ -51,8 +53,7 @@ export default class BiometricWindowsMain implements OsBiometricService {
       );
       return value;
     } else {
-      const encValue = new EncString(value);
-      this.setIv(encValue.iv);
+      this.setIv(iv);
       const storageDetails = await this.getStorageDetails({
         clientKeyHalfB64,
       });
@@ -79,10 +80,9 @@ export default class BiometricWindowsMain implements OsBiometricService {
       storageDetails.key_material,
       storageDetails.ivB64,
     );
-    const parsedStoredValue = new EncString(storedValue);
     await this.storeValueWitness(
       parsedValue,
-      parsedStoredValue,
+      biometrics.getStoredIv(storedValue),
       service,
       storageKey,
       clientKeyPartB64,
@@ -131,25 +131,25 @@ export default class BiometricWindowsMain implements OsBiometricService {
    * Stores a witness key alongside the encrypted value. This is used to determine if the value is up to date.
    *
    * @param unencryptedValue The key to store
-   * @param encryptedValue The encrypted value of the key to store. Used to sync IV of the witness key with the stored key.
+   * @param ivB64 The IV of the encrypted value of the key to store. Used to sync IV of the witness key with the stored key.
    * @param service The service to store the witness key under
    * @param storageKey The key to store the witness key under. The witness key will be stored under storageKey + {@link KEY_WITNESS_SUFFIX}
    * @returns
    */
   private async storeValueWitness(
     unencryptedValue: SymmetricCryptoKey,
-    encryptedValue: EncString,
+    ivB64: string | null,
     service: string,
     storageKey: string,
     clientKeyPartB64: string,
   ) {
-    if (encryptedValue.iv == null || encryptedValue == null) {
+    if (ivB64 == null) {
       return;
     }
 
     const storageDetails = {
       keyMaterial: this.witnessKeyMaterial(unencryptedValue, clientKeyPartB64),
-      ivB64: encryptedValue.iv,
+      ivB64,
     };
     await biometrics.setBiometricSecret(
       service,
@@ -11,7 +11,18 @@ export class ClipboardMain {
     });
 
     ipcMain.handle("clipboard.write", async (_event: any, message: ClipboardWriteMessage) => {
+      if (message.clearMs != null) {
+        return await clipboards.writeWithTimeout(
+          message.text,
+          message.password ?? false,
+          message.clearMs,
+        );
+      }
       return await clipboards.write(message.text, message.password ?? false);
     });
+
+    ipcMain.handle("clipboard.clearPending", async (_event: any, _message: any) => {
+      clipboards.clearPending();
+    });
   }
 }
@@ -5,6 +5,7 @@ import { ConsoleLogService } from "@bitwarden/common/platform/services/console-l
 import { passwords } from "@bitwarden/desktop-napi";
 
 import { DesktopBiometricsService } from "../../key-management/biometrics/index";
+import { parseNativeError } from "../utils/native-error";
 
 const AuthRequiredSuffix = "_biometric";
 
@@ -39,10 +40,7 @@ export class DesktopCredentialStorageListener {
         }
         return val;
       } catch (e) {
-        if (
-          e.message === "Password not found." ||
-          e.message === "The specified item could not be found in the keychain."
-        ) {
+        if (parseNativeError(e)?.code === "PASSWORD_NOT_FOUND") {
           return null;
         }
         this.logService.info(e);
@@ -39,6 +39,7 @@ const passwords = {
 const clipboard = {
   read: (): Promise<string> => ipcRenderer.invoke("clipboard.read"),
   write: (message: ClipboardWriteMessage) => ipcRenderer.invoke("clipboard.write", message),
+  clearPending: (): Promise<void> => ipcRenderer.invoke("clipboard.clearPending"),
 };
 
 const sshAgent = {
@@ -1,3 +1,6 @@
+import { firstValueFrom } from "rxjs";
+
+import { AutofillSettingsServiceAbstraction } from "@bitwarden/common/autofill/services/autofill-settings.service";
 import { ClientType, DeviceType } from "@bitwarden/common/enums";
 import { I18nService } from "@bitwarden/common/platform/abstractions/i18n.service";
 import { MessagingService } from "@bitwarden/common/platform/abstractions/messaging.service";
@@ -14,6 +17,7 @@ export class ElectronPlatformUtilsService implements PlatformUtilsService {
   constructor(
     protected i18nService: I18nService,
     private messagingService: MessagingService,
+    private autofillSettingsService: AutofillSettingsServiceAbstraction,
   ) {}
 
   getDevice(): DeviceType {
@@ -108,23 +112,36 @@ export class ElectronPlatformUtilsService implements PlatformUtilsService {
   }
 
   copyToClipboard(text: string, options?: ClipboardOptions): void {
+    // Callers don't wait for the clipboard, like the other clients
+    // eslint-disable-next-line @typescript-eslint/no-floating-promises
+    this.writeToClipboard(text, options);
+  }
+
+  // The main process clears the clipboard after the delay, so that it is also cleared when the
+  // renderer is reloaded or closed in the meantime
+  private async writeToClipboard(text: string, options?: ClipboardOptions): Promise<void> {
     const clearing = options?.clearing === true;
-    const clearMs = options?.clearMs ?? null;
+    const clearMs = clearing ? null : await this.getClearClipboardMs(text, options?.clearMs);
 
-    // FIXME: Verify that this floating promise is intentional. If it is, add an explanatory comment and ensure there is proper error handling.
-    // eslint-disable-next-line @typescript-eslint/no-floating-promises
-    ipc.platform.clipboard.write({
+    await ipc.platform.clipboard.write({
       text: text,
       password: (options?.allowHistory ?? false) === false, // default to false
+      clearMs: clearMs ?? undefined,
     } satisfies ClipboardWriteMessage);
+  }
 
-    if (!clearing) {
-      this.messagingService.send("copiedToClipboard", {
-        clipboardValue: text,
-        clearMs: clearMs,
-        clearing: clearing,
-      });
+  // The delay after which the copied text is cleared, the one set by the user unless the caller
+  // gives one
+  private async getClearClipboardMs(text: string, clearMs?: number): Promise<number | null> {
+    if (text == null || text.trim() === "") {
+      return null;
     }
+    if (clearMs) {
+      return clearMs;
+    }
+
+    const delayInSeconds = await firstValueFrom(this.autofillSettingsService.clearClipboardDelay$);
+    return delayInSeconds ? delayInSeconds * 1000 : null;
   }
 
   readFromClipboard(): Promise<string> {
@@ -1,4 +1,6 @@
 export type ClipboardWriteMessage = {
   text: string;
   password?: boolean;
+  /** Clear the clipboard after this many milliseconds, if it still holds the text. */
+  clearMs?: number;
 };
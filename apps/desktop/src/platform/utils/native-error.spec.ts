@@ -0,0 +1,37 @@
+import { parseNativeError } from "./native-error";
+
+function nativeError(code: unknown, message: string, details?: Record<string, string>) {
+  return Object.assign(new Error(message), details ? { code, details } : { code });
+}
+
+describe("parseNativeError", () => {
+  it("reads the code, message and details", () => {
+    const error = nativeError("PASSWORD_NOT_FOUND", "No password found", {
+      account: "a",
+      service: "s",
+    });
+
+    expect(parseNativeError(error)).toEqual({
+      code: "PASSWORD_NOT_FOUND",
+      message: "No password found",
+      details: { account: "a", service: "s" },
+    });
+  });
+
+  it("reads errors without details", () => {
+    const error = nativeError("BIOMETRIC_CANCELLED", "The operation was cancelled by the user");
+
+    expect(parseNativeError(error)).toEqual({
+      code: "BIOMETRIC_CANCELLED",
+      message: "The operation was cancelled by the user",
+    });
+  });
+
+  it("returns null for other errors", () => {
+    expect(parseNativeError(new Error("Password not found."))).toBeNull();
+    expect(parseNativeError(nativeError("GenericFailure", "napi status"))).toBeNull();
+    expect(parseNativeError(nativeError("ENOENT", "no such file"))).toBeNull();
+    expect(parseNativeError("PASSWORD_NOT_FOUND")).toBeNull();
+    expect(parseNativeError(null)).toBeNull();
+  });
+});
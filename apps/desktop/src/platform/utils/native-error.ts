@@ -0,0 +1,65 @@
+/**
+ * Stable error codes thrown by `@bitwarden/desktop-napi`.
+ *
+ * Keep in sync with `ErrorCode` in `desktop_native/napi/src/error.rs`, which is checked by its tests.
+ */
+export const NATIVE_ERROR_CODES = [
+  "INVALID_INPUT",
+  "USER_CANCELLED",
+  "PASSWORD_NOT_FOUND",
+  "PASSWORD_ACCESS_DENIED",
+  "PASSWORD_STORE_UNAVAILABLE",
+  "PASSWORD_STORE_FAILED",
+  "BIOMETRIC_CANCELLED",
+  "BIOMETRIC_ACCESS_DENIED",
+  "BIOMETRIC_UNAVAILABLE",
+  "BIOMETRIC_UNSUPPORTED",
+  "BIOMETRIC_KEY_INVALID",
+  "BIOMETRIC_FAILED",
+  "CLIPBOARD_EMPTY",
+  "CLIPBOARD_UNAVAILABLE",
+  "CLIPBOARD_UNSUPPORTED",
+  "CLIPBOARD_FAILED",
+  "SSH_AGENT_NOT_RUNNING",
+  "SSH_AGENT_START_FAILED",
+  "SSH_AGENT_FAILED",
+  "SSH_KEY_UNSUPPORTED",
+  "SSH_KEY_INVALID",
+  "SSH_KEY_GENERATION_FAILED",
+  "SSH_KEY_EXPORT_FAILED",
+  "PROCESS_ISOLATION_UNSUPPORTED",
+  "PROCESS_ISOLATION_FAILED",
+  "POWER_MONITOR_UNAVAILABLE",
+  "IPC_BIND_FAILED",
+  "IPC_SEND_FAILED",
+  "REGISTRY_FAILED",
+] as const;
+
+export type NativeErrorCode = (typeof NATIVE_ERROR_CODES)[number];
+
+export type NativeError = {
+  code: NativeErrorCode;
+  message: string;
+  details?: Record<string, string>;
+};
+
+/**
+ * Reads the code, message and details of an error thrown by `@bitwarden/desktop-napi`.
+ * @returns The error, or null if the error was not thrown by the native module.
+ */
+export function parseNativeError(e: unknown): NativeError | null {
+  if (!(e instanceof Error)) {
+    return null;
+  }
+
+  const { code, details } = e as Error & { code?: unknown; details?: unknown };
+  if (!NATIVE_ERROR_CODES.includes(code as NativeErrorCode)) {
+    return null;
+  }
+
+  const error: NativeError = { code: code as NativeErrorCode, message: e.message };
+  if (typeof details === "object" && details !== null) {
+    error.details = details as Record<string, string>;
+  }
+  return error;
+}